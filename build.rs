@@ -0,0 +1,50 @@
+// Compiles the GLSL shaders into the SPIR-V files next to them, the same ones shaders/compile.sh builds.
+// Without glslc on the PATH the build goes on and the renderer uses whatever SPIR-V is already there.
+
+use std::path::Path;
+use std::process::Command;
+
+const SHADERS: [(&str, &str); 5] = [
+    ("shader.vert", "vert.spv"),
+    ("shader.frag", "frag.spv"),
+    ("triangle.vert", "triangle_vert.spv"),
+    ("triangle.frag", "triangle_frag.spv"),
+    ("vertexbuffer.vert", "vertexbuffer_vert.spv"),
+];
+
+fn main() {
+    let shader_dir = Path::new("shaders");
+    for (source, _) in SHADERS {
+        println!(
+            "cargo:rerun-if-changed={}",
+            shader_dir.join(source).display()
+        );
+    }
+    if let Err(error) = Command::new("glslc").arg("--version").output() {
+        let missing: Vec<_> = SHADERS
+            .iter()
+            .map(|(_, output)| *output)
+            .filter(|output| !shader_dir.join(output).exists())
+            .collect();
+        if !missing.is_empty() {
+            println!(
+                "cargo:warning=Couldn't run glslc ({}), {} won't be built",
+                error,
+                missing.join(", ")
+            );
+        }
+        return;
+    }
+    for (source, output) in SHADERS {
+        let source = shader_dir.join(source);
+        let status = Command::new("glslc")
+            .arg(&source)
+            .arg("-o")
+            .arg(shader_dir.join(output))
+            .status()
+            .expect("glslc ran a moment ago");
+        if !status.success() {
+            panic!("glslc failed to compile {}", source.display());
+        }
+    }
+}
@@ -1,4 +1,5 @@
-use ash::{vk, Entry};
+use vulkanrust::config::RendererConfig;
+use vulkanrust::Result;
 use winit::{
     dpi::PhysicalSize,
     event::{Event, WindowEvent},
@@ -6,28 +7,22 @@ use winit::{
     window::WindowBuilder,
 };
 
-const WIDTH: u32 = 800;
-const HEIGHT: u32 = 600;
-
-pub struct HelloTriangleApplication {
+struct HelloTriangleApplication {
     event_loop: winit::event_loop::EventLoop<()>,
     window: winit::window::Window,
 }
 
 impl HelloTriangleApplication {
-    pub fn new() -> Self {
+    fn new() -> Result<Self> {
         let event_loop = EventLoop::new();
+        let config = RendererConfig::default();
         let window = WindowBuilder::new()
             .with_resizable(false)
-            .with_inner_size(PhysicalSize::new(WIDTH, HEIGHT))
-            .build(&event_loop)
-            .unwrap();
-        Self {
-            event_loop: event_loop,
-            window: window,
-        }
+            .with_inner_size(PhysicalSize::new(config.width, config.height))
+            .build(&event_loop)?;
+        Ok(Self { event_loop, window })
     }
-    pub fn run(self) {
+    fn run(self) -> ! {
         self.event_loop.run(move |event, _, control_flow| {
             *control_flow = ControlFlow::Wait;
 
@@ -41,3 +36,8 @@ impl HelloTriangleApplication {
         });
     }
 }
+
+fn main() -> Result<()> {
+    let app = HelloTriangleApplication::new()?;
+    app.run();
+}
@@ -0,0 +1,402 @@
+use ash::extensions::khr::{Surface, Swapchain};
+use ash::{vk, Entry};
+use vulkanrust::config::RendererConfig;
+use vulkanrust::{commands, device, instance, pipeline, surface, swapchain};
+use vulkanrust::{Error, Result};
+use winit::{
+    dpi::PhysicalSize,
+    event::{Event, WindowEvent},
+    event_loop::{ControlFlow, EventLoop},
+    window::WindowBuilder,
+};
+
+const MAX_FRAMES_IN_FLIGHT: usize = 2;
+
+struct VulkanDetails {
+    entry: ash::Entry,
+    instance: ash::Instance,
+    debug_messenger: vk::DebugUtilsMessengerEXT,
+    surface: vk::SurfaceKHR,
+    physical_device: vk::PhysicalDevice,
+    device: ash::Device,
+    graphics_queue: vk::Queue,
+    present_queue: vk::Queue,
+    swap_chain: vk::SwapchainKHR,
+    swap_chain_images: Vec<vk::Image>,
+    swap_chain_image_format: vk::Format,
+    swap_chain_extent: vk::Extent2D,
+    swap_chain_image_views: Vec<vk::ImageView>,
+    render_pass: vk::RenderPass,
+    pipeline_layout: vk::PipelineLayout,
+    graphics_pipeline: vk::Pipeline,
+    swap_chain_framebuffers: Vec<vk::Framebuffer>,
+    command_pool: vk::CommandPool,
+    command_buffers: Vec<vk::CommandBuffer>,
+    image_available_semaphores: Vec<vk::Semaphore>,
+    render_finished_semaphores: Vec<vk::Semaphore>,
+    in_flight_fences: Vec<vk::Fence>,
+    framebuffer_resized: bool,
+    current_frame: usize,
+}
+
+struct HelloTriangleApplication {
+    event_loop: winit::event_loop::EventLoop<()>,
+    window: winit::window::Window,
+    vulkan_details: VulkanDetails,
+}
+
+impl VulkanDetails {
+    fn new(window: &winit::window::Window) -> Result<Self> {
+        let entry = Entry::linked();
+        let config = RendererConfig::default();
+        let instance = instance::create_instance(&entry, Some(window), &config)?;
+        let debug_messenger = instance::create_debug_messenger(&entry, &instance, &config.debug)?;
+        let surface = surface::create_surface(window, &entry, &instance)?;
+        let physical_device = device::pick_physical_device(&entry, &instance, &surface, &config)?;
+        let device = device::create_logical_device(&entry, &instance, &physical_device, &surface)?;
+        let indices = device::find_queue_familes(&entry, &instance, &physical_device, &surface)?;
+        let graphics_queue = unsafe {
+            device.get_device_queue(indices.graphics.ok_or(Error::NoSuitableDevice)? as u32, 0)
+        };
+        let present_queue = unsafe {
+            device.get_device_queue(indices.present.ok_or(Error::NoSuitableDevice)? as u32, 0)
+        };
+        let (swap_chain, swap_chain_images, swap_chain_image_format, swap_chain_extent) =
+            swapchain::create_swap_chain(
+                window,
+                &entry,
+                &instance,
+                &physical_device,
+                &device,
+                &surface,
+                vk::PresentModeKHR::MAILBOX,
+            )?;
+        let swap_chain_image_views =
+            swapchain::create_image_views(&device, &swap_chain_images, &swap_chain_image_format)?;
+        let render_pass = pipeline::create_render_pass(
+            &device,
+            &swap_chain_image_format,
+            vk::ImageLayout::PRESENT_SRC_KHR,
+            None,
+            vk::SampleCountFlags::TYPE_1,
+        )?;
+        let (pipeline_layout, graphics_pipeline) = pipeline::create_graphics_pipeline(
+            &device,
+            &render_pass,
+            &pipeline::GraphicsPipelineInfo {
+                vert_shader_path: "shaders/triangle_vert.spv",
+                frag_shader_path: "shaders/triangle_frag.spv",
+                vertex_binding_descriptions: &[],
+                vertex_attribute_descriptions: &[],
+                descriptor_set_layouts: &[],
+                push_constant_ranges: &[],
+                max_push_constants_size: device::max_push_constants_size(
+                    &instance,
+                    &physical_device,
+                ),
+                depth: None,
+                samples: vk::SampleCountFlags::TYPE_1,
+                min_sample_shading: None,
+            },
+        )?;
+        let swap_chain_framebuffers = swapchain::create_framebuffers(
+            &device,
+            &swap_chain_image_views,
+            &swap_chain_extent,
+            &render_pass,
+            None,
+            None,
+        )?;
+        let command_pool =
+            commands::create_command_pool(&entry, &instance, &physical_device, &device, &surface)?;
+        let command_buffers =
+            commands::create_command_buffers(&device, &command_pool, MAX_FRAMES_IN_FLIGHT)?;
+        let (image_available_semaphores, render_finished_semaphores, in_flight_fences) =
+            commands::create_sync_objects(&device, MAX_FRAMES_IN_FLIGHT)?;
+        Ok(Self {
+            entry,
+            instance,
+            debug_messenger,
+            surface,
+            physical_device,
+            device,
+            graphics_queue,
+            present_queue,
+            swap_chain,
+            swap_chain_images,
+            swap_chain_image_format,
+            swap_chain_extent,
+            swap_chain_image_views,
+            render_pass,
+            pipeline_layout,
+            graphics_pipeline,
+            swap_chain_framebuffers,
+            command_pool,
+            command_buffers,
+            image_available_semaphores,
+            render_finished_semaphores,
+            in_flight_fences,
+            framebuffer_resized: false,
+            current_frame: 0,
+        })
+    }
+    fn record_command_buffer(&self, image_index: usize) -> Result<()> {
+        let command_buffer = self.command_buffers[self.current_frame];
+        let begin_info = vk::CommandBufferBeginInfo {
+            s_type: vk::StructureType::COMMAND_BUFFER_BEGIN_INFO,
+            ..Default::default()
+        };
+        let clear_color = vk::ClearValue {
+            color: vk::ClearColorValue {
+                float32: [0.0, 0.0, 0.0, 1.0],
+            },
+        };
+        let render_pass_info = vk::RenderPassBeginInfo {
+            s_type: vk::StructureType::RENDER_PASS_BEGIN_INFO,
+            render_pass: self.render_pass,
+            framebuffer: self.swap_chain_framebuffers[image_index],
+            render_area: vk::Rect2D {
+                offset: vk::Offset2D { x: 0, y: 0 },
+                extent: self.swap_chain_extent,
+            },
+            clear_value_count: 1,
+            p_clear_values: &clear_color,
+            ..Default::default()
+        };
+        let viewport = vk::Viewport {
+            x: 0.0,
+            y: 0.0,
+            width: self.swap_chain_extent.width as f32,
+            height: self.swap_chain_extent.height as f32,
+            min_depth: 0.0,
+            max_depth: 1.0,
+        };
+        let scissor = vk::Rect2D {
+            offset: vk::Offset2D { x: 0, y: 0 },
+            extent: self.swap_chain_extent,
+        };
+        unsafe {
+            self.device
+                .begin_command_buffer(command_buffer, &begin_info)?;
+            self.device.cmd_begin_render_pass(
+                command_buffer,
+                &render_pass_info,
+                vk::SubpassContents::INLINE,
+            );
+            self.device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.graphics_pipeline,
+            );
+            self.device.cmd_set_viewport(command_buffer, 0, &[viewport]);
+            self.device.cmd_set_scissor(command_buffer, 0, &[scissor]);
+            self.device.cmd_draw(command_buffer, 3, 1, 0, 0);
+            self.device.cmd_end_render_pass(command_buffer);
+            self.device.end_command_buffer(command_buffer)?;
+        }
+        Ok(())
+    }
+    fn draw_frame(&mut self, window: &winit::window::Window) -> Result<()> {
+        unsafe {
+            self.device.wait_for_fences(
+                &[self.in_flight_fences[self.current_frame]],
+                true,
+                u64::MAX,
+            )?;
+            let swap_chain_handle = Swapchain::new(&self.instance, &self.device);
+            let (image_index, _) = match swap_chain_handle.acquire_next_image(
+                self.swap_chain,
+                u64::MAX,
+                self.image_available_semaphores[self.current_frame],
+                vk::Fence::null(),
+            ) {
+                Ok(value) => value,
+                Err(error) => match error {
+                    vk::Result::ERROR_OUT_OF_DATE_KHR => {
+                        return self.recreate_swap_chain(window);
+                    }
+                    _ => return Err(error.into()),
+                },
+            };
+            self.device
+                .reset_fences(&[self.in_flight_fences[self.current_frame]])?;
+            self.device.reset_command_buffer(
+                self.command_buffers[self.current_frame],
+                vk::CommandBufferResetFlags::empty(),
+            )?;
+            self.record_command_buffer(image_index as usize)?;
+            let submit_info = vk::SubmitInfo {
+                s_type: vk::StructureType::SUBMIT_INFO,
+                wait_semaphore_count: 1,
+                p_wait_semaphores: [self.image_available_semaphores[self.current_frame]].as_ptr(),
+                p_wait_dst_stage_mask: [vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT].as_ptr(),
+                command_buffer_count: 1,
+                p_command_buffers: [self.command_buffers[self.current_frame]].as_ptr(),
+                signal_semaphore_count: 1,
+                p_signal_semaphores: [self.render_finished_semaphores[self.current_frame]].as_ptr(),
+                ..Default::default()
+            };
+            self.device.queue_submit(
+                self.graphics_queue,
+                &[submit_info],
+                self.in_flight_fences[self.current_frame],
+            )?;
+            let present_info = vk::PresentInfoKHR {
+                s_type: vk::StructureType::PRESENT_INFO_KHR,
+                wait_semaphore_count: 1,
+                p_wait_semaphores: [self.render_finished_semaphores[self.current_frame]].as_ptr(),
+                swapchain_count: 1,
+                p_swapchains: [self.swap_chain].as_ptr(),
+                p_image_indices: &image_index,
+                ..Default::default()
+            };
+            match swap_chain_handle.queue_present(self.present_queue, &present_info) {
+                Ok(should_recreate) => {
+                    if should_recreate || self.framebuffer_resized {
+                        self.framebuffer_resized = false;
+                        self.recreate_swap_chain(window)?;
+                    }
+                }
+                Err(error) => match error {
+                    vk::Result::ERROR_OUT_OF_DATE_KHR => self.recreate_swap_chain(window)?,
+                    _ => return Err(error.into()),
+                },
+            };
+            self.current_frame = (self.current_frame + 1) % MAX_FRAMES_IN_FLIGHT;
+        }
+        Ok(())
+    }
+    fn cleanup_swap_chain(&mut self) {
+        unsafe {
+            for framebuffer in &self.swap_chain_framebuffers {
+                self.device.destroy_framebuffer(*framebuffer, None);
+            }
+            for image_view in &self.swap_chain_image_views {
+                self.device.destroy_image_view(*image_view, None);
+            }
+            Swapchain::new(&self.instance, &self.device).destroy_swapchain(self.swap_chain, None);
+        }
+    }
+    fn recreate_swap_chain(&mut self, window: &winit::window::Window) -> Result<()> {
+        unsafe { self.device.device_wait_idle()? };
+
+        self.cleanup_swap_chain();
+
+        (
+            self.swap_chain,
+            self.swap_chain_images,
+            self.swap_chain_image_format,
+            self.swap_chain_extent,
+        ) = swapchain::create_swap_chain(
+            window,
+            &self.entry,
+            &self.instance,
+            &self.physical_device,
+            &self.device,
+            &self.surface,
+            vk::PresentModeKHR::MAILBOX,
+        )?;
+
+        self.swap_chain_image_views = swapchain::create_image_views(
+            &self.device,
+            &self.swap_chain_images,
+            &self.swap_chain_image_format,
+        )?;
+
+        self.swap_chain_framebuffers = swapchain::create_framebuffers(
+            &self.device,
+            &self.swap_chain_image_views,
+            &self.swap_chain_extent,
+            &self.render_pass,
+            None,
+            None,
+        )?;
+        Ok(())
+    }
+    fn cleanup(&mut self) {
+        unsafe {
+            self.cleanup_swap_chain();
+            self.device.destroy_pipeline(self.graphics_pipeline, None);
+            self.device
+                .destroy_pipeline_layout(self.pipeline_layout, None);
+            self.device.destroy_render_pass(self.render_pass, None);
+            for i in 0..MAX_FRAMES_IN_FLIGHT {
+                self.device
+                    .destroy_semaphore(self.image_available_semaphores[i], None);
+                self.device
+                    .destroy_semaphore(self.render_finished_semaphores[i], None);
+                self.device.destroy_fence(self.in_flight_fences[i], None);
+            }
+            self.device.destroy_command_pool(self.command_pool, None);
+            self.device.destroy_device(None);
+            instance::destroy_debug_messenger(&self.entry, &self.instance, self.debug_messenger);
+            Surface::new(&self.entry, &self.instance).destroy_surface(self.surface, None);
+            self.instance.destroy_instance(None);
+        }
+    }
+}
+
+impl HelloTriangleApplication {
+    fn new() -> Result<Self> {
+        let (event_loop, window) = HelloTriangleApplication::init_window()?;
+        let vulkan_details = VulkanDetails::new(&window)?;
+        Ok(Self {
+            event_loop,
+            window,
+            vulkan_details,
+        })
+    }
+    fn run(mut self) -> ! {
+        self.event_loop.run(move |event, _, control_flow| {
+            *control_flow = ControlFlow::Poll;
+
+            match event {
+                Event::WindowEvent {
+                    event: WindowEvent::CloseRequested,
+                    window_id,
+                } if window_id == self.window.id() => *control_flow = ControlFlow::Exit,
+                Event::WindowEvent {
+                    event: WindowEvent::Resized(size),
+                    window_id,
+                } if window_id == self.window.id() => {
+                    self.vulkan_details.framebuffer_resized = true;
+                    if size.width > 0 && size.height > 0 {
+                        if let Err(error) = self.vulkan_details.draw_frame(&self.window) {
+                            log::error!("Unable to draw a frame: {}", error);
+                            *control_flow = ControlFlow::ExitWithCode(1);
+                        }
+                    }
+                }
+                Event::LoopDestroyed => {
+                    if let Err(error) = unsafe { self.vulkan_details.device.device_wait_idle() } {
+                        log::error!("Unable to wait for the device to go idle: {}", error);
+                    }
+                    self.vulkan_details.cleanup();
+                }
+                _ => {
+                    if self.window.inner_size().width > 0 && self.window.inner_size().height > 0 {
+                        if let Err(error) = self.vulkan_details.draw_frame(&self.window) {
+                            log::error!("Unable to draw a frame: {}", error);
+                            *control_flow = ControlFlow::ExitWithCode(1);
+                        }
+                    }
+                }
+            }
+        });
+    }
+    fn init_window() -> Result<(winit::event_loop::EventLoop<()>, winit::window::Window)> {
+        let event_loop = EventLoop::new();
+        let config = RendererConfig::default();
+        let window = WindowBuilder::new()
+            .with_resizable(true)
+            .with_inner_size(PhysicalSize::new(config.width, config.height))
+            .build(&event_loop)?;
+        Ok((event_loop, window))
+    }
+}
+
+fn main() -> Result<()> {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
+    let app = HelloTriangleApplication::new()?;
+    app.run();
+}
@@ -0,0 +1,171 @@
+use ash::extensions::khr::{Surface, Swapchain};
+use ash::{vk, Entry};
+use vulkanrust::config::RendererConfig;
+use vulkanrust::{device, instance, pipeline, surface, swapchain};
+use vulkanrust::{Error, Result};
+use winit::{
+    dpi::PhysicalSize,
+    event::{Event, WindowEvent},
+    event_loop::{ControlFlow, EventLoop},
+    window::WindowBuilder,
+};
+
+struct VulkanDetails {
+    entry: ash::Entry,
+    instance: ash::Instance,
+    debug_messenger: vk::DebugUtilsMessengerEXT,
+    surface: vk::SurfaceKHR,
+    _physical_device: vk::PhysicalDevice,
+    device: ash::Device,
+    _graphics_queue: vk::Queue,
+    _present_queue: vk::Queue,
+    swap_chain: vk::SwapchainKHR,
+    _swap_chain_images: Vec<vk::Image>,
+    _swap_chain_image_format: vk::Format,
+    _swap_chain_extent: vk::Extent2D,
+    swap_chain_image_views: Vec<vk::ImageView>,
+    render_pass: vk::RenderPass,
+    pipeline_layout: vk::PipelineLayout,
+    graphics_pipeline: vk::Pipeline,
+}
+
+struct HelloTriangleApplication {
+    event_loop: winit::event_loop::EventLoop<()>,
+    window: winit::window::Window,
+    vulkan_details: VulkanDetails,
+}
+
+impl VulkanDetails {
+    fn new(window: &winit::window::Window) -> Result<Self> {
+        let entry = Entry::linked();
+        let config = RendererConfig::default();
+        let instance = instance::create_instance(&entry, Some(window), &config)?;
+        let debug_messenger = instance::create_debug_messenger(&entry, &instance, &config.debug)?;
+        let surface = surface::create_surface(window, &entry, &instance)?;
+        let physical_device = device::pick_physical_device(&entry, &instance, &surface, &config)?;
+        let device = device::create_logical_device(&entry, &instance, &physical_device, &surface)?;
+        let indices = device::find_queue_familes(&entry, &instance, &physical_device, &surface)?;
+        let graphics_queue = unsafe {
+            device.get_device_queue(indices.graphics.ok_or(Error::NoSuitableDevice)? as u32, 0)
+        };
+        let present_queue = unsafe {
+            device.get_device_queue(indices.present.ok_or(Error::NoSuitableDevice)? as u32, 0)
+        };
+        let (swap_chain, swap_chain_images, swap_chain_image_format, swap_chain_extent) =
+            swapchain::create_swap_chain(
+                window,
+                &entry,
+                &instance,
+                &physical_device,
+                &device,
+                &surface,
+                vk::PresentModeKHR::MAILBOX,
+            )?;
+        let swap_chain_image_views =
+            swapchain::create_image_views(&device, &swap_chain_images, &swap_chain_image_format)?;
+        let render_pass = pipeline::create_render_pass(
+            &device,
+            &swap_chain_image_format,
+            vk::ImageLayout::PRESENT_SRC_KHR,
+            None,
+            vk::SampleCountFlags::TYPE_1,
+        )?;
+        // The triangle is hardcoded in the vertex shader, so there are no vertex inputs or descriptors yet
+        let (pipeline_layout, graphics_pipeline) = pipeline::create_graphics_pipeline(
+            &device,
+            &render_pass,
+            &pipeline::GraphicsPipelineInfo {
+                vert_shader_path: "shaders/triangle_vert.spv",
+                frag_shader_path: "shaders/triangle_frag.spv",
+                vertex_binding_descriptions: &[],
+                vertex_attribute_descriptions: &[],
+                descriptor_set_layouts: &[],
+                push_constant_ranges: &[],
+                max_push_constants_size: device::max_push_constants_size(
+                    &instance,
+                    &physical_device,
+                ),
+                depth: None,
+                samples: vk::SampleCountFlags::TYPE_1,
+                min_sample_shading: None,
+            },
+        )?;
+        Ok(Self {
+            entry,
+            instance,
+            debug_messenger,
+            surface,
+            _physical_device: physical_device,
+            device,
+            _graphics_queue: graphics_queue,
+            _present_queue: present_queue,
+            swap_chain,
+            _swap_chain_images: swap_chain_images,
+            _swap_chain_image_format: swap_chain_image_format,
+            _swap_chain_extent: swap_chain_extent,
+            swap_chain_image_views,
+            render_pass,
+            pipeline_layout,
+            graphics_pipeline,
+        })
+    }
+    fn cleanup(&mut self) {
+        unsafe {
+            self.device.destroy_pipeline(self.graphics_pipeline, None);
+            self.device
+                .destroy_pipeline_layout(self.pipeline_layout, None);
+            self.device.destroy_render_pass(self.render_pass, None);
+            for image_view in &self.swap_chain_image_views {
+                self.device.destroy_image_view(*image_view, None);
+            }
+            Swapchain::new(&self.instance, &self.device).destroy_swapchain(self.swap_chain, None);
+            self.device.destroy_device(None);
+            instance::destroy_debug_messenger(&self.entry, &self.instance, self.debug_messenger);
+            Surface::new(&self.entry, &self.instance).destroy_surface(self.surface, None);
+            self.instance.destroy_instance(None);
+        }
+    }
+}
+
+impl HelloTriangleApplication {
+    fn new() -> Result<Self> {
+        let (event_loop, window) = HelloTriangleApplication::init_window()?;
+        let vulkan_details = VulkanDetails::new(&window)?;
+        Ok(Self {
+            event_loop,
+            window,
+            vulkan_details,
+        })
+    }
+    fn run(mut self) -> ! {
+        self.event_loop.run(move |event, _, control_flow| {
+            *control_flow = ControlFlow::Wait;
+
+            match event {
+                Event::WindowEvent {
+                    event: WindowEvent::CloseRequested,
+                    window_id,
+                } if window_id == self.window.id() => {
+                    self.vulkan_details.cleanup();
+                    *control_flow = ControlFlow::Exit
+                }
+                _ => (),
+            }
+        });
+    }
+    fn init_window() -> Result<(winit::event_loop::EventLoop<()>, winit::window::Window)> {
+        let event_loop = EventLoop::new();
+        let config = RendererConfig::default();
+        let window = WindowBuilder::new()
+            .with_resizable(false)
+            .with_inner_size(PhysicalSize::new(config.width, config.height))
+            .build(&event_loop)?;
+        Ok((event_loop, window))
+    }
+}
+
+fn main() -> Result<()> {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
+    let app = HelloTriangleApplication::new()?;
+    app.run();
+}
@@ -0,0 +1,73 @@
+use ash::{vk, Entry};
+use vulkanrust::config::RendererConfig;
+use vulkanrust::instance;
+use vulkanrust::Result;
+use winit::{
+    dpi::PhysicalSize,
+    event::{Event, WindowEvent},
+    event_loop::{ControlFlow, EventLoop},
+    window::WindowBuilder,
+};
+
+struct HelloTriangleApplication {
+    entry: ash::Entry,
+    instance: ash::Instance,
+    debug_messenger: vk::DebugUtilsMessengerEXT,
+}
+
+impl HelloTriangleApplication {
+    fn new() -> Result<Self> {
+        let entry = Entry::linked();
+        let config = RendererConfig::default();
+        // No surface is made in this chapter, so the instance needs no surface extensions
+        let instance = instance::create_instance(&entry, None, &config)?;
+        let debug_messenger = instance::create_debug_messenger(&entry, &instance, &config.debug)?;
+        Ok(Self {
+            entry,
+            instance,
+            debug_messenger,
+        })
+    }
+    fn init_window() -> Result<(winit::event_loop::EventLoop<()>, winit::window::Window)> {
+        let event_loop = EventLoop::new();
+        let config = RendererConfig::default();
+        let window = WindowBuilder::new()
+            .with_resizable(false)
+            .with_inner_size(PhysicalSize::new(config.width, config.height))
+            .build(&event_loop)?;
+        Ok((event_loop, window))
+    }
+    fn cleanup(&mut self) {
+        unsafe {
+            instance::destroy_debug_messenger(&self.entry, &self.instance, self.debug_messenger);
+            self.instance.destroy_instance(None);
+        }
+    }
+    fn run(
+        mut self,
+        event_loop: winit::event_loop::EventLoop<()>,
+        window: winit::window::Window,
+    ) -> ! {
+        event_loop.run(move |event, _, control_flow| {
+            *control_flow = ControlFlow::Wait;
+
+            match event {
+                Event::WindowEvent {
+                    event: WindowEvent::CloseRequested,
+                    window_id,
+                } if window_id == window.id() => {
+                    self.cleanup();
+                    *control_flow = ControlFlow::Exit
+                }
+                _ => (),
+            }
+        });
+    }
+}
+
+fn main() -> Result<()> {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
+    let app = HelloTriangleApplication::new()?;
+    let (event_loop, window) = HelloTriangleApplication::init_window()?;
+    app.run(event_loop, window);
+}
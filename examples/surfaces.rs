@@ -0,0 +1,108 @@
+use ash::extensions::khr::Surface;
+use ash::{vk, Entry};
+use vulkanrust::config::RendererConfig;
+use vulkanrust::{device, instance, surface};
+use vulkanrust::{Error, Result};
+use winit::{
+    dpi::PhysicalSize,
+    event::{Event, WindowEvent},
+    event_loop::{ControlFlow, EventLoop},
+    window::WindowBuilder,
+};
+
+struct VulkanDetails {
+    entry: ash::Entry,
+    instance: ash::Instance,
+    debug_messenger: vk::DebugUtilsMessengerEXT,
+    surface: vk::SurfaceKHR,
+    _physical_device: vk::PhysicalDevice,
+    device: ash::Device,
+    _graphics_queue: vk::Queue,
+    _present_queue: vk::Queue,
+}
+
+struct HelloTriangleApplication {
+    event_loop: winit::event_loop::EventLoop<()>,
+    window: winit::window::Window,
+    vulkan_details: VulkanDetails,
+}
+
+impl VulkanDetails {
+    fn new(window: &winit::window::Window) -> Result<Self> {
+        let entry = Entry::linked();
+        let config = RendererConfig::default();
+        let instance = instance::create_instance(&entry, Some(window), &config)?;
+        let debug_messenger = instance::create_debug_messenger(&entry, &instance, &config.debug)?;
+        let surface = surface::create_surface(window, &entry, &instance)?;
+        let physical_device = device::pick_physical_device(&entry, &instance, &surface, &config)?;
+        let device = device::create_logical_device(&entry, &instance, &physical_device, &surface)?;
+        let indices = device::find_queue_familes(&entry, &instance, &physical_device, &surface)?;
+        let graphics_queue = unsafe {
+            device.get_device_queue(indices.graphics.ok_or(Error::NoSuitableDevice)? as u32, 0)
+        };
+        let present_queue = unsafe {
+            device.get_device_queue(indices.present.ok_or(Error::NoSuitableDevice)? as u32, 0)
+        };
+        Ok(Self {
+            entry,
+            instance,
+            debug_messenger,
+            surface,
+            _physical_device: physical_device,
+            device,
+            _graphics_queue: graphics_queue,
+            _present_queue: present_queue,
+        })
+    }
+    fn cleanup(&mut self) {
+        unsafe {
+            self.device.destroy_device(None);
+            instance::destroy_debug_messenger(&self.entry, &self.instance, self.debug_messenger);
+            Surface::new(&self.entry, &self.instance).destroy_surface(self.surface, None);
+            self.instance.destroy_instance(None);
+        }
+    }
+}
+
+impl HelloTriangleApplication {
+    fn new() -> Result<Self> {
+        let (event_loop, window) = HelloTriangleApplication::init_window()?;
+        let vulkan_details = VulkanDetails::new(&window)?;
+        Ok(Self {
+            event_loop,
+            window,
+            vulkan_details,
+        })
+    }
+    fn run(mut self) -> ! {
+        self.event_loop.run(move |event, _, control_flow| {
+            *control_flow = ControlFlow::Wait;
+
+            match event {
+                Event::WindowEvent {
+                    event: WindowEvent::CloseRequested,
+                    window_id,
+                } if window_id == self.window.id() => {
+                    self.vulkan_details.cleanup();
+                    *control_flow = ControlFlow::Exit
+                }
+                _ => (),
+            }
+        });
+    }
+    fn init_window() -> Result<(winit::event_loop::EventLoop<()>, winit::window::Window)> {
+        let event_loop = EventLoop::new();
+        let config = RendererConfig::default();
+        let window = WindowBuilder::new()
+            .with_resizable(false)
+            .with_inner_size(PhysicalSize::new(config.width, config.height))
+            .build(&event_loop)?;
+        Ok((event_loop, window))
+    }
+}
+
+fn main() -> Result<()> {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
+    let app = HelloTriangleApplication::new()?;
+    app.run();
+}
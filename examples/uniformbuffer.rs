@@ -0,0 +1,9 @@
+use vulkanrust::config::RendererConfig;
+use vulkanrust::renderer::HelloTriangleApplication;
+use vulkanrust::Result;
+
+fn main() -> Result<()> {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
+    let app = HelloTriangleApplication::new(&RendererConfig::default())?;
+    app.run();
+}
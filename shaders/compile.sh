@@ -0,0 +1,9 @@
+#!/bin/sh
+# Compiles the shaders into the SPIR-V files loaded by the renderer and the examples, cargo build
+# does the same through build.rs whenever glslc is on the PATH
+cd "$(dirname "$0")" || exit 1
+glslc shader.vert -o vert.spv
+glslc shader.frag -o frag.spv
+glslc triangle.vert -o triangle_vert.spv
+glslc triangle.frag -o triangle_frag.spv
+glslc vertexbuffer.vert -o vertexbuffer_vert.spv
@@ -1,9 +1,16 @@
 #version 450
 
+layout(push_constant) uniform PushConstants {
+    vec4 tint;
+} object;
+
+layout(binding = 2) uniform sampler2D texSampler;
+
 layout(location = 0) in vec3 fragColor;
+layout(location = 1) in vec2 fragTexCoord;
 
 layout(location = 0) out vec4 outColor;
 
 void main() {
-    outColor = vec4(fragColor, 1.0);
-}
\ No newline at end of file
+    outColor = texture(texSampler, fragTexCoord) * vec4(fragColor, 1.0) * object.tint;
+}
@@ -0,0 +1,22 @@
+#version 450
+
+layout(location = 0) out vec3 fragColor;
+
+vec2 positions[3] = vec2[](
+    vec2(0.0, -0.5),
+    vec2(0.5, 0.5),
+    vec2(-0.5, 0.5)
+);
+
+vec3 colors[3] = vec3[](
+    vec3(1.0, 0.0, 0.0),
+    vec3(0.0, 1.0, 0.0),
+    vec3(0.0, 0.0, 1.0)
+);
+
+// The renderer draws this through the quad's six indices, wrapping them turns the second
+// triangle into a line of zero area that covers nothing
+void main() {
+    gl_Position = vec4(positions[gl_VertexIndex % 3], 0.0, 1.0);
+    fragColor = colors[gl_VertexIndex % 3];
+}
@@ -0,0 +1,498 @@
+// Reports what every Vulkan device can do, as text or as JSON for attaching to bug reports.
+// Pass --surface to open a hidden window and also report what the devices can present to it.
+use ash::extensions::khr::Surface;
+use ash::{vk, Entry};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::ffi::CStr;
+use std::fmt::Debug;
+use vulkanrust::config::RendererConfig;
+use vulkanrust::device::{self, DeviceInfo, FEATURE_NAMES};
+use vulkanrust::swapchain::SwapchainSupportDetails;
+use vulkanrust::{instance, surface, Result};
+use winit::event_loop::EventLoop;
+use winit::window::WindowBuilder;
+
+#[derive(Serialize)]
+struct DeviceReport {
+    index: usize,
+    name: String,
+    device_type: String,
+    vendor_id: String,
+    vendor: Option<&'static str>,
+    device_id: String,
+    api_version: String,
+    driver_version: u32,
+    driver: Option<DriverReport>,
+    // Acceptance by the same checks the renderer uses to pick a device
+    suitable: bool,
+    rejected_because: Option<String>,
+    limits: Map<String, Value>,
+    features: Map<String, Value>,
+    memory_heaps: Vec<MemoryHeapReport>,
+    memory_types: Vec<MemoryTypeReport>,
+    queue_families: Vec<QueueFamilyReport>,
+    extensions: Vec<ExtensionReport>,
+    surface: Option<SurfaceReport>,
+}
+
+#[derive(Serialize)]
+struct DriverReport {
+    id: String,
+    name: String,
+    info: String,
+}
+
+#[derive(Serialize)]
+struct MemoryHeapReport {
+    size: vk::DeviceSize,
+    flags: String,
+}
+
+#[derive(Serialize)]
+struct MemoryTypeReport {
+    heap_index: u32,
+    property_flags: String,
+}
+
+#[derive(Serialize)]
+struct QueueFamilyReport {
+    index: usize,
+    queue_flags: String,
+    queue_count: u32,
+    timestamp_valid_bits: u32,
+    min_image_transfer_granularity: [u32; 3],
+    // Only known when there is a surface to present to
+    present: Option<bool>,
+}
+
+#[derive(Serialize)]
+struct ExtensionReport {
+    name: String,
+    spec_version: u32,
+}
+
+#[derive(Serialize)]
+struct SurfaceReport {
+    min_image_count: u32,
+    max_image_count: u32,
+    current_extent: [u32; 2],
+    min_image_extent: [u32; 2],
+    max_image_extent: [u32; 2],
+    supported_usage_flags: String,
+    supported_composite_alpha: String,
+    formats: Vec<SurfaceFormatReport>,
+    present_modes: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct SurfaceFormatReport {
+    format: String,
+    color_space: String,
+}
+
+// Numbers and arrays of numbers print as valid JSON with {:?}, everything else is kept as its debug string
+fn debug_value<T: Debug>(value: &T) -> Value {
+    let text = format!("{:?}", value);
+    serde_json::from_str(&text).unwrap_or(Value::String(text))
+}
+
+macro_rules! limits {
+    ($limits:expr, $($name:ident),* $(,)?) => {{
+        let mut map = Map::new();
+        $(map.insert(stringify!($name).to_string(), debug_value(&$limits.$name));)*
+        map
+    }};
+}
+
+fn version_string(version: u32) -> String {
+    format!(
+        "{}.{}.{}",
+        vk::api_version_major(version),
+        vk::api_version_minor(version),
+        vk::api_version_patch(version)
+    )
+}
+
+fn report_limits(limits: &vk::PhysicalDeviceLimits) -> Map<String, Value> {
+    limits!(
+        limits,
+        max_image_dimension1_d,
+        max_image_dimension2_d,
+        max_image_dimension3_d,
+        max_image_dimension_cube,
+        max_image_array_layers,
+        max_texel_buffer_elements,
+        max_uniform_buffer_range,
+        max_storage_buffer_range,
+        max_push_constants_size,
+        max_memory_allocation_count,
+        max_sampler_allocation_count,
+        buffer_image_granularity,
+        sparse_address_space_size,
+        max_bound_descriptor_sets,
+        max_per_stage_descriptor_samplers,
+        max_per_stage_descriptor_uniform_buffers,
+        max_per_stage_descriptor_storage_buffers,
+        max_per_stage_descriptor_sampled_images,
+        max_per_stage_descriptor_storage_images,
+        max_per_stage_descriptor_input_attachments,
+        max_per_stage_resources,
+        max_descriptor_set_samplers,
+        max_descriptor_set_uniform_buffers,
+        max_descriptor_set_uniform_buffers_dynamic,
+        max_descriptor_set_storage_buffers,
+        max_descriptor_set_storage_buffers_dynamic,
+        max_descriptor_set_sampled_images,
+        max_descriptor_set_storage_images,
+        max_descriptor_set_input_attachments,
+        max_vertex_input_attributes,
+        max_vertex_input_bindings,
+        max_vertex_input_attribute_offset,
+        max_vertex_input_binding_stride,
+        max_vertex_output_components,
+        max_tessellation_generation_level,
+        max_tessellation_patch_size,
+        max_tessellation_control_per_vertex_input_components,
+        max_tessellation_control_per_vertex_output_components,
+        max_tessellation_control_per_patch_output_components,
+        max_tessellation_control_total_output_components,
+        max_tessellation_evaluation_input_components,
+        max_tessellation_evaluation_output_components,
+        max_geometry_shader_invocations,
+        max_geometry_input_components,
+        max_geometry_output_components,
+        max_geometry_output_vertices,
+        max_geometry_total_output_components,
+        max_fragment_input_components,
+        max_fragment_output_attachments,
+        max_fragment_dual_src_attachments,
+        max_fragment_combined_output_resources,
+        max_compute_shared_memory_size,
+        max_compute_work_group_count,
+        max_compute_work_group_invocations,
+        max_compute_work_group_size,
+        sub_pixel_precision_bits,
+        sub_texel_precision_bits,
+        mipmap_precision_bits,
+        max_draw_indexed_index_value,
+        max_draw_indirect_count,
+        max_sampler_lod_bias,
+        max_sampler_anisotropy,
+        max_viewports,
+        max_viewport_dimensions,
+        viewport_bounds_range,
+        viewport_sub_pixel_bits,
+        min_memory_map_alignment,
+        min_texel_buffer_offset_alignment,
+        min_uniform_buffer_offset_alignment,
+        min_storage_buffer_offset_alignment,
+        min_texel_offset,
+        max_texel_offset,
+        min_texel_gather_offset,
+        max_texel_gather_offset,
+        min_interpolation_offset,
+        max_interpolation_offset,
+        sub_pixel_interpolation_offset_bits,
+        max_framebuffer_width,
+        max_framebuffer_height,
+        max_framebuffer_layers,
+        framebuffer_color_sample_counts,
+        framebuffer_depth_sample_counts,
+        framebuffer_stencil_sample_counts,
+        framebuffer_no_attachments_sample_counts,
+        max_color_attachments,
+        sampled_image_color_sample_counts,
+        sampled_image_integer_sample_counts,
+        sampled_image_depth_sample_counts,
+        sampled_image_stencil_sample_counts,
+        storage_image_sample_counts,
+        max_sample_mask_words,
+        timestamp_compute_and_graphics,
+        timestamp_period,
+        max_clip_distances,
+        max_cull_distances,
+        max_combined_clip_and_cull_distances,
+        discrete_queue_priorities,
+        point_size_range,
+        line_width_range,
+        point_size_granularity,
+        line_width_granularity,
+        strict_lines,
+        standard_sample_locations,
+        optimal_buffer_copy_offset_alignment,
+        optimal_buffer_copy_row_pitch_alignment,
+        non_coherent_atom_size,
+    )
+}
+
+fn report_features(features: &vk::PhysicalDeviceFeatures) -> Map<String, Value> {
+    FEATURE_NAMES
+        .iter()
+        .zip(device::feature_bits(features))
+        .map(|(name, bit)| (name.to_string(), Value::Bool(*bit == vk::TRUE)))
+        .collect()
+}
+
+fn report_device(
+    entry: &ash::Entry,
+    instance: &ash::Instance,
+    physical_device: &vk::PhysicalDevice,
+    index: usize,
+    surface: &vk::SurfaceKHR,
+    config: &RendererConfig,
+) -> Result<DeviceReport> {
+    let info = DeviceInfo::new(entry, instance, physical_device, index)?;
+    let properties = unsafe { instance.get_physical_device_properties(*physical_device) };
+    let features = unsafe { instance.get_physical_device_features(*physical_device) };
+    let memory_properties =
+        unsafe { instance.get_physical_device_memory_properties(*physical_device) };
+    let queue_family_properties =
+        unsafe { instance.get_physical_device_queue_family_properties(*physical_device) };
+    let extension_properties =
+        unsafe { instance.enumerate_device_extension_properties(*physical_device)? };
+    let rejected_because =
+        device::unsuitable_reason(entry, instance, physical_device, surface, config)?;
+    let has_surface = *surface != vk::SurfaceKHR::null();
+    let surface_interface = Surface::new(entry, instance);
+    let mut queue_families = Vec::new();
+    for (index, queue_family) in queue_family_properties.iter().enumerate() {
+        let granularity = queue_family.min_image_transfer_granularity;
+        queue_families.push(QueueFamilyReport {
+            index,
+            queue_flags: format!("{:?}", queue_family.queue_flags),
+            queue_count: queue_family.queue_count,
+            timestamp_valid_bits: queue_family.timestamp_valid_bits,
+            min_image_transfer_granularity: [
+                granularity.width,
+                granularity.height,
+                granularity.depth,
+            ],
+            present: if has_surface {
+                Some(unsafe {
+                    surface_interface.get_physical_device_surface_support(
+                        *physical_device,
+                        index as u32,
+                        *surface,
+                    )?
+                })
+            } else {
+                None
+            },
+        });
+    }
+    let surface_report = if has_surface {
+        let support = SwapchainSupportDetails::new(entry, instance, physical_device, surface)?;
+        let capabilities = support.capabilities;
+        Some(SurfaceReport {
+            min_image_count: capabilities.min_image_count,
+            max_image_count: capabilities.max_image_count,
+            current_extent: [
+                capabilities.current_extent.width,
+                capabilities.current_extent.height,
+            ],
+            min_image_extent: [
+                capabilities.min_image_extent.width,
+                capabilities.min_image_extent.height,
+            ],
+            max_image_extent: [
+                capabilities.max_image_extent.width,
+                capabilities.max_image_extent.height,
+            ],
+            supported_usage_flags: format!("{:?}", capabilities.supported_usage_flags),
+            supported_composite_alpha: format!("{:?}", capabilities.supported_composite_alpha),
+            formats: support
+                .formats
+                .iter()
+                .map(|format| SurfaceFormatReport {
+                    format: format!("{:?}", format.format),
+                    color_space: format!("{:?}", format.color_space),
+                })
+                .collect(),
+            present_modes: support
+                .present_modes
+                .iter()
+                .map(|mode| format!("{:?}", mode))
+                .collect(),
+        })
+    } else {
+        None
+    };
+    Ok(DeviceReport {
+        index,
+        name: info.name.clone(),
+        device_type: format!("{:?}", info.device_type),
+        vendor_id: format!("{:#06x}", info.vendor_id),
+        vendor: info.vendor_name(),
+        device_id: format!("{:#06x}", info.device_id),
+        api_version: version_string(info.api_version),
+        driver_version: info.driver_version,
+        driver: info.driver.map(|driver| DriverReport {
+            id: format!("{:?}", driver.id),
+            name: driver.name,
+            info: driver.info,
+        }),
+        suitable: rejected_because.is_none(),
+        rejected_because,
+        limits: report_limits(&properties.limits),
+        features: report_features(&features),
+        memory_heaps: memory_properties.memory_heaps
+            [..memory_properties.memory_heap_count as usize]
+            .iter()
+            .map(|heap| MemoryHeapReport {
+                size: heap.size,
+                flags: format!("{:?}", heap.flags),
+            })
+            .collect(),
+        memory_types: memory_properties.memory_types
+            [..memory_properties.memory_type_count as usize]
+            .iter()
+            .map(|memory_type| MemoryTypeReport {
+                heap_index: memory_type.heap_index,
+                property_flags: format!("{:?}", memory_type.property_flags),
+            })
+            .collect(),
+        queue_families,
+        extensions: extension_properties
+            .iter()
+            .map(|extension| ExtensionReport {
+                name: unsafe { CStr::from_ptr(extension.extension_name.as_ptr()) }
+                    .to_string_lossy()
+                    .into_owned(),
+                spec_version: extension.spec_version,
+            })
+            .collect(),
+        surface: surface_report,
+    })
+}
+
+// Prints the report as an indented outline, the same shape as the JSON without the punctuation
+fn print_text(key: &str, value: &Value, depth: usize) {
+    let indent = "  ".repeat(depth);
+    match value {
+        Value::Object(map) => {
+            println!("{}{}:", indent, key);
+            for (key, value) in map {
+                print_text(key, value, depth + 1);
+            }
+        }
+        Value::Array(items) if items.iter().any(|item| item.is_object()) => {
+            println!("{}{}: {} entries", indent, key, items.len());
+            for (index, item) in items.iter().enumerate() {
+                print_text(&format!("[{}]", index), item, depth + 1);
+            }
+        }
+        Value::Array(items) => {
+            let items: Vec<String> = items.iter().map(text_scalar).collect();
+            println!("{}{}: [{}]", indent, key, items.join(", "));
+        }
+        _ => println!("{}{}: {}", indent, key, text_scalar(value)),
+    }
+}
+
+fn text_scalar(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        Value::Null => "-".to_string(),
+        _ => value.to_string(),
+    }
+}
+
+fn run(args: &[String]) -> Result<Value> {
+    let mut config = RendererConfig::from_args(args)?;
+    // Every device is reported, the selection only decides which ones the renderer would accept
+    let selector = config.device.take();
+    let entry = Entry::linked();
+    let event_loop;
+    let window = if args.iter().any(|arg| arg == "--surface") {
+        event_loop = EventLoop::new();
+        Some(
+            WindowBuilder::new()
+                .with_visible(false)
+                .with_title("devinfo")
+                .build(&event_loop)?,
+        )
+    } else {
+        None
+    };
+    let instance = instance::create_instance(&entry, window.as_ref(), &config)?;
+    let debug_messenger = instance::create_debug_messenger(&entry, &instance, &config.debug)?;
+    let surface = match &window {
+        Some(window) => surface::create_surface(window, &entry, &instance)?,
+        None => vk::SurfaceKHR::null(),
+    };
+    let result = (|| -> Result<Value> {
+        let mut devices = Vec::new();
+        for (index, physical_device) in unsafe { instance.enumerate_physical_devices()? }
+            .iter()
+            .enumerate()
+        {
+            let mut report =
+                report_device(&entry, &instance, physical_device, index, &surface, &config)?;
+            if let Some(selector) = &selector {
+                let info = DeviceInfo::new(&entry, &instance, physical_device, index)?;
+                if report.suitable && !selector.matches(&info) {
+                    report.suitable = false;
+                    report.rejected_because = Some(format!("not selected by {}", selector));
+                }
+            }
+            devices.push(report);
+        }
+        Ok(serde_json::json!({
+            "instance_api_version": version_string(instance::api_version(&entry)?),
+            "devices": devices,
+        }))
+    })();
+    unsafe {
+        if surface != vk::SurfaceKHR::null() {
+            Surface::new(&entry, &instance).destroy_surface(surface, None);
+        }
+        instance::destroy_debug_messenger(&entry, &instance, debug_messenger);
+        instance.destroy_instance(None);
+    }
+    result
+}
+
+fn main() {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
+    let args: Vec<String> = std::env::args().collect();
+    match run(&args) {
+        Ok(report) => {
+            if args.iter().any(|arg| arg == "--json") {
+                println!("{:#}", report);
+            } else if let Value::Object(map) = &report {
+                for (key, value) in map {
+                    print_text(key, value, 0);
+                }
+            }
+        }
+        Err(error) => {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The field names come from the Debug output, so a limit missing from report_limits shows up
+    #[test]
+    fn every_limit_is_reported() {
+        let limits = vk::PhysicalDeviceLimits::default();
+        let report = report_limits(&limits);
+        let debug = format!("{:#?}", limits);
+        let fields: Vec<&str> = debug
+            .lines()
+            .filter_map(|line| line.strip_prefix("    "))
+            .filter(|line| !line.starts_with(' '))
+            .filter_map(|line| line.split_once(':').map(|(name, _)| name))
+            .collect();
+        assert_eq!(fields.len(), 106);
+        for field in &fields {
+            assert!(report.contains_key(*field), "{} isn't reported", field);
+        }
+        assert_eq!(report.len(), fields.len());
+    }
+}
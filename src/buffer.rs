@@ -0,0 +1,139 @@
+use crate::error::{Error, Result};
+use crate::memory::{self, Allocation, AllocationStrategy, MemoryAllocator, MemoryRequest};
+use crate::upload::{StagingUploader, UploadHandle};
+use crate::vertex::Vertex;
+use ash::vk;
+use std::mem::size_of_val;
+
+// The best memory type with all of properties, for callers that don't go through the allocator
+pub fn find_memory_type(
+    instance: &ash::Instance,
+    physical_device: &vk::PhysicalDevice,
+    type_filter: u32,
+    properties: vk::MemoryPropertyFlags,
+) -> Result<u32> {
+    let mem_properties =
+        unsafe { instance.get_physical_device_memory_properties(*physical_device) };
+    memory::select_memory_types(&mem_properties, type_filter, MemoryRequest::new(properties))
+        .first()
+        .copied()
+        .ok_or(Error::NoSuitableMemoryType)
+}
+
+pub fn create_buffer(
+    device: &ash::Device,
+    allocator: &MemoryAllocator,
+    size: vk::DeviceSize,
+    usage: vk::BufferUsageFlags,
+    request: MemoryRequest,
+) -> Result<(vk::Buffer, Allocation)> {
+    create_buffer_with_strategy(
+        device,
+        allocator,
+        size,
+        usage,
+        request,
+        AllocationStrategy::FreeList,
+    )
+}
+
+pub fn create_buffer_with_strategy(
+    device: &ash::Device,
+    allocator: &MemoryAllocator,
+    size: vk::DeviceSize,
+    usage: vk::BufferUsageFlags,
+    request: MemoryRequest,
+    strategy: AllocationStrategy,
+) -> Result<(vk::Buffer, Allocation)> {
+    let buffer_info = vk::BufferCreateInfo {
+        s_type: vk::StructureType::BUFFER_CREATE_INFO,
+        size,
+        usage,
+        sharing_mode: vk::SharingMode::EXCLUSIVE,
+        ..Default::default()
+    };
+    let buffer = unsafe { device.create_buffer(&buffer_info, None)? };
+
+    match allocator.allocate_buffer(buffer, request, strategy) {
+        Ok(allocation) => Ok((buffer, allocation)),
+        Err(error) => {
+            unsafe { device.destroy_buffer(buffer, None) };
+            Err(error)
+        }
+    }
+}
+
+pub fn destroy_buffer(
+    device: &ash::Device,
+    allocator: &MemoryAllocator,
+    buffer: vk::Buffer,
+    allocation: &Allocation,
+) {
+    unsafe { device.destroy_buffer(buffer, None) };
+    allocator.free(allocation);
+}
+
+// The upload is only recorded, it runs when the uploader is flushed. Rendering submitted after that
+// sees the data without waiting on the CPU.
+pub fn create_vertex_buffer(
+    device: &ash::Device,
+    allocator: &MemoryAllocator,
+    uploader: &mut StagingUploader,
+    vertices: &[Vertex],
+) -> Result<(vk::Buffer, Allocation, UploadHandle)> {
+    let buffer_size = size_of_val(vertices) as u64;
+    let (buffer, allocation) = create_buffer(
+        device,
+        allocator,
+        buffer_size,
+        vk::BufferUsageFlags::TRANSFER_DST | vk::BufferUsageFlags::VERTEX_BUFFER,
+        MemoryRequest::new(vk::MemoryPropertyFlags::DEVICE_LOCAL),
+    )?;
+    let result = uploader.upload_buffer(
+        vertices,
+        buffer,
+        0,
+        vk::PipelineStageFlags::VERTEX_INPUT,
+        vk::AccessFlags::VERTEX_ATTRIBUTE_READ,
+    );
+    match result {
+        Ok(upload) => Ok((buffer, allocation, upload)),
+        Err(error) => {
+            // Nothing was recorded, so the buffer can go right away
+            destroy_buffer(device, allocator, buffer, &allocation);
+            Err(error)
+        }
+    }
+}
+
+// Takes u16 or u32 indices, the index type is given again when the buffer is bound
+pub fn create_index_buffer<T: Copy>(
+    device: &ash::Device,
+    allocator: &MemoryAllocator,
+    uploader: &mut StagingUploader,
+    indices: &[T],
+) -> Result<(vk::Buffer, Allocation, UploadHandle)> {
+    let buffer_size = size_of_val(indices) as u64;
+    let (buffer, allocation) = create_buffer(
+        device,
+        allocator,
+        buffer_size,
+        vk::BufferUsageFlags::TRANSFER_DST | vk::BufferUsageFlags::INDEX_BUFFER,
+        MemoryRequest::new(vk::MemoryPropertyFlags::DEVICE_LOCAL),
+    )?;
+    let result = uploader.upload_buffer(
+        indices,
+        buffer,
+        0,
+        vk::PipelineStageFlags::VERTEX_INPUT,
+        vk::AccessFlags::INDEX_READ,
+    );
+    match result {
+        Ok(upload) => Ok((buffer, allocation, upload)),
+        Err(error) => {
+            // Nothing was recorded, so the buffer can go right away
+            destroy_buffer(device, allocator, buffer, &allocation);
+            Err(error)
+        }
+    }
+}
@@ -0,0 +1,200 @@
+use crate::device;
+use crate::error::{Error, Result};
+use ash::vk;
+
+pub fn create_command_pool(
+    entry: &ash::Entry,
+    instance: &ash::Instance,
+    physical_device: &vk::PhysicalDevice,
+    device: &ash::Device,
+    surface: &vk::SurfaceKHR,
+) -> Result<vk::CommandPool> {
+    let indices = device::find_queue_familes(entry, instance, physical_device, surface)?;
+    let graphics_queue_family_index = indices.graphics.ok_or(Error::NoSuitableDevice)?;
+    create_command_pool_for_family(device, graphics_queue_family_index as u32)
+}
+
+// Command buffers can only be submitted to queues of the family their pool was made for
+pub fn create_command_pool_for_family(
+    device: &ash::Device,
+    queue_family_index: u32,
+) -> Result<vk::CommandPool> {
+    let pool_info = vk::CommandPoolCreateInfo {
+        s_type: vk::StructureType::COMMAND_POOL_CREATE_INFO,
+        flags: vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER,
+        queue_family_index,
+        ..Default::default()
+    };
+    Ok(unsafe { device.create_command_pool(&pool_info, None)? })
+}
+
+// Where one-off uploads are submitted. With a dedicated transfer family the copies run there, and
+// ownership of what they write is handed to the graphics family before rendering uses it.
+#[derive(Clone, Copy, Debug)]
+pub struct UploadQueues {
+    pub transfer_command_pool: vk::CommandPool,
+    pub transfer_queue: vk::Queue,
+    pub transfer_family: u32,
+    pub graphics_command_pool: vk::CommandPool,
+    pub graphics_queue: vk::Queue,
+    pub graphics_family: u32,
+}
+
+impl UploadQueues {
+    // Everything runs on the graphics queue, so no ownership has to change hands
+    pub fn graphics_only(
+        command_pool: vk::CommandPool,
+        graphics_queue: vk::Queue,
+        graphics_family: u32,
+    ) -> Self {
+        Self {
+            transfer_command_pool: command_pool,
+            transfer_queue: graphics_queue,
+            transfer_family: graphics_family,
+            graphics_command_pool: command_pool,
+            graphics_queue,
+            graphics_family,
+        }
+    }
+    pub fn needs_ownership_transfer(&self) -> bool {
+        self.transfer_family != self.graphics_family
+    }
+}
+
+// Resources made with exclusive sharing belong to one queue family at a time. Handing one over takes
+// the same barrier twice: a release recorded on the old family and an acquire recorded on the new one.
+pub fn buffer_ownership_barrier(
+    buffer: vk::Buffer,
+    src_queue_family: u32,
+    dst_queue_family: u32,
+    src_access_mask: vk::AccessFlags,
+    dst_access_mask: vk::AccessFlags,
+) -> vk::BufferMemoryBarrier {
+    vk::BufferMemoryBarrier {
+        s_type: vk::StructureType::BUFFER_MEMORY_BARRIER,
+        src_access_mask,
+        dst_access_mask,
+        src_queue_family_index: src_queue_family,
+        dst_queue_family_index: dst_queue_family,
+        buffer,
+        offset: 0,
+        size: vk::WHOLE_SIZE,
+        ..Default::default()
+    }
+}
+
+// Moves the subresources of image from old_layout to new_layout. Set the queue families on the result
+// to hand the image over as well, the release and the acquire then both do the same transition.
+pub fn image_layout_barrier(
+    image: vk::Image,
+    subresource_range: vk::ImageSubresourceRange,
+    old_layout: vk::ImageLayout,
+    new_layout: vk::ImageLayout,
+    src_access_mask: vk::AccessFlags,
+    dst_access_mask: vk::AccessFlags,
+) -> vk::ImageMemoryBarrier {
+    vk::ImageMemoryBarrier {
+        s_type: vk::StructureType::IMAGE_MEMORY_BARRIER,
+        src_access_mask,
+        dst_access_mask,
+        old_layout,
+        new_layout,
+        src_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+        dst_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+        image,
+        subresource_range,
+        ..Default::default()
+    }
+}
+
+pub fn create_command_buffers(
+    device: &ash::Device,
+    command_pool: &vk::CommandPool,
+    count: usize,
+) -> Result<Vec<vk::CommandBuffer>> {
+    let alloc_info = vk::CommandBufferAllocateInfo {
+        s_type: vk::StructureType::COMMAND_BUFFER_ALLOCATE_INFO,
+        command_pool: *command_pool,
+        level: vk::CommandBufferLevel::PRIMARY,
+        command_buffer_count: count as u32,
+        ..Default::default()
+    };
+    Ok(unsafe { device.allocate_command_buffers(&alloc_info)? })
+}
+
+pub fn create_sync_objects(
+    device: &ash::Device,
+    count: usize,
+) -> Result<(Vec<vk::Semaphore>, Vec<vk::Semaphore>, Vec<vk::Fence>)> {
+    let semaphore_info = vk::SemaphoreCreateInfo {
+        s_type: vk::StructureType::SEMAPHORE_CREATE_INFO,
+        ..Default::default()
+    };
+    let fence_info = vk::FenceCreateInfo {
+        s_type: vk::StructureType::FENCE_CREATE_INFO,
+        flags: vk::FenceCreateFlags::SIGNALED,
+        ..Default::default()
+    };
+    let mut image_available_semaphores = Vec::new();
+    let mut render_finished_semaphores = Vec::new();
+    let mut in_flight_fences = Vec::new();
+    unsafe {
+        for _ in 0..count {
+            image_available_semaphores.push(device.create_semaphore(&semaphore_info, None)?);
+            render_finished_semaphores.push(device.create_semaphore(&semaphore_info, None)?);
+            in_flight_fences.push(device.create_fence(&fence_info, None)?);
+        }
+    }
+    Ok((
+        image_available_semaphores,
+        render_finished_semaphores,
+        in_flight_fences,
+    ))
+}
+
+pub fn begin_single_time_commands(
+    device: &ash::Device,
+    command_pool: &vk::CommandPool,
+) -> Result<vk::CommandBuffer> {
+    let alloc_info = vk::CommandBufferAllocateInfo {
+        s_type: vk::StructureType::COMMAND_BUFFER_ALLOCATE_INFO,
+        level: vk::CommandBufferLevel::PRIMARY,
+        command_pool: *command_pool,
+        command_buffer_count: 1,
+        ..Default::default()
+    };
+    let command_buffer = unsafe { device.allocate_command_buffers(&alloc_info)?[0] };
+
+    let begin_info = vk::CommandBufferBeginInfo {
+        s_type: vk::StructureType::COMMAND_BUFFER_BEGIN_INFO,
+        flags: vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT,
+        ..Default::default()
+    };
+
+    unsafe {
+        device.begin_command_buffer(command_buffer, &begin_info)?;
+    }
+    Ok(command_buffer)
+}
+
+pub fn end_single_time_commands(
+    device: &ash::Device,
+    command_pool: &vk::CommandPool,
+    graphics_queue: &vk::Queue,
+    command_buffer: vk::CommandBuffer,
+) -> Result<()> {
+    let submit_info = vk::SubmitInfo {
+        s_type: vk::StructureType::SUBMIT_INFO,
+        command_buffer_count: 1,
+        p_command_buffers: &command_buffer,
+        ..Default::default()
+    };
+
+    unsafe {
+        device.end_command_buffer(command_buffer)?;
+        device.queue_submit(*graphics_queue, &[submit_info], vk::Fence::null())?;
+        device.queue_wait_idle(*graphics_queue)?;
+        device.free_command_buffers(*command_pool, &[command_buffer]);
+    }
+    Ok(())
+}
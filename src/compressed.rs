@@ -0,0 +1,368 @@
+use crate::error::{Error, Result};
+use crate::texture;
+use ash::vk;
+use ddsfile::{Caps2, D3DFormat, Dds, DxgiFormat, MiscFlag};
+use std::ops::RangeInclusive;
+use std::path::Path;
+
+// BC1-7, then ETC2 and EAC, then the LDR ASTC formats, which Vulkan numbers one after another
+pub const BLOCK_COMPRESSED_FORMATS: RangeInclusive<i32> =
+    vk::Format::BC1_RGB_UNORM_BLOCK.as_raw()..=vk::Format::ASTC_12X12_SRGB_BLOCK.as_raw();
+
+pub fn is_block_compressed(format: vk::Format) -> bool {
+    BLOCK_COMPRESSED_FORMATS.contains(&format.as_raw())
+}
+
+// Pre-baked mip levels and array layers read from a KTX2 or DDS file, still block compressed
+pub struct CompressedTexture {
+    pub format: vk::Format,
+    pub width: u32,
+    pub height: u32,
+    pub mip_levels: u32,
+    pub array_layers: u32,
+    // Every six array layers are the faces of a cube, in the order +X, -X, +Y, -Y, +Z, -Z
+    pub cube: bool,
+    // Every subresource, in the order the file stores them
+    pub data: Vec<u8>,
+    // The copies that put data into the image, buffer_offset counting from the start of data
+    pub regions: Vec<vk::BufferImageCopy>,
+}
+
+// Reads a KTX2 or DDS file, telling them apart by their first bytes rather than the extension
+pub fn load_compressed(path: &Path) -> Result<CompressedTexture> {
+    let load_error = |reason: String| Error::TextureLoad {
+        path: path.to_path_buf(),
+        reason,
+    };
+    let bytes = std::fs::read(path).map_err(|error| load_error(error.to_string()))?;
+    if bytes.starts_with(b"\xabKTX 20\xbb") {
+        read_ktx2(&bytes).map_err(load_error)
+    } else if bytes.starts_with(b"DDS ") {
+        read_dds(&bytes).map_err(load_error)
+    } else {
+        Err(load_error("not a KTX2 or DDS file".to_string()))
+    }
+}
+
+fn level_extent(width: u32, height: u32, mip_level: u32) -> vk::Extent3D {
+    vk::Extent3D {
+        width: (width >> mip_level).max(1),
+        height: (height >> mip_level).max(1),
+        depth: 1,
+    }
+}
+
+fn subresource_copy(
+    buffer_offset: usize,
+    mip_level: u32,
+    base_array_layer: u32,
+    layer_count: u32,
+    image_extent: vk::Extent3D,
+) -> vk::BufferImageCopy {
+    vk::BufferImageCopy {
+        buffer_offset: buffer_offset as vk::DeviceSize,
+        // Tightly packed
+        buffer_row_length: 0,
+        buffer_image_height: 0,
+        image_subresource: vk::ImageSubresourceLayers {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            mip_level,
+            base_array_layer,
+            layer_count,
+        },
+        image_offset: vk::Offset3D { x: 0, y: 0, z: 0 },
+        image_extent,
+    }
+}
+
+// KTX2 uses Vulkan's own format numbers. Each level holds all of its layers and cube faces, which
+// become array layers of their own.
+fn read_ktx2(bytes: &[u8]) -> std::result::Result<CompressedTexture, String> {
+    let reader = ktx2::Reader::new(bytes).map_err(|error| error.to_string())?;
+    let header = reader.header();
+    if let Some(scheme) = header.supercompression_scheme {
+        return Err(format!("{:?} supercompression isn't supported", scheme));
+    }
+    let format = header
+        .format
+        .map(|format| vk::Format::from_raw(format.value() as i32))
+        .filter(|format| is_block_compressed(*format))
+        .ok_or_else(|| format!("{:?} isn't a block compressed format", header.format))?;
+    if header.pixel_depth > 1 {
+        return Err("3D textures aren't supported".to_string());
+    }
+    let cube = header.face_count == 6;
+    if cube && header.pixel_width != header.pixel_height {
+        return Err("the faces of a cube map have to be square".to_string());
+    }
+    let array_layers = header.layer_count.max(1) * header.face_count.max(1);
+    let mut data = Vec::new();
+    let mut regions = Vec::new();
+    for (mip_level, level) in reader.levels().enumerate() {
+        regions.push(subresource_copy(
+            data.len(),
+            mip_level as u32,
+            0,
+            array_layers,
+            level_extent(header.pixel_width, header.pixel_height, mip_level as u32),
+        ));
+        data.extend_from_slice(level.data);
+    }
+    Ok(CompressedTexture {
+        format,
+        width: header.pixel_width,
+        height: header.pixel_height,
+        mip_levels: regions.len() as u32,
+        array_layers,
+        cube,
+        data,
+        regions,
+    })
+}
+
+fn dxgi_to_vk_format(format: DxgiFormat) -> Option<vk::Format> {
+    match format {
+        DxgiFormat::BC1_UNorm => Some(vk::Format::BC1_RGBA_UNORM_BLOCK),
+        DxgiFormat::BC1_UNorm_sRGB => Some(vk::Format::BC1_RGBA_SRGB_BLOCK),
+        DxgiFormat::BC2_UNorm => Some(vk::Format::BC2_UNORM_BLOCK),
+        DxgiFormat::BC2_UNorm_sRGB => Some(vk::Format::BC2_SRGB_BLOCK),
+        DxgiFormat::BC3_UNorm => Some(vk::Format::BC3_UNORM_BLOCK),
+        DxgiFormat::BC3_UNorm_sRGB => Some(vk::Format::BC3_SRGB_BLOCK),
+        DxgiFormat::BC4_UNorm => Some(vk::Format::BC4_UNORM_BLOCK),
+        DxgiFormat::BC4_SNorm => Some(vk::Format::BC4_SNORM_BLOCK),
+        DxgiFormat::BC5_UNorm => Some(vk::Format::BC5_UNORM_BLOCK),
+        DxgiFormat::BC5_SNorm => Some(vk::Format::BC5_SNORM_BLOCK),
+        DxgiFormat::BC6H_UF16 => Some(vk::Format::BC6H_UFLOAT_BLOCK),
+        DxgiFormat::BC6H_SF16 => Some(vk::Format::BC6H_SFLOAT_BLOCK),
+        DxgiFormat::BC7_UNorm => Some(vk::Format::BC7_UNORM_BLOCK),
+        DxgiFormat::BC7_UNorm_sRGB => Some(vk::Format::BC7_SRGB_BLOCK),
+        _ => None,
+    }
+}
+
+// Files without a DX10 header name the old DXT formats, premultiplied alpha is up to the shader
+fn d3d_to_vk_format(format: D3DFormat) -> Option<vk::Format> {
+    match format {
+        D3DFormat::DXT1 => Some(vk::Format::BC1_RGBA_UNORM_BLOCK),
+        D3DFormat::DXT2 | D3DFormat::DXT3 => Some(vk::Format::BC2_UNORM_BLOCK),
+        D3DFormat::DXT4 | D3DFormat::DXT5 => Some(vk::Format::BC3_UNORM_BLOCK),
+        _ => None,
+    }
+}
+
+// BC formats are all made of 4x4 blocks, BC1 and BC4 blocks are half the size of the others
+fn bc_level_size(format: vk::Format, extent: vk::Extent3D) -> usize {
+    let block_size = match format {
+        vk::Format::BC1_RGBA_UNORM_BLOCK
+        | vk::Format::BC1_RGBA_SRGB_BLOCK
+        | vk::Format::BC4_UNORM_BLOCK
+        | vk::Format::BC4_SNORM_BLOCK => 8,
+        _ => 16,
+    };
+    (extent.width.div_ceil(4) * extent.height.div_ceil(4) * block_size) as usize
+}
+
+// DDS stores every layer with its whole mip chain before the next layer, cube maps as six layers
+fn read_dds(bytes: &[u8]) -> std::result::Result<CompressedTexture, String> {
+    let dds = Dds::read(bytes).map_err(|error| error.to_string())?;
+    let format = match &dds.header10 {
+        Some(header10) => dxgi_to_vk_format(header10.dxgi_format),
+        None => dds.get_d3d_format().and_then(d3d_to_vk_format),
+    }
+    .ok_or_else(|| "only BC compressed DDS files are supported".to_string())?;
+    if dds.get_depth() > 1 {
+        return Err("3D textures aren't supported".to_string());
+    }
+    let width = dds.get_width();
+    let height = dds.get_height();
+    let mip_levels = dds.get_num_mipmap_levels().max(1);
+    // The levels are stored back to back, so more of them than the size allows can't be read
+    if mip_levels > texture::mip_level_count(width, height) {
+        return Err(format!(
+            "{} mip levels are too many for {}x{}",
+            mip_levels, width, height
+        ));
+    }
+    let cube = match &dds.header10 {
+        Some(header10) => header10.misc_flag.contains(MiscFlag::TEXTURECUBE),
+        None => dds.header.caps2.contains(Caps2::CUBEMAP),
+    };
+    if cube && dds.get_width() != dds.get_height() {
+        return Err("the faces of a cube map have to be square".to_string());
+    }
+    // Without a DX10 header a cube map always has all six faces
+    let array_layers = match &dds.header10 {
+        Some(header10) if cube => header10.array_size.max(1) * 6,
+        _ => dds.get_num_array_layers().max(1),
+    };
+    let mut regions = Vec::new();
+    let mut offset = 0;
+    for array_layer in 0..array_layers {
+        for mip_level in 0..mip_levels {
+            let extent = level_extent(width, height, mip_level);
+            regions.push(subresource_copy(offset, mip_level, array_layer, 1, extent));
+            offset += bc_level_size(format, extent);
+        }
+    }
+    if offset > dds.data.len() {
+        return Err(format!(
+            "{} bytes of texel data are needed, but the file has {}",
+            offset,
+            dds.data.len()
+        ));
+    }
+    Ok(CompressedTexture {
+        format,
+        width,
+        height,
+        mip_levels,
+        array_layers,
+        cube,
+        data: dds.data[..offset].to_vec(),
+        regions,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ddsfile::{AlphaMode, D3D10ResourceDimension, NewDxgiParams};
+
+    fn extent(width: u32, height: u32) -> vk::Extent3D {
+        vk::Extent3D {
+            width,
+            height,
+            depth: 1,
+        }
+    }
+
+    // Written out and read back, so the test goes through the same header parsing as a file
+    fn dds_bytes(
+        format: DxgiFormat,
+        size: u32,
+        mip_levels: u32,
+        layers: u32,
+        cube: bool,
+    ) -> Vec<u8> {
+        let dds = Dds::new_dxgi(NewDxgiParams {
+            height: size,
+            width: size,
+            depth: None,
+            format,
+            mipmap_levels: Some(mip_levels),
+            array_layers: Some(layers),
+            caps2: None,
+            is_cubemap: cube,
+            resource_dimension: D3D10ResourceDimension::Texture2D,
+            alpha_mode: AlphaMode::Unknown,
+        })
+        .unwrap();
+        let mut bytes = Vec::new();
+        dds.write(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn dxgi_formats() {
+        assert_eq!(
+            dxgi_to_vk_format(DxgiFormat::BC1_UNorm_sRGB),
+            Some(vk::Format::BC1_RGBA_SRGB_BLOCK)
+        );
+        assert_eq!(
+            dxgi_to_vk_format(DxgiFormat::BC6H_UF16),
+            Some(vk::Format::BC6H_UFLOAT_BLOCK)
+        );
+        assert_eq!(
+            dxgi_to_vk_format(DxgiFormat::BC7_UNorm),
+            Some(vk::Format::BC7_UNORM_BLOCK)
+        );
+        assert_eq!(dxgi_to_vk_format(DxgiFormat::R8G8B8A8_UNorm), None);
+    }
+
+    #[test]
+    fn d3d_formats() {
+        assert_eq!(
+            d3d_to_vk_format(D3DFormat::DXT1),
+            Some(vk::Format::BC1_RGBA_UNORM_BLOCK)
+        );
+        assert_eq!(
+            d3d_to_vk_format(D3DFormat::DXT3),
+            Some(vk::Format::BC2_UNORM_BLOCK)
+        );
+        assert_eq!(
+            d3d_to_vk_format(D3DFormat::DXT5),
+            Some(vk::Format::BC3_UNORM_BLOCK)
+        );
+        assert_eq!(d3d_to_vk_format(D3DFormat::A8R8G8B8), None);
+    }
+
+    #[test]
+    fn bc_level_sizes() {
+        assert_eq!(
+            bc_level_size(vk::Format::BC1_RGBA_UNORM_BLOCK, extent(8, 8)),
+            32
+        );
+        assert_eq!(bc_level_size(vk::Format::BC4_UNORM_BLOCK, extent(8, 8)), 32);
+        assert_eq!(bc_level_size(vk::Format::BC7_UNORM_BLOCK, extent(8, 8)), 64);
+        // Partial blocks take a whole block
+        assert_eq!(
+            bc_level_size(vk::Format::BC1_RGBA_UNORM_BLOCK, extent(5, 3)),
+            16
+        );
+        assert_eq!(bc_level_size(vk::Format::BC3_UNORM_BLOCK, extent(1, 1)), 16);
+        assert_eq!(
+            bc_level_size(vk::Format::BC3_UNORM_BLOCK, extent(6, 10)),
+            96
+        );
+    }
+
+    #[test]
+    fn dds_arrays_are_layer_major() {
+        let texture = read_dds(&dds_bytes(DxgiFormat::BC1_UNorm, 8, 4, 2, false)).unwrap();
+        assert_eq!(texture.format, vk::Format::BC1_RGBA_UNORM_BLOCK);
+        assert_eq!((texture.mip_levels, texture.array_layers), (4, 2));
+        assert!(!texture.cube);
+        // 32 bytes for level 0, then a block each for 4x4, 2x2 and 1x1
+        let offsets: Vec<(u32, u32, vk::DeviceSize)> = texture
+            .regions
+            .iter()
+            .map(|region| {
+                (
+                    region.image_subresource.base_array_layer,
+                    region.image_subresource.mip_level,
+                    region.buffer_offset,
+                )
+            })
+            .collect();
+        assert_eq!(
+            offsets,
+            [
+                (0, 0, 0),
+                (0, 1, 32),
+                (0, 2, 40),
+                (0, 3, 48),
+                (1, 0, 56),
+                (1, 1, 88),
+                (1, 2, 96),
+                (1, 3, 104),
+            ]
+        );
+        assert_eq!(texture.data.len(), 112);
+        assert_eq!(texture.regions[3].image_extent, extent(1, 1));
+    }
+
+    #[test]
+    fn dds_cube_maps_have_six_layers() {
+        let texture = read_dds(&dds_bytes(DxgiFormat::BC3_UNorm, 4, 1, 6, true)).unwrap();
+        assert!(texture.cube);
+        assert_eq!(texture.array_layers, 6);
+        assert_eq!(texture.regions[5].buffer_offset, 5 * 16);
+    }
+
+    #[test]
+    fn dds_with_too_many_mip_levels_is_rejected() {
+        // 8x8 only halves down to 1x1 in four levels
+        let error = read_dds(&dds_bytes(DxgiFormat::BC1_UNorm, 8, 5, 1, false)).err();
+        assert_eq!(error.as_deref(), Some("5 mip levels are too many for 8x8"));
+    }
+}
@@ -0,0 +1,545 @@
+use crate::device::DeviceSelector;
+use crate::error::{Error, Result};
+use crate::instance::{DebugConfig, Validation};
+use crate::pipeline::DepthTest;
+use ash::vk;
+use serde::Deserialize;
+use std::path::Path;
+use std::{env, fs};
+
+pub const DEVICE_VARIABLE: &str = "VULKANRUST_DEVICE";
+
+// Everything about the renderer that can be chosen at runtime, so different tools can share one binary
+#[derive(Clone, Debug)]
+pub struct RendererConfig {
+    pub width: u32,
+    pub height: u32,
+    pub max_frames_in_flight: usize,
+    pub application_name: String,
+    pub engine_name: String,
+    // Used when the surface supports it, FIFO otherwise as it is always available
+    pub present_mode: vk::PresentModeKHR,
+    pub vert_shader_path: String,
+    pub frag_shader_path: String,
+    // PNG, JPEG, KTX2 or DDS file sampled by every object, a white texture is used without one
+    pub texture_path: Option<String>,
+    // OBJ mesh or glTF scene drawn for every object, a quad is drawn without one
+    pub mesh_path: Option<String>,
+    // Bytes of host visible memory uploads are staged in, bigger uploads get a staging buffer of their own
+    pub staging_ring_size: u64,
+    // How many objects one frame can draw, each draw record of every object gets a slot in the
+    // per-object uniform buffer
+    pub max_objects: usize,
+    // How fragments are tested against the depth buffer, and whether they write to it
+    pub depth_compare: vk::CompareOp,
+    pub depth_write: bool,
+    // Samples per pixel, lowered to the most the device supports for color and depth together
+    pub msaa_samples: u32,
+    // Shades at least this fraction of each pixel's samples, when the device supports sample shading
+    pub min_sample_shading: Option<f32>,
+    // The best suitable device is used when nothing is selected
+    pub device: Option<DeviceSelector>,
+    pub debug: DebugConfig,
+}
+
+impl Default for RendererConfig {
+    fn default() -> Self {
+        Self {
+            width: 800,
+            height: 600,
+            max_frames_in_flight: 2,
+            application_name: "Hello Triangle".to_string(),
+            engine_name: "No Engine".to_string(),
+            present_mode: vk::PresentModeKHR::MAILBOX,
+            vert_shader_path: "shaders/vert.spv".to_string(),
+            frag_shader_path: "shaders/frag.spv".to_string(),
+            texture_path: None,
+            mesh_path: None,
+            staging_ring_size: 16 * 1024 * 1024,
+            max_objects: 256,
+            depth_compare: vk::CompareOp::LESS,
+            depth_write: true,
+            msaa_samples: 1,
+            min_sample_shading: None,
+            device: None,
+            debug: DebugConfig::default(),
+        }
+    }
+}
+
+// The layout of a config file, where everything left out keeps its default
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct ConfigFile {
+    width: Option<u32>,
+    height: Option<u32>,
+    max_frames_in_flight: Option<usize>,
+    application_name: Option<String>,
+    engine_name: Option<String>,
+    present_mode: Option<String>,
+    vert_shader_path: Option<String>,
+    frag_shader_path: Option<String>,
+    texture: Option<String>,
+    mesh: Option<String>,
+    staging_ring_size: Option<u64>,
+    max_objects: Option<usize>,
+    depth_compare: Option<String>,
+    depth_write: Option<bool>,
+    msaa_samples: Option<u32>,
+    min_sample_shading: Option<f32>,
+    device: Option<String>,
+    debug: DebugConfigFile,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct DebugConfigFile {
+    validation: Option<bool>,
+    min_severity: Option<String>,
+    message_types: Option<Vec<String>>,
+    fail_on_error: Option<bool>,
+}
+
+impl RendererConfig {
+    pub fn size(mut self, width: u32, height: u32) -> Self {
+        self.width = width;
+        self.height = height;
+        self
+    }
+    pub fn max_frames_in_flight(mut self, max_frames_in_flight: usize) -> Self {
+        self.max_frames_in_flight = max_frames_in_flight;
+        self
+    }
+    pub fn application_name(mut self, application_name: &str) -> Self {
+        self.application_name = application_name.to_string();
+        self
+    }
+    pub fn engine_name(mut self, engine_name: &str) -> Self {
+        self.engine_name = engine_name.to_string();
+        self
+    }
+    pub fn present_mode(mut self, present_mode: vk::PresentModeKHR) -> Self {
+        self.present_mode = present_mode;
+        self
+    }
+    pub fn shaders(mut self, vert_shader_path: &str, frag_shader_path: &str) -> Self {
+        self.vert_shader_path = vert_shader_path.to_string();
+        self.frag_shader_path = frag_shader_path.to_string();
+        self
+    }
+    pub fn texture(mut self, texture_path: &str) -> Self {
+        self.texture_path = Some(texture_path.to_string());
+        self
+    }
+    pub fn mesh(mut self, mesh_path: &str) -> Self {
+        self.mesh_path = Some(mesh_path.to_string());
+        self
+    }
+    pub fn staging_ring_size(mut self, staging_ring_size: u64) -> Self {
+        self.staging_ring_size = staging_ring_size;
+        self
+    }
+    pub fn max_objects(mut self, max_objects: usize) -> Self {
+        self.max_objects = max_objects;
+        self
+    }
+    pub fn depth(mut self, depth_compare: vk::CompareOp, depth_write: bool) -> Self {
+        self.depth_compare = depth_compare;
+        self.depth_write = depth_write;
+        self
+    }
+    pub fn msaa(mut self, msaa_samples: u32, min_sample_shading: Option<f32>) -> Self {
+        self.msaa_samples = msaa_samples;
+        self.min_sample_shading = min_sample_shading;
+        self
+    }
+    pub fn device(mut self, device: DeviceSelector) -> Self {
+        self.device = Some(device);
+        self
+    }
+    pub fn debug(mut self, debug: DebugConfig) -> Self {
+        self.debug = debug;
+        self
+    }
+
+    pub fn depth_test(&self) -> DepthTest {
+        DepthTest {
+            compare_op: self.depth_compare,
+            write: self.depth_write,
+        }
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .map_err(|error| Error::Config(format!("{}: {}", path.display(), error)))?;
+        Self::default().merge_toml(&text)
+    }
+
+    // Overrides whatever the TOML document sets, leaving everything else as it is
+    pub fn merge_toml(mut self, text: &str) -> Result<Self> {
+        let file: ConfigFile =
+            toml::from_str(text).map_err(|error| Error::Config(error.to_string()))?;
+        if let Some(width) = file.width {
+            self.width = width;
+        }
+        if let Some(height) = file.height {
+            self.height = height;
+        }
+        if let Some(max_frames_in_flight) = file.max_frames_in_flight {
+            self.max_frames_in_flight = max_frames_in_flight;
+        }
+        if let Some(application_name) = file.application_name {
+            self.application_name = application_name;
+        }
+        if let Some(engine_name) = file.engine_name {
+            self.engine_name = engine_name;
+        }
+        if let Some(present_mode) = file.present_mode {
+            self.present_mode = parse_present_mode(&present_mode)?;
+        }
+        if let Some(vert_shader_path) = file.vert_shader_path {
+            self.vert_shader_path = vert_shader_path;
+        }
+        if let Some(frag_shader_path) = file.frag_shader_path {
+            self.frag_shader_path = frag_shader_path;
+        }
+        if let Some(texture_path) = file.texture {
+            self.texture_path = Some(texture_path);
+        }
+        if let Some(mesh_path) = file.mesh {
+            self.mesh_path = Some(mesh_path);
+        }
+        if let Some(staging_ring_size) = file.staging_ring_size {
+            self.staging_ring_size = staging_ring_size;
+        }
+        if let Some(max_objects) = file.max_objects {
+            self.max_objects = max_objects;
+        }
+        if let Some(depth_compare) = file.depth_compare {
+            self.depth_compare = parse_compare_op(&depth_compare)?;
+        }
+        if let Some(depth_write) = file.depth_write {
+            self.depth_write = depth_write;
+        }
+        if let Some(msaa_samples) = file.msaa_samples {
+            self.msaa_samples = msaa_samples;
+        }
+        if let Some(min_sample_shading) = file.min_sample_shading {
+            self.min_sample_shading = Some(min_sample_shading);
+        }
+        if let Some(device) = file.device {
+            self.device = Some(DeviceSelector::parse(&device));
+        }
+        // Set in the file, validation is explicitly asked for and required
+        if let Some(validation) = file.debug.validation {
+            self.debug.validation = if validation {
+                Validation::Required
+            } else {
+                Validation::Disabled
+            };
+        }
+        if let Some(min_severity) = file.debug.min_severity {
+            self.debug.min_severity = parse_severity(&min_severity)?;
+        }
+        if let Some(message_types) = file.debug.message_types {
+            self.debug.message_types = vk::DebugUtilsMessageTypeFlagsEXT::empty();
+            for message_type in message_types {
+                self.debug.message_types |= parse_message_type(&message_type)?;
+            }
+        }
+        if let Some(fail_on_error) = file.debug.fail_on_error {
+            self.debug.fail_on_error = fail_on_error;
+        }
+        self.validate()
+    }
+
+    // Applies the renderer flags among the command line arguments, a --config file is loaded before
+    // any of the other flags so that they override it. Arguments meant for something else are ignored.
+    // VULKANRUST_DEVICE overrides the device in the file, and --device overrides both.
+    pub fn from_args(args: &[String]) -> Result<Self> {
+        let mut config = match flag_value(args, "--config")? {
+            Some(path) => Self::from_file(Path::new(path))?,
+            None => Self::default(),
+        };
+        if let Ok(device) = env::var(DEVICE_VARIABLE) {
+            config.device = Some(DeviceSelector::parse(&device));
+        }
+        if let Some(device) = flag_value(args, "--device")? {
+            config.device = Some(DeviceSelector::parse(device));
+        }
+        if let Some(width) = flag_value(args, "--width")? {
+            config.width = parse_number(width, "--width")?;
+        }
+        if let Some(height) = flag_value(args, "--height")? {
+            config.height = parse_number(height, "--height")?;
+        }
+        if let Some(frames) = flag_value(args, "--frames-in-flight")? {
+            config.max_frames_in_flight = parse_number(frames, "--frames-in-flight")?;
+        }
+        if let Some(max_objects) = flag_value(args, "--max-objects")? {
+            config.max_objects = parse_number(max_objects, "--max-objects")?;
+        }
+        if let Some(depth_compare) = flag_value(args, "--depth-compare")? {
+            config.depth_compare = parse_compare_op(depth_compare)?;
+        }
+        if args.iter().any(|arg| arg == "--no-depth-write") {
+            config.depth_write = false;
+        }
+        if let Some(msaa_samples) = flag_value(args, "--msaa")? {
+            config.msaa_samples = parse_number(msaa_samples, "--msaa")?;
+        }
+        if let Some(min_sample_shading) = flag_value(args, "--sample-shading")? {
+            config.min_sample_shading = Some(parse_number(min_sample_shading, "--sample-shading")?);
+        }
+        if let Some(present_mode) = flag_value(args, "--present-mode")? {
+            config.present_mode = parse_present_mode(present_mode)?;
+        }
+        if let Some(path) = flag_value(args, "--vert-shader")? {
+            config.vert_shader_path = path.to_string();
+        }
+        if let Some(path) = flag_value(args, "--frag-shader")? {
+            config.frag_shader_path = path.to_string();
+        }
+        if let Some(path) = flag_value(args, "--texture")? {
+            config.texture_path = Some(path.to_string());
+        }
+        if let Some(path) = flag_value(args, "--mesh")? {
+            config.mesh_path = Some(path.to_string());
+        }
+        if let Some(min_severity) = flag_value(args, "--validation-severity")? {
+            config.debug.min_severity = parse_severity(min_severity)?;
+        }
+        // A comma separated list, such as validation,performance
+        if let Some(message_types) = flag_value(args, "--validation-message-types")? {
+            config.debug.message_types = vk::DebugUtilsMessageTypeFlagsEXT::empty();
+            for message_type in message_types.split(',') {
+                config.debug.message_types |= parse_message_type(message_type.trim())?;
+            }
+        }
+        if args.iter().any(|arg| arg == "--validation") {
+            config.debug.validation = Validation::Required;
+        }
+        if args.iter().any(|arg| arg == "--no-validation") {
+            config.debug.validation = Validation::Disabled;
+        }
+        if args.iter().any(|arg| arg == "--fail-on-validation-error") {
+            config.debug.validation = Validation::Required;
+            config.debug.fail_on_error = true;
+        }
+        config.validate()
+    }
+
+    fn validate(self) -> Result<Self> {
+        if self.width == 0 || self.height == 0 {
+            return Err(Error::Config(format!(
+                "The window can't be {}x{}",
+                self.width, self.height
+            )));
+        }
+        if self.staging_ring_size == 0 {
+            return Err(Error::Config(
+                "The staging ring needs room for at least one byte".to_string(),
+            ));
+        }
+        if self.max_objects == 0 {
+            return Err(Error::Config(
+                "At least one object has to be drawable".to_string(),
+            ));
+        }
+        if self.msaa_samples == 0 {
+            return Err(Error::Config("Pixels need at least one sample".to_string()));
+        }
+        if self
+            .min_sample_shading
+            .is_some_and(|fraction| !(0.0..=1.0).contains(&fraction))
+        {
+            return Err(Error::Config(format!(
+                "Sample shading needs a fraction between 0 and 1, not {}",
+                self.min_sample_shading.unwrap()
+            )));
+        }
+        if self.max_frames_in_flight == 0 {
+            return Err(Error::Config(
+                "At least one frame has to be in flight".to_string(),
+            ));
+        }
+        Ok(self)
+    }
+}
+
+fn flag_value<'a>(args: &'a [String], flag: &str) -> Result<Option<&'a str>> {
+    match args.iter().position(|arg| arg == flag) {
+        Some(index) => match args.get(index + 1) {
+            Some(value) => Ok(Some(value)),
+            None => Err(Error::Config(format!("{} needs a value", flag))),
+        },
+        None => Ok(None),
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(value: &str, flag: &str) -> Result<T> {
+    value
+        .parse()
+        .map_err(|_| Error::Config(format!("{} needs a number, not {}", flag, value)))
+}
+
+fn parse_present_mode(name: &str) -> Result<vk::PresentModeKHR> {
+    match name {
+        "immediate" => Ok(vk::PresentModeKHR::IMMEDIATE),
+        "mailbox" => Ok(vk::PresentModeKHR::MAILBOX),
+        "fifo" => Ok(vk::PresentModeKHR::FIFO),
+        "fifo_relaxed" => Ok(vk::PresentModeKHR::FIFO_RELAXED),
+        _ => Err(Error::Config(format!("Unknown present mode {}", name))),
+    }
+}
+
+fn parse_compare_op(name: &str) -> Result<vk::CompareOp> {
+    match name {
+        "never" => Ok(vk::CompareOp::NEVER),
+        "less" => Ok(vk::CompareOp::LESS),
+        "equal" => Ok(vk::CompareOp::EQUAL),
+        "less_or_equal" => Ok(vk::CompareOp::LESS_OR_EQUAL),
+        "greater" => Ok(vk::CompareOp::GREATER),
+        "not_equal" => Ok(vk::CompareOp::NOT_EQUAL),
+        "greater_or_equal" => Ok(vk::CompareOp::GREATER_OR_EQUAL),
+        "always" => Ok(vk::CompareOp::ALWAYS),
+        _ => Err(Error::Config(format!("Unknown depth compare op {}", name))),
+    }
+}
+
+fn parse_severity(name: &str) -> Result<vk::DebugUtilsMessageSeverityFlagsEXT> {
+    match name {
+        "verbose" => Ok(vk::DebugUtilsMessageSeverityFlagsEXT::VERBOSE),
+        "info" => Ok(vk::DebugUtilsMessageSeverityFlagsEXT::INFO),
+        "warning" => Ok(vk::DebugUtilsMessageSeverityFlagsEXT::WARNING),
+        "error" => Ok(vk::DebugUtilsMessageSeverityFlagsEXT::ERROR),
+        _ => Err(Error::Config(format!("Unknown message severity {}", name))),
+    }
+}
+
+fn parse_message_type(name: &str) -> Result<vk::DebugUtilsMessageTypeFlagsEXT> {
+    match name {
+        "general" => Ok(vk::DebugUtilsMessageTypeFlagsEXT::GENERAL),
+        "validation" => Ok(vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION),
+        "performance" => Ok(vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE),
+        _ => Err(Error::Config(format!("Unknown message type {}", name))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    // Written under a name of its own, as tests run in parallel
+    fn write_config(name: &str, text: &str) -> String {
+        let path = env::temp_dir().join(format!("vulkanrust-{}-{}.toml", name, std::process::id()));
+        fs::write(&path, text).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn flags_override_the_config_file() {
+        let path = write_config(
+            "flags",
+            "width = 1024\nheight = 768\nmsaa_samples = 4\n[debug]\nmin_severity = \"error\"\n",
+        );
+        let config = RendererConfig::from_args(&args(&[
+            "vulkanrust",
+            "--width",
+            "640",
+            "--config",
+            &path,
+            "--validation-severity",
+            "info",
+        ]))
+        .unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!((config.width, config.height), (640, 768));
+        assert_eq!(config.msaa_samples, 4);
+        assert_eq!(
+            config.debug.min_severity,
+            vk::DebugUtilsMessageSeverityFlagsEXT::INFO
+        );
+    }
+
+    // The only test that touches the environment, so nothing else sees the variable change
+    #[test]
+    fn device_precedence() {
+        let path = write_config("device", "device = \"vendor:amd\"\n");
+        let config_args = args(&["vulkanrust", "--config", &path]);
+        env::remove_var(DEVICE_VARIABLE);
+        let from_file = RendererConfig::from_args(&config_args).unwrap();
+        env::set_var(DEVICE_VARIABLE, "1");
+        let from_variable = RendererConfig::from_args(&config_args);
+        let mut flag_args = config_args.clone();
+        flag_args.extend(args(&["--device", "name:llvmpipe"]));
+        let from_flag = RendererConfig::from_args(&flag_args);
+        env::remove_var(DEVICE_VARIABLE);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(
+            from_file.device,
+            Some(DeviceSelector::Vendor("amd".to_string()))
+        );
+        assert_eq!(
+            from_variable.unwrap().device,
+            Some(DeviceSelector::Index(1))
+        );
+        assert_eq!(
+            from_flag.unwrap().device,
+            Some(DeviceSelector::Name("llvmpipe".to_string()))
+        );
+    }
+
+    #[test]
+    fn unknown_keys_are_errors() {
+        assert!(matches!(
+            RendererConfig::default().merge_toml("widht = 640\n"),
+            Err(Error::Config(_))
+        ));
+        assert!(matches!(
+            RendererConfig::default().merge_toml("[debug]\npanic_on_error = true\n"),
+            Err(Error::Config(_))
+        ));
+    }
+
+    #[test]
+    fn validation_in_the_file_is_required() {
+        let config = RendererConfig::default()
+            .merge_toml("[debug]\nvalidation = true\nmessage_types = [\"validation\"]\n")
+            .unwrap();
+        assert_eq!(config.debug.validation, Validation::Required);
+        assert_eq!(
+            config.debug.message_types,
+            vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION
+        );
+        let config = RendererConfig::default()
+            .merge_toml("[debug]\nvalidation = false\n")
+            .unwrap();
+        assert_eq!(config.debug.validation, Validation::Disabled);
+    }
+
+    #[test]
+    fn message_types_flag() {
+        let config = RendererConfig::from_args(&args(&[
+            "vulkanrust",
+            "--validation-message-types",
+            "validation, performance",
+        ]))
+        .unwrap();
+        assert_eq!(
+            config.debug.message_types,
+            vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION
+                | vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE
+        );
+        assert!(RendererConfig::from_args(&args(&[
+            "vulkanrust",
+            "--validation-message-types",
+            "verbose",
+        ]))
+        .is_err());
+    }
+}
@@ -0,0 +1,817 @@
+use crate::config::RendererConfig;
+use crate::error::{Error, Result};
+use crate::instance;
+use crate::swapchain::SwapchainSupportDetails;
+use ash::extensions::khr::Surface;
+use ash::vk;
+use std::ffi::{c_void, CStr};
+use std::fmt;
+use std::mem::size_of;
+use std::slice;
+
+pub const DEVICE_EXTENSIONS: &[*const i8] =
+    &[unsafe { CStr::from_bytes_with_nul_unchecked("VK_KHR_swapchain\0".as_bytes()).as_ptr() }];
+
+// Picks the selected device when there is a selection, otherwise the suitable device with the best score
+pub fn pick_physical_device(
+    entry: &ash::Entry,
+    instance: &ash::Instance,
+    surface: &vk::SurfaceKHR,
+    config: &RendererConfig,
+) -> Result<vk::PhysicalDevice> {
+    let devices = unsafe { instance.enumerate_physical_devices()? };
+    let mut best: Option<(DeviceScore, vk::PhysicalDevice)> = None;
+    for (index, device) in devices.into_iter().enumerate() {
+        let info = DeviceInfo::new(entry, instance, &device, index)?;
+        if let Some(selector) = &config.device {
+            if !selector.matches(&info) {
+                log::info!(
+                    "Rejected GPU {} ({}): not selected by {}",
+                    index,
+                    info.name,
+                    selector
+                );
+                continue;
+            }
+        }
+        if let Some(reason) = unsuitable_reason(entry, instance, &device, surface, config)? {
+            log::info!("Rejected GPU {} ({}): {}", index, info.name, reason);
+            continue;
+        }
+        let score = info.score();
+        log::info!(
+            "Accepted GPU {} ({}, {:?}, {} MiB of device local memory)",
+            index,
+            info.name,
+            info.device_type,
+            info.device_local_memory / (1024 * 1024)
+        );
+        if best.is_none_or(|(best_score, _)| score > best_score) {
+            best = Some((score, device));
+        }
+    }
+    match (best, &config.device) {
+        (Some((_, device)), _) => Ok(device),
+        (None, Some(selector)) => Err(Error::NoMatchingDevice(selector.to_string())),
+        (None, None) => Err(Error::NoSuitableDevice),
+    }
+}
+
+// Says why the renderer can't run on a device, or None when it can
+pub fn unsuitable_reason(
+    entry: &ash::Entry,
+    instance: &ash::Instance,
+    device: &vk::PhysicalDevice,
+    surface: &vk::SurfaceKHR,
+    config: &RendererConfig,
+) -> Result<Option<String>> {
+    let indices = find_queue_familes(entry, instance, device, surface)?;
+    if indices.graphics.is_none() {
+        return Ok(Some("no graphics queue family".to_string()));
+    }
+    if let Some(feature) = missing_feature(instance, device) {
+        return Ok(Some(format!("the {} feature is not supported", feature)));
+    }
+    let limits = unsafe { instance.get_physical_device_properties(*device) }.limits;
+    if let Some(limit) = missing_limit(&limits, &REQUIRED_LIMITS) {
+        return Ok(Some(limit));
+    }
+    let largest_side = config.width.max(config.height);
+    if limits.max_image_dimension2_d < largest_side
+        || limits.max_framebuffer_width < config.width
+        || limits.max_framebuffer_height < config.height
+    {
+        return Ok(Some(format!(
+            "can't render {}x{} images",
+            config.width, config.height
+        )));
+    }
+    if *surface == vk::SurfaceKHR::null() {
+        return Ok(None);
+    }
+    if indices.present.is_none() {
+        return Ok(Some(
+            "no queue family can present to the surface".to_string(),
+        ));
+    }
+    if !check_device_extension_support(instance, device)? {
+        return Ok(Some(
+            "the swap chain extension is not supported".to_string(),
+        ));
+    }
+    let swap_chain_support = SwapchainSupportDetails::new(entry, instance, device, surface)?;
+    if swap_chain_support.formats.is_empty() || swap_chain_support.present_modes.is_empty() {
+        return Ok(Some(
+            "no surface formats or present modes are supported".to_string(),
+        ));
+    }
+    Ok(None)
+}
+
+// The features create_logical_device enables, every one of them has to be supported
+pub fn required_features() -> vk::PhysicalDeviceFeatures {
+    vk::PhysicalDeviceFeatures {
+        ..Default::default()
+    }
+}
+
+// Limits the renderer's pipelines and shaders rely on
+#[derive(Clone, Copy, Debug)]
+pub struct RequiredLimits {
+    // The biggest push constant block a pipeline declares
+    pub push_constants_size: u32,
+    // The biggest uniform buffer one descriptor covers
+    pub uniform_buffer_range: u32,
+}
+
+pub const REQUIRED_LIMITS: RequiredLimits = RequiredLimits {
+    // The tint pushed before each draw
+    push_constants_size: 16,
+    // The view and projection matrices
+    uniform_buffer_range: 128,
+};
+
+// Describes the first limit the device falls short of, or None when it has all of them
+pub fn missing_limit(
+    limits: &vk::PhysicalDeviceLimits,
+    required: &RequiredLimits,
+) -> Option<String> {
+    if limits.max_push_constants_size < required.push_constants_size {
+        return Some(format!(
+            "only {} bytes of push constants are supported, {} are needed",
+            limits.max_push_constants_size, required.push_constants_size
+        ));
+    }
+    if limits.max_uniform_buffer_range < required.uniform_buffer_range {
+        return Some(format!(
+            "uniform buffers can only cover {} bytes, {} are needed",
+            limits.max_uniform_buffer_range, required.uniform_buffer_range
+        ));
+    }
+    // Each object's slot in the dynamic uniform buffers starts on a multiple of the alignment
+    if !limits.min_uniform_buffer_offset_alignment.is_power_of_two() {
+        return Some(format!(
+            "the uniform buffer offset alignment {} is not a power of two",
+            limits.min_uniform_buffer_offset_alignment
+        ));
+    }
+    None
+}
+
+// Features that are enabled when the device has them, whatever uses one checks for it first
+fn optional_features(
+    instance: &ash::Instance,
+    device: &vk::PhysicalDevice,
+) -> vk::PhysicalDeviceFeatures {
+    let supported = unsafe { instance.get_physical_device_features(*device) };
+    vk::PhysicalDeviceFeatures {
+        sample_rate_shading: supported.sample_rate_shading,
+        sampler_anisotropy: supported.sampler_anisotropy,
+        image_cube_array: supported.image_cube_array,
+        ..Default::default()
+    }
+}
+
+pub fn supports_sample_rate_shading(instance: &ash::Instance, device: &vk::PhysicalDevice) -> bool {
+    optional_features(instance, device).sample_rate_shading == vk::TRUE
+}
+
+// CUBE_ARRAY views need this, single cube maps don't
+pub fn supports_image_cube_array(instance: &ash::Instance, device: &vk::PhysicalDevice) -> bool {
+    optional_features(instance, device).image_cube_array == vk::TRUE
+}
+
+// How many bytes of push constants pipelines can declare, at least 128
+pub fn max_push_constants_size(instance: &ash::Instance, device: &vk::PhysicalDevice) -> u32 {
+    unsafe { instance.get_physical_device_properties(*device) }
+        .limits
+        .max_push_constants_size
+}
+
+// The most anisotropy samplers can use, None when the device can't filter anisotropically
+pub fn max_sampler_anisotropy(
+    instance: &ash::Instance,
+    device: &vk::PhysicalDevice,
+) -> Option<f32> {
+    if optional_features(instance, device).sampler_anisotropy == vk::FALSE {
+        return None;
+    }
+    Some(
+        unsafe { instance.get_physical_device_properties(*device) }
+            .limits
+            .max_sampler_anisotropy,
+    )
+}
+
+// The highest sample count that both color and depth attachments support, up to requested
+pub fn clamp_sample_count(
+    instance: &ash::Instance,
+    device: &vk::PhysicalDevice,
+    requested: u32,
+) -> vk::SampleCountFlags {
+    let limits = unsafe { instance.get_physical_device_properties(*device) }.limits;
+    let supported = limits.framebuffer_color_sample_counts & limits.framebuffer_depth_sample_counts;
+    [
+        vk::SampleCountFlags::TYPE_64,
+        vk::SampleCountFlags::TYPE_32,
+        vk::SampleCountFlags::TYPE_16,
+        vk::SampleCountFlags::TYPE_8,
+        vk::SampleCountFlags::TYPE_4,
+        vk::SampleCountFlags::TYPE_2,
+    ]
+    .into_iter()
+    .find(|count| count.as_raw() <= requested && supported.contains(*count))
+    .unwrap_or(vk::SampleCountFlags::TYPE_1)
+}
+
+// The name of the first required feature the device doesn't support
+fn missing_feature(instance: &ash::Instance, device: &vk::PhysicalDevice) -> Option<&'static str> {
+    let supported = unsafe { instance.get_physical_device_features(*device) };
+    first_missing_feature(&required_features(), &supported)
+}
+
+fn first_missing_feature(
+    required: &vk::PhysicalDeviceFeatures,
+    supported: &vk::PhysicalDeviceFeatures,
+) -> Option<&'static str> {
+    FEATURE_NAMES
+        .iter()
+        .zip(feature_bits(required).iter().zip(feature_bits(supported)))
+        .find(|(_, (required, supported))| **required == vk::TRUE && **supported != vk::TRUE)
+        .map(|(name, _)| *name)
+}
+
+// VkPhysicalDeviceFeatures is nothing but VkBool32s, one per feature in declaration order
+pub fn feature_bits(features: &vk::PhysicalDeviceFeatures) -> &[vk::Bool32] {
+    unsafe {
+        slice::from_raw_parts(
+            features as *const vk::PhysicalDeviceFeatures as *const vk::Bool32,
+            size_of::<vk::PhysicalDeviceFeatures>() / size_of::<vk::Bool32>(),
+        )
+    }
+}
+
+pub const FEATURE_NAMES: [&str; 55] = [
+    "robustBufferAccess",
+    "fullDrawIndexUint32",
+    "imageCubeArray",
+    "independentBlend",
+    "geometryShader",
+    "tessellationShader",
+    "sampleRateShading",
+    "dualSrcBlend",
+    "logicOp",
+    "multiDrawIndirect",
+    "drawIndirectFirstInstance",
+    "depthClamp",
+    "depthBiasClamp",
+    "fillModeNonSolid",
+    "depthBounds",
+    "wideLines",
+    "largePoints",
+    "alphaToOne",
+    "multiViewport",
+    "samplerAnisotropy",
+    "textureCompressionETC2",
+    "textureCompressionASTC_LDR",
+    "textureCompressionBC",
+    "occlusionQueryPrecise",
+    "pipelineStatisticsQuery",
+    "vertexPipelineStoresAndAtomics",
+    "fragmentStoresAndAtomics",
+    "shaderTessellationAndGeometryPointSize",
+    "shaderImageGatherExtended",
+    "shaderStorageImageExtendedFormats",
+    "shaderStorageImageMultisample",
+    "shaderStorageImageReadWithoutFormat",
+    "shaderStorageImageWriteWithoutFormat",
+    "shaderUniformBufferArrayDynamicIndexing",
+    "shaderSampledImageArrayDynamicIndexing",
+    "shaderStorageBufferArrayDynamicIndexing",
+    "shaderStorageImageArrayDynamicIndexing",
+    "shaderClipDistance",
+    "shaderCullDistance",
+    "shaderFloat64",
+    "shaderInt64",
+    "shaderInt16",
+    "shaderResourceResidency",
+    "shaderResourceMinLod",
+    "sparseBinding",
+    "sparseResidencyBuffer",
+    "sparseResidencyImage2D",
+    "sparseResidencyImage3D",
+    "sparseResidency2Samples",
+    "sparseResidency4Samples",
+    "sparseResidency8Samples",
+    "sparseResidency16Samples",
+    "sparseResidencyAliased",
+    "variableMultisampleRate",
+    "inheritedQueries",
+];
+
+// Devices are ranked by type first, and by how much device local memory they have after that
+pub type DeviceScore = (u32, vk::DeviceSize);
+
+// What we know about a physical device, enough to select, rank and describe it
+#[derive(Clone, Debug)]
+pub struct DeviceInfo {
+    pub index: usize,
+    pub name: String,
+    pub device_type: vk::PhysicalDeviceType,
+    pub vendor_id: u32,
+    pub device_id: u32,
+    pub api_version: u32,
+    pub driver_version: u32,
+    // Only known when the device supports VK_KHR_driver_properties
+    pub driver: Option<DriverInfo>,
+    pub device_local_memory: vk::DeviceSize,
+}
+
+#[derive(Clone, Debug)]
+pub struct DriverInfo {
+    pub id: vk::DriverId,
+    pub name: String,
+    pub info: String,
+}
+
+impl DeviceInfo {
+    pub fn new(
+        entry: &ash::Entry,
+        instance: &ash::Instance,
+        device: &vk::PhysicalDevice,
+        index: usize,
+    ) -> Result<Self> {
+        let properties = unsafe { instance.get_physical_device_properties(*device) };
+        let memory_properties = unsafe { instance.get_physical_device_memory_properties(*device) };
+        let device_local_memory = memory_properties.memory_heaps
+            [..memory_properties.memory_heap_count as usize]
+            .iter()
+            .filter(|heap| heap.flags.contains(vk::MemoryHeapFlags::DEVICE_LOCAL))
+            .map(|heap| heap.size)
+            .sum();
+        Ok(Self {
+            index,
+            name: unsafe { CStr::from_ptr(properties.device_name.as_ptr()) }
+                .to_string_lossy()
+                .into_owned(),
+            device_type: properties.device_type,
+            vendor_id: properties.vendor_id,
+            device_id: properties.device_id,
+            api_version: properties.api_version,
+            driver_version: properties.driver_version,
+            driver: driver_info(entry, instance, device, &properties)?,
+            device_local_memory,
+        })
+    }
+    pub fn score(&self) -> DeviceScore {
+        let type_rank = match self.device_type {
+            vk::PhysicalDeviceType::DISCRETE_GPU => 4,
+            vk::PhysicalDeviceType::INTEGRATED_GPU => 3,
+            vk::PhysicalDeviceType::VIRTUAL_GPU => 2,
+            vk::PhysicalDeviceType::CPU => 1,
+            _ => 0,
+        };
+        (type_rank, self.device_local_memory)
+    }
+    // The PCI vendor name, for the vendors that make Vulkan drivers
+    pub fn vendor_name(&self) -> Option<&'static str> {
+        match self.vendor_id {
+            0x1002 => Some("AMD"),
+            0x1010 => Some("ImgTec"),
+            0x106b => Some("Apple"),
+            0x10de => Some("NVIDIA"),
+            0x13b5 => Some("ARM"),
+            0x5143 => Some("Qualcomm"),
+            0x8086 => Some("Intel"),
+            0x10005 => Some("Mesa"),
+            _ => None,
+        }
+    }
+}
+
+// Reading the driver properties needs Vulkan 1.1 on both the instance and the device
+fn driver_info(
+    entry: &ash::Entry,
+    instance: &ash::Instance,
+    device: &vk::PhysicalDevice,
+    properties: &vk::PhysicalDeviceProperties,
+) -> Result<Option<DriverInfo>> {
+    if instance::api_version(entry)? < vk::API_VERSION_1_1
+        || properties.api_version < vk::API_VERSION_1_1
+        || !supports_extension(instance, device, vk::KhrDriverPropertiesFn::name())?
+    {
+        return Ok(None);
+    }
+    let mut driver_properties = vk::PhysicalDeviceDriverProperties {
+        s_type: vk::StructureType::PHYSICAL_DEVICE_DRIVER_PROPERTIES,
+        ..Default::default()
+    };
+    let mut properties2 = vk::PhysicalDeviceProperties2 {
+        s_type: vk::StructureType::PHYSICAL_DEVICE_PROPERTIES_2,
+        p_next: &mut driver_properties as *mut _ as *mut c_void,
+        ..Default::default()
+    };
+    unsafe { instance.get_physical_device_properties2(*device, &mut properties2) };
+    Ok(Some(DriverInfo {
+        id: driver_properties.driver_id,
+        name: unsafe { CStr::from_ptr(driver_properties.driver_name.as_ptr()) }
+            .to_string_lossy()
+            .into_owned(),
+        info: unsafe { CStr::from_ptr(driver_properties.driver_info.as_ptr()) }
+            .to_string_lossy()
+            .into_owned(),
+    }))
+}
+
+// Reading the heap budgets also goes through the Vulkan 1.1 properties2 queries
+pub fn supports_memory_budget(
+    entry: &ash::Entry,
+    instance: &ash::Instance,
+    device: &vk::PhysicalDevice,
+) -> Result<bool> {
+    let properties = unsafe { instance.get_physical_device_properties(*device) };
+    Ok(instance::api_version(entry)? >= vk::API_VERSION_1_1
+        && properties.api_version >= vk::API_VERSION_1_1
+        && supports_extension(instance, device, vk::ExtMemoryBudgetFn::name())?)
+}
+
+fn supports_extension(
+    instance: &ash::Instance,
+    device: &vk::PhysicalDevice,
+    name: &CStr,
+) -> Result<bool> {
+    Ok(
+        unsafe { instance.enumerate_device_extension_properties(*device)? }
+            .iter()
+            .any(|extension| unsafe { CStr::from_ptr(extension.extension_name.as_ptr()) == name }),
+    )
+}
+
+// Which device to run on, from the config file, the VULKANRUST_DEVICE variable or --device.
+// A number is an index into the device list, "vendor:<name>" matches the PCI vendor or the driver,
+// and anything else matches part of the device name. Matching ignores case.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DeviceSelector {
+    Index(usize),
+    Name(String),
+    Vendor(String),
+}
+
+impl DeviceSelector {
+    pub fn parse(text: &str) -> Self {
+        if let Ok(index) = text.parse() {
+            DeviceSelector::Index(index)
+        } else if let Some(vendor) = text.strip_prefix("vendor:") {
+            DeviceSelector::Vendor(vendor.to_lowercase())
+        } else {
+            DeviceSelector::Name(text.strip_prefix("name:").unwrap_or(text).to_lowercase())
+        }
+    }
+    pub fn matches(&self, info: &DeviceInfo) -> bool {
+        match self {
+            DeviceSelector::Index(index) => info.index == *index,
+            DeviceSelector::Name(name) => info.name.to_lowercase().contains(name),
+            DeviceSelector::Vendor(vendor) => {
+                let driver_names = info.driver.iter().flat_map(|driver| {
+                    [
+                        format!("{:?}", driver.id),
+                        driver.name.clone(),
+                        driver.info.clone(),
+                    ]
+                });
+                info.vendor_name()
+                    .map(str::to_string)
+                    .into_iter()
+                    .chain(driver_names)
+                    .any(|name| name.to_lowercase().contains(vendor))
+            }
+        }
+    }
+}
+
+impl fmt::Display for DeviceSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeviceSelector::Index(index) => write!(f, "index {}", index),
+            DeviceSelector::Name(name) => write!(f, "name \"{}\"", name),
+            DeviceSelector::Vendor(vendor) => write!(f, "vendor \"{}\"", vendor),
+        }
+    }
+}
+
+pub fn check_device_extension_support(
+    instance: &ash::Instance,
+    device: &vk::PhysicalDevice,
+) -> Result<bool> {
+    let extension_properties = unsafe { instance.enumerate_device_extension_properties(*device)? };
+    for device_extension in DEVICE_EXTENSIONS {
+        let device_extension = unsafe { CStr::from_ptr(*device_extension) };
+        if !extension_properties
+            .iter()
+            .any(|extension_property| unsafe {
+                CStr::from_ptr(extension_property.extension_name.as_ptr()) == device_extension
+            })
+        {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+// The queue families the renderer takes its queues from. Transfer and compute are only set when the
+// device has families for them that can't do graphics, queues from those run alongside rendering.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct QueueFamilyIndices {
+    pub graphics: Option<usize>,
+    pub present: Option<usize>,
+    pub transfer: Option<usize>,
+    pub compute: Option<usize>,
+}
+
+impl QueueFamilyIndices {
+    // Uploads fall back to the graphics family when there is no dedicated transfer family
+    pub fn transfer_or_graphics(&self) -> Option<usize> {
+        self.transfer.or(self.graphics)
+    }
+    pub fn compute_or_graphics(&self) -> Option<usize> {
+        self.compute.or(self.graphics)
+    }
+    // Every family a queue is created from, each of them once
+    pub fn unique(&self) -> Vec<u32> {
+        let mut families: Vec<u32> = [self.graphics, self.present, self.transfer, self.compute]
+            .into_iter()
+            .flatten()
+            .map(|family| family as u32)
+            .collect();
+        families.sort_unstable();
+        families.dedup();
+        families
+    }
+}
+
+pub fn find_queue_familes(
+    entry: &ash::Entry,
+    instance: &ash::Instance,
+    device: &vk::PhysicalDevice,
+    surface: &vk::SurfaceKHR,
+) -> Result<QueueFamilyIndices> {
+    let queue_family_properties =
+        unsafe { instance.get_physical_device_queue_family_properties(*device) };
+    let mut indices = dedicated_queue_families(&queue_family_properties);
+    // Nothing can be presented without a surface, so there is no present queue to look for
+    if *surface == vk::SurfaceKHR::null() {
+        return Ok(indices);
+    }
+    let surface_details = Surface::new(entry, instance);
+    for index in 0..queue_family_properties.len() {
+        if unsafe {
+            surface_details.get_physical_device_surface_support(*device, index as u32, *surface)?
+        } {
+            indices.present = Some(index);
+            break;
+        }
+    }
+    Ok(indices)
+}
+
+// Picks the graphics, transfer and compute families, everything but the present family which
+// needs the surface
+fn dedicated_queue_families(
+    queue_family_properties: &[vk::QueueFamilyProperties],
+) -> QueueFamilyIndices {
+    let has = |queue_family: &vk::QueueFamilyProperties, flags: vk::QueueFlags| {
+        queue_family.queue_flags & flags == flags
+    };
+    let graphics = queue_family_properties
+        .iter()
+        .position(|queue_family| has(queue_family, vk::QueueFlags::GRAPHICS));
+    // Compute families can always transfer, even when they don't say so. A family that does nothing
+    // but transfers is usually backed by a copy engine, so it is the first choice.
+    let can_transfer = |queue_family: &vk::QueueFamilyProperties| {
+        has(queue_family, vk::QueueFlags::TRANSFER) || has(queue_family, vk::QueueFlags::COMPUTE)
+    };
+    let transfer = queue_family_properties
+        .iter()
+        .position(|queue_family| {
+            can_transfer(queue_family)
+                && !has(queue_family, vk::QueueFlags::GRAPHICS)
+                && !has(queue_family, vk::QueueFlags::COMPUTE)
+        })
+        .or_else(|| {
+            queue_family_properties.iter().position(|queue_family| {
+                can_transfer(queue_family) && !has(queue_family, vk::QueueFlags::GRAPHICS)
+            })
+        });
+    let async_compute = |queue_family: &vk::QueueFamilyProperties| {
+        has(queue_family, vk::QueueFlags::COMPUTE) && !has(queue_family, vk::QueueFlags::GRAPHICS)
+    };
+    // Compute and transfer only share a family when there is no other one
+    let compute = queue_family_properties
+        .iter()
+        .enumerate()
+        .position(|(index, queue_family)| async_compute(queue_family) && Some(index) != transfer)
+        .or_else(|| queue_family_properties.iter().position(async_compute));
+    QueueFamilyIndices {
+        graphics,
+        present: None,
+        transfer,
+        compute,
+    }
+}
+
+pub fn create_logical_device(
+    entry: &ash::Entry,
+    instance: &ash::Instance,
+    physical_device: &vk::PhysicalDevice,
+    surface: &vk::SurfaceKHR,
+) -> Result<ash::Device> {
+    let indices = find_queue_familes(entry, instance, physical_device, surface)?;
+    indices.graphics.ok_or(Error::NoSuitableDevice)?;
+    let mut device_extensions: Vec<*const i8> = if *surface == vk::SurfaceKHR::null() {
+        Vec::new()
+    } else {
+        DEVICE_EXTENSIONS.to_vec()
+    };
+    // Optional, the allocator uses it to stay within each heap's budget
+    if supports_memory_budget(entry, instance, physical_device)? {
+        device_extensions.push(vk::ExtMemoryBudgetFn::name().as_ptr());
+    }
+    let mut device_queue_create_infos = Vec::new();
+    for queue in indices.unique() {
+        device_queue_create_infos.push(vk::DeviceQueueCreateInfo {
+            s_type: vk::StructureType::DEVICE_QUEUE_CREATE_INFO,
+            queue_family_index: queue,
+            queue_count: 1,
+            p_queue_priorities: &1.0,
+            ..Default::default()
+        })
+    }
+    let optional = optional_features(instance, physical_device);
+    let device_features = vk::PhysicalDeviceFeatures {
+        sample_rate_shading: optional.sample_rate_shading,
+        sampler_anisotropy: optional.sampler_anisotropy,
+        image_cube_array: optional.image_cube_array,
+        ..required_features()
+    };
+    // No device layers, they are ignored and the instance's validation layer covers the device too
+    let device_create_info = vk::DeviceCreateInfo {
+        s_type: vk::StructureType::DEVICE_CREATE_INFO,
+        queue_create_info_count: device_queue_create_infos.len() as u32,
+        p_queue_create_infos: device_queue_create_infos.as_ptr(),
+        p_enabled_features: &device_features,
+        enabled_extension_count: device_extensions.len() as u32,
+        pp_enabled_extension_names: device_extensions.as_ptr(),
+        ..Default::default()
+    };
+    Ok(unsafe { instance.create_device(*physical_device, &device_create_info, None)? })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(index: usize, name: &str, device_type: vk::PhysicalDeviceType) -> DeviceInfo {
+        DeviceInfo {
+            index,
+            name: name.to_string(),
+            device_type,
+            vendor_id: 0x10de,
+            device_id: 0,
+            api_version: vk::API_VERSION_1_1,
+            driver_version: 0,
+            driver: None,
+            device_local_memory: 0,
+        }
+    }
+
+    #[test]
+    fn parse_selectors() {
+        assert_eq!(DeviceSelector::parse("1"), DeviceSelector::Index(1));
+        assert_eq!(
+            DeviceSelector::parse("vendor:NVIDIA"),
+            DeviceSelector::Vendor("nvidia".to_string())
+        );
+        assert_eq!(
+            DeviceSelector::parse("name:2080"),
+            DeviceSelector::Name("2080".to_string())
+        );
+        assert_eq!(
+            DeviceSelector::parse("GeForce"),
+            DeviceSelector::Name("geforce".to_string())
+        );
+    }
+
+    #[test]
+    fn selectors_match() {
+        let mut device = info(
+            1,
+            "NVIDIA GeForce RTX 2080",
+            vk::PhysicalDeviceType::DISCRETE_GPU,
+        );
+        assert!(DeviceSelector::parse("1").matches(&device));
+        assert!(!DeviceSelector::parse("0").matches(&device));
+        assert!(DeviceSelector::parse("rtx").matches(&device));
+        assert!(!DeviceSelector::parse("radeon").matches(&device));
+        assert!(DeviceSelector::parse("vendor:nvidia").matches(&device));
+        assert!(!DeviceSelector::parse("vendor:mesa").matches(&device));
+        // The driver counts as a vendor too
+        device.driver = Some(DriverInfo {
+            id: vk::DriverId::MESA_LLVMPIPE,
+            name: "llvmpipe".to_string(),
+            info: "Mesa 23.0".to_string(),
+        });
+        assert!(DeviceSelector::parse("vendor:mesa").matches(&device));
+    }
+
+    #[test]
+    fn type_ranks_before_memory() {
+        let mut integrated = info(0, "integrated", vk::PhysicalDeviceType::INTEGRATED_GPU);
+        integrated.device_local_memory = 16 << 30;
+        let mut discrete = info(1, "discrete", vk::PhysicalDeviceType::DISCRETE_GPU);
+        discrete.device_local_memory = 4 << 30;
+        let mut bigger = discrete.clone();
+        bigger.device_local_memory = 8 << 30;
+        let cpu = info(2, "cpu", vk::PhysicalDeviceType::CPU);
+        assert!(discrete.score() > integrated.score());
+        assert!(bigger.score() > discrete.score());
+        assert!(integrated.score() > cpu.score());
+    }
+
+    #[test]
+    fn limits_are_checked() {
+        let limits = vk::PhysicalDeviceLimits {
+            max_push_constants_size: 128,
+            max_uniform_buffer_range: 16384,
+            min_uniform_buffer_offset_alignment: 256,
+            ..Default::default()
+        };
+        assert_eq!(missing_limit(&limits, &REQUIRED_LIMITS), None);
+        let small = vk::PhysicalDeviceLimits {
+            max_push_constants_size: 8,
+            ..limits
+        };
+        assert!(missing_limit(&small, &REQUIRED_LIMITS).is_some());
+        let unaligned = vk::PhysicalDeviceLimits {
+            min_uniform_buffer_offset_alignment: 48,
+            ..limits
+        };
+        assert!(missing_limit(&unaligned, &REQUIRED_LIMITS).is_some());
+    }
+
+    #[test]
+    fn missing_features_are_named() {
+        // Anisotropic filtering and the rest are optional, any device will do
+        assert!(feature_bits(&required_features())
+            .iter()
+            .all(|&enabled| enabled == vk::FALSE));
+        let required = vk::PhysicalDeviceFeatures {
+            image_cube_array: vk::TRUE,
+            sampler_anisotropy: vk::TRUE,
+            ..Default::default()
+        };
+        let supported = vk::PhysicalDeviceFeatures {
+            image_cube_array: vk::TRUE,
+            ..Default::default()
+        };
+        assert_eq!(
+            first_missing_feature(&required, &supported),
+            Some("samplerAnisotropy")
+        );
+        assert_eq!(first_missing_feature(&required, &required), None);
+    }
+
+    fn family(queue_flags: vk::QueueFlags) -> vk::QueueFamilyProperties {
+        vk::QueueFamilyProperties {
+            queue_flags,
+            queue_count: 1,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn dedicated_families_are_preferred() {
+        let graphics =
+            vk::QueueFlags::GRAPHICS | vk::QueueFlags::COMPUTE | vk::QueueFlags::TRANSFER;
+        let compute = vk::QueueFlags::COMPUTE | vk::QueueFlags::TRANSFER;
+        let indices = dedicated_queue_families(&[
+            family(graphics),
+            family(compute),
+            family(vk::QueueFlags::TRANSFER),
+        ]);
+        assert_eq!(indices.graphics, Some(0));
+        assert_eq!(indices.transfer, Some(2));
+        assert_eq!(indices.compute, Some(1));
+        assert_eq!(indices.unique(), [0, 1, 2]);
+
+        // With a single compute family, transfer and compute share it
+        let indices = dedicated_queue_families(&[family(graphics), family(compute)]);
+        assert_eq!((indices.transfer, indices.compute), (Some(1), Some(1)));
+        assert_eq!(indices.unique(), [0, 1]);
+
+        // A device with one family does everything on it
+        let indices = dedicated_queue_families(&[family(graphics)]);
+        assert_eq!((indices.transfer, indices.compute), (None, None));
+        assert_eq!(indices.compute_or_graphics(), Some(0));
+        assert_eq!(indices.unique(), [0]);
+    }
+}
@@ -0,0 +1,111 @@
+use ash::vk;
+use std::fmt;
+use std::path::PathBuf;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+// Everything that can go wrong while setting up or driving the renderer
+#[derive(Debug)]
+pub enum Error {
+    // A Vulkan call returned an error code
+    Vulkan(vk::Result),
+    MissingLayer(String),
+    // The validation layer reported an error while fail_on_error was on
+    Validation,
+    MissingExtension(String),
+    NoSuitableDevice,
+    // A device was selected, but none of the suitable devices match the selection
+    NoMatchingDevice(String),
+    NoSuitableMemoryType,
+    // Memory types exist for the allocation, but every heap they are in is out of budget
+    MemoryBudgetExceeded(vk::DeviceSize),
+    // None of the formats that would do can be used the way we need
+    UnsupportedFormat(String),
+    ShaderLoad {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    // A texture file couldn't be read or decoded
+    TextureLoad {
+        path: PathBuf,
+        reason: String,
+    },
+    // A mesh file couldn't be read, or has nothing to draw
+    MeshLoad {
+        path: PathBuf,
+        reason: String,
+    },
+    // The window could not be created, or its handle isn't one we can make a surface for
+    Window(winit::error::OsError),
+    UnsupportedWindowHandle,
+    Image(png::EncodingError),
+    // A config file or command line flag couldn't be understood
+    Config(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Vulkan(result) => write!(f, "Vulkan call failed: {}", result),
+            Error::MissingLayer(name) => write!(f, "Layer {} is not available", name),
+            Error::Validation => write!(f, "The validation layer reported an error, see the log"),
+            Error::MissingExtension(name) => write!(f, "Extension {} is not available", name),
+            Error::NoSuitableDevice => write!(f, "Failed to find a suitable GPU!"),
+            Error::NoMatchingDevice(selector) => {
+                write!(f, "No suitable GPU matches the {}", selector)
+            }
+            Error::NoSuitableMemoryType => write!(f, "Unable to find suitable memory type!"),
+            Error::MemoryBudgetExceeded(size) => {
+                write!(f, "No memory heap has {} bytes left in its budget", size)
+            }
+            Error::UnsupportedFormat(formats) => {
+                write!(f, "None of the formats are supported: {}", formats)
+            }
+            Error::ShaderLoad { path, source } => {
+                write!(f, "Unable to load shader {}: {}", path.display(), source)
+            }
+            Error::TextureLoad { path, reason } => {
+                write!(f, "Unable to load texture {}: {}", path.display(), reason)
+            }
+            Error::MeshLoad { path, reason } => {
+                write!(f, "Unable to load mesh {}: {}", path.display(), reason)
+            }
+            Error::Window(error) => write!(f, "Unable to create window: {}", error),
+            Error::UnsupportedWindowHandle => {
+                write!(f, "Unable to create a surface for this kind of window")
+            }
+            Error::Image(error) => write!(f, "Unable to write image: {}", error),
+            Error::Config(message) => write!(f, "Invalid configuration: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Vulkan(result) => Some(result),
+            Error::ShaderLoad { source, .. } => Some(source),
+            Error::Window(error) => Some(error),
+            Error::Image(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<vk::Result> for Error {
+    fn from(result: vk::Result) -> Self {
+        Error::Vulkan(result)
+    }
+}
+
+impl From<winit::error::OsError> for Error {
+    fn from(error: winit::error::OsError) -> Self {
+        Error::Window(error)
+    }
+}
+
+impl From<png::EncodingError> for Error {
+    fn from(error: png::EncodingError) -> Self {
+        Error::Image(error)
+    }
+}
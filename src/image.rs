@@ -0,0 +1,293 @@
+use crate::error::{Error, Result};
+use crate::memory::{Allocation, MemoryAllocator, MemoryRequest};
+use ash::vk;
+
+// Depth formats in order of preference, every device supports at least one of them
+pub const DEPTH_FORMAT_CANDIDATES: [vk::Format; 3] = [
+    vk::Format::D32_SFLOAT,
+    vk::Format::D32_SFLOAT_S8_UINT,
+    vk::Format::D24_UNORM_S8_UINT,
+];
+
+// Everything about a 2D image besides its memory
+pub struct ImageInfo {
+    pub extent: vk::Extent2D,
+    pub format: vk::Format,
+    pub tiling: vk::ImageTiling,
+    pub usage: vk::ImageUsageFlags,
+    pub samples: vk::SampleCountFlags,
+    pub mip_levels: u32,
+    pub array_layers: u32,
+    // Lets every six array layers be viewed as the faces of a cube
+    pub cube: bool,
+}
+
+// Creates a 2D image with memory of its own from the allocator
+pub fn create_image(
+    device: &ash::Device,
+    allocator: &MemoryAllocator,
+    info: &ImageInfo,
+    request: MemoryRequest,
+) -> Result<(vk::Image, Allocation)> {
+    let image_info = vk::ImageCreateInfo {
+        s_type: vk::StructureType::IMAGE_CREATE_INFO,
+        flags: if info.cube {
+            vk::ImageCreateFlags::CUBE_COMPATIBLE
+        } else {
+            vk::ImageCreateFlags::empty()
+        },
+        image_type: vk::ImageType::TYPE_2D,
+        format: info.format,
+        extent: vk::Extent3D {
+            width: info.extent.width,
+            height: info.extent.height,
+            depth: 1,
+        },
+        mip_levels: info.mip_levels,
+        array_layers: info.array_layers,
+        samples: info.samples,
+        tiling: info.tiling,
+        usage: info.usage,
+        sharing_mode: vk::SharingMode::EXCLUSIVE,
+        initial_layout: vk::ImageLayout::UNDEFINED,
+        ..Default::default()
+    };
+    let image = unsafe { device.create_image(&image_info, None)? };
+
+    match allocator.allocate_image(image, request) {
+        Ok(allocation) => Ok((image, allocation)),
+        Err(error) => {
+            unsafe { device.destroy_image(image, None) };
+            Err(error)
+        }
+    }
+}
+
+pub fn destroy_image(
+    device: &ash::Device,
+    allocator: &MemoryAllocator,
+    image: vk::Image,
+    allocation: &Allocation,
+) {
+    unsafe { device.destroy_image(image, None) };
+    allocator.free(allocation);
+}
+
+// How shaders see an image with these layers: sampler2D, sampler2DArray, samplerCube or
+// samplerCubeArray
+pub fn view_type(array_layers: u32, cube: bool) -> vk::ImageViewType {
+    match (cube, array_layers) {
+        (true, 6) => vk::ImageViewType::CUBE,
+        (true, _) => vk::ImageViewType::CUBE_ARRAY,
+        (false, 1) => vk::ImageViewType::TYPE_2D,
+        (false, _) => vk::ImageViewType::TYPE_2D_ARRAY,
+    }
+}
+
+pub fn create_image_view(
+    device: &ash::Device,
+    image: vk::Image,
+    format: vk::Format,
+    aspect_mask: vk::ImageAspectFlags,
+    view_type: vk::ImageViewType,
+    mip_levels: u32,
+    array_layers: u32,
+) -> Result<vk::ImageView> {
+    let create_info = vk::ImageViewCreateInfo {
+        s_type: vk::StructureType::IMAGE_VIEW_CREATE_INFO,
+        image,
+        view_type,
+        format,
+        components: vk::ComponentMapping {
+            r: vk::ComponentSwizzle::IDENTITY,
+            g: vk::ComponentSwizzle::IDENTITY,
+            b: vk::ComponentSwizzle::IDENTITY,
+            a: vk::ComponentSwizzle::IDENTITY,
+        },
+        subresource_range: vk::ImageSubresourceRange {
+            aspect_mask,
+            base_mip_level: 0,
+            level_count: mip_levels,
+            base_array_layer: 0,
+            layer_count: array_layers,
+        },
+        ..Default::default()
+    };
+    Ok(unsafe { device.create_image_view(&create_info, None)? })
+}
+
+// The first of candidates that supports features with the given tiling
+pub fn find_supported_format(
+    instance: &ash::Instance,
+    physical_device: &vk::PhysicalDevice,
+    candidates: &[vk::Format],
+    tiling: vk::ImageTiling,
+    features: vk::FormatFeatureFlags,
+) -> Result<vk::Format> {
+    candidates
+        .iter()
+        .copied()
+        .find(|format| {
+            let properties = unsafe {
+                instance.get_physical_device_format_properties(*physical_device, *format)
+            };
+            match tiling {
+                vk::ImageTiling::LINEAR => properties.linear_tiling_features.contains(features),
+                _ => properties.optimal_tiling_features.contains(features),
+            }
+        })
+        .ok_or_else(|| Error::UnsupportedFormat(format!("{:?} with {:?}", candidates, features)))
+}
+
+// Mipmaps are generated by blitting each level into the next one with linear filtering
+pub fn supports_linear_blit(
+    instance: &ash::Instance,
+    physical_device: &vk::PhysicalDevice,
+    format: vk::Format,
+) -> bool {
+    let properties =
+        unsafe { instance.get_physical_device_format_properties(*physical_device, format) };
+    properties.optimal_tiling_features.contains(
+        vk::FormatFeatureFlags::BLIT_SRC
+            | vk::FormatFeatureFlags::BLIT_DST
+            | vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR,
+    )
+}
+
+pub fn find_depth_format(
+    instance: &ash::Instance,
+    physical_device: &vk::PhysicalDevice,
+) -> Result<vk::Format> {
+    find_supported_format(
+        instance,
+        physical_device,
+        &DEPTH_FORMAT_CANDIDATES,
+        vk::ImageTiling::OPTIMAL,
+        vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT,
+    )
+}
+
+pub fn has_stencil_component(format: vk::Format) -> bool {
+    format == vk::Format::D32_SFLOAT_S8_UINT || format == vk::Format::D24_UNORM_S8_UINT
+}
+
+// Views and barriers of a combined depth and stencil image have to cover both aspects
+pub fn depth_aspect_mask(format: vk::Format) -> vk::ImageAspectFlags {
+    if has_stencil_component(format) {
+        vk::ImageAspectFlags::DEPTH | vk::ImageAspectFlags::STENCIL
+    } else {
+        vk::ImageAspectFlags::DEPTH
+    }
+}
+
+// The depth image is only used within a render pass, which clears it, so it needs no transition
+pub fn create_depth_resources(
+    device: &ash::Device,
+    allocator: &MemoryAllocator,
+    format: vk::Format,
+    extent: &vk::Extent2D,
+    samples: vk::SampleCountFlags,
+) -> Result<(vk::Image, Allocation, vk::ImageView)> {
+    create_attachment(
+        device,
+        allocator,
+        format,
+        extent,
+        samples,
+        vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+        depth_aspect_mask(format),
+    )
+}
+
+// The multisampled image rendered to before it's resolved into the swap chain image
+pub fn create_color_resources(
+    device: &ash::Device,
+    allocator: &MemoryAllocator,
+    format: vk::Format,
+    extent: &vk::Extent2D,
+    samples: vk::SampleCountFlags,
+) -> Result<(vk::Image, Allocation, vk::ImageView)> {
+    create_attachment(
+        device,
+        allocator,
+        format,
+        extent,
+        samples,
+        vk::ImageUsageFlags::COLOR_ATTACHMENT,
+        vk::ImageAspectFlags::COLOR,
+    )
+}
+
+// Attachments that never leave the render pass are transient, so tiled GPUs can keep them in tile
+// memory and never back them with lazily allocated memory at all
+fn create_attachment(
+    device: &ash::Device,
+    allocator: &MemoryAllocator,
+    format: vk::Format,
+    extent: &vk::Extent2D,
+    samples: vk::SampleCountFlags,
+    usage: vk::ImageUsageFlags,
+    aspect_mask: vk::ImageAspectFlags,
+) -> Result<(vk::Image, Allocation, vk::ImageView)> {
+    let (image, allocation) = create_image(
+        device,
+        allocator,
+        &ImageInfo {
+            extent: *extent,
+            format,
+            tiling: vk::ImageTiling::OPTIMAL,
+            usage: usage | vk::ImageUsageFlags::TRANSIENT_ATTACHMENT,
+            samples,
+            mip_levels: 1,
+            array_layers: 1,
+            cube: false,
+        },
+        MemoryRequest::new(vk::MemoryPropertyFlags::DEVICE_LOCAL)
+            .prefer(vk::MemoryPropertyFlags::LAZILY_ALLOCATED),
+    )?;
+    match create_image_view(
+        device,
+        image,
+        format,
+        aspect_mask,
+        vk::ImageViewType::TYPE_2D,
+        1,
+        1,
+    ) {
+        Ok(view) => Ok((image, allocation, view)),
+        Err(error) => {
+            destroy_image(device, allocator, image, &allocation);
+            Err(error)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn view_types_follow_the_layers() {
+        assert_eq!(view_type(1, false), vk::ImageViewType::TYPE_2D);
+        assert_eq!(view_type(4, false), vk::ImageViewType::TYPE_2D_ARRAY);
+        assert_eq!(view_type(6, true), vk::ImageViewType::CUBE);
+        assert_eq!(view_type(12, true), vk::ImageViewType::CUBE_ARRAY);
+    }
+
+    #[test]
+    fn stencil_formats_cover_both_aspects() {
+        assert_eq!(
+            depth_aspect_mask(vk::Format::D32_SFLOAT),
+            vk::ImageAspectFlags::DEPTH
+        );
+        for format in [
+            vk::Format::D32_SFLOAT_S8_UINT,
+            vk::Format::D24_UNORM_S8_UINT,
+        ] {
+            assert_eq!(
+                depth_aspect_mask(format),
+                vk::ImageAspectFlags::DEPTH | vk::ImageAspectFlags::STENCIL
+            );
+        }
+    }
+}
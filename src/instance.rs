@@ -1,145 +1,324 @@
+use crate::config::RendererConfig;
+use crate::error::{Error, Result};
 use ash::extensions::ext::DebugUtils;
-use ash::extensions::khr::Surface;
-use ash::extensions::khr::Win32Surface;
-use ash::{prelude, vk, Entry};
-use std::ffi::c_void;
-use std::ffi::CStr;
-use winit::{
-    dpi::PhysicalSize,
-    event::{Event, WindowEvent},
-    event_loop::{ControlFlow, EventLoop},
-    window::WindowBuilder,
+use ash::extensions::khr::{
+    AndroidSurface, Surface, WaylandSurface, Win32Surface, XcbSurface, XlibSurface,
 };
+use ash::vk;
+use raw_window_handle::{HasRawWindowHandle, RawWindowHandle};
+use std::ffi::{c_void, CStr, CString};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::{ptr, slice};
 
-const WIDTH: u32 = 800;
-const HEIGHT: u32 = 600;
-
-const VALIDATION_LAYERS: &[*const i8] = &[unsafe {
+pub const VALIDATION_LAYERS: &[*const i8] = &[unsafe {
     CStr::from_bytes_with_nul_unchecked("VK_LAYER_KHRONOS_validation\0".as_bytes()).as_ptr()
 }];
 
-const REQUIRED_EXTENSIONS: &[*const i8] = &[
-    Surface::name().as_ptr(),
-    Win32Surface::name().as_ptr(),
-    DebugUtils::name().as_ptr(),
-];
+// Set by the debug callback when it sees an error and fail_on_error is on, its address is the user data
+static VALIDATION_ERROR: AtomicBool = AtomicBool::new(false);
 
-pub struct HelloTriangleApplication {
-    entry: ash::Entry,
-    instance: ash::Instance,
-    debug_messenger: vk::DebugUtilsMessengerEXT,
+// Whether the validation layer gets loaded
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Validation {
+    Disabled,
+    // Loaded when it is installed, otherwise a warning is logged and rendering goes on without it
+    IfAvailable,
+    // Creating the instance fails when the layer isn't installed
+    Required,
+}
+
+// Which validation messages get reported, and what happens when the validation layers find an error
+#[derive(Clone, Copy, Debug)]
+pub struct DebugConfig {
+    pub validation: Validation,
+    // Messages less severe than this are never reported
+    pub min_severity: vk::DebugUtilsMessageSeverityFlagsEXT,
+    pub message_types: vk::DebugUtilsMessageTypeFlagsEXT,
+    // Meant for tests. The callback can't unwind into the driver, so the error is recorded and
+    // returned by check_validation_error after the next submit or at teardown
+    pub fail_on_error: bool,
+}
+
+impl Default for DebugConfig {
+    fn default() -> Self {
+        Self {
+            validation: if cfg!(debug_assertions) {
+                Validation::IfAvailable
+            } else {
+                Validation::Disabled
+            },
+            min_severity: vk::DebugUtilsMessageSeverityFlagsEXT::WARNING,
+            message_types: vk::DebugUtilsMessageTypeFlagsEXT::GENERAL
+                | vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION
+                | vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE,
+            fail_on_error: false,
+        }
+    }
+}
+
+impl DebugConfig {
+    // Every severity from the minimum upwards, the severity bits are ordered from least to most severe
+    pub fn severity_mask(&self) -> vk::DebugUtilsMessageSeverityFlagsEXT {
+        [
+            vk::DebugUtilsMessageSeverityFlagsEXT::VERBOSE,
+            vk::DebugUtilsMessageSeverityFlagsEXT::INFO,
+            vk::DebugUtilsMessageSeverityFlagsEXT::WARNING,
+            vk::DebugUtilsMessageSeverityFlagsEXT::ERROR,
+        ]
+        .into_iter()
+        .filter(|severity| severity.as_raw() >= self.min_severity.as_raw())
+        .fold(
+            vk::DebugUtilsMessageSeverityFlagsEXT::empty(),
+            |mask, severity| mask | severity,
+        )
+    }
+}
+
+// The strings in the callback data are optional, so null pointers are read as empty strings
+unsafe fn lossy_c_str(pointer: *const i8) -> String {
+    if pointer.is_null() {
+        String::new()
+    } else {
+        CStr::from_ptr(pointer).to_string_lossy().into_owned()
+    }
 }
 
 extern "system" fn debug_callback(
-    _message_severity: vk::DebugUtilsMessageSeverityFlagsEXT,
-    _message_type: vk::DebugUtilsMessageTypeFlagsEXT,
+    message_severity: vk::DebugUtilsMessageSeverityFlagsEXT,
+    message_type: vk::DebugUtilsMessageTypeFlagsEXT,
     callback_data: *const vk::DebugUtilsMessengerCallbackDataEXT,
-    _: *mut c_void,
+    user_data: *mut c_void,
 ) -> vk::Bool32 {
-    print!("validation layer: {}", unsafe {
-        CStr::from_ptr((*callback_data).p_message).to_str().unwrap()
-    });
+    let level = match message_severity {
+        vk::DebugUtilsMessageSeverityFlagsEXT::ERROR => log::Level::Error,
+        vk::DebugUtilsMessageSeverityFlagsEXT::WARNING => log::Level::Warn,
+        vk::DebugUtilsMessageSeverityFlagsEXT::INFO => log::Level::Info,
+        _ => log::Level::Debug,
+    };
+    let callback_data = unsafe { &*callback_data };
+    let message_id_name = unsafe { lossy_c_str(callback_data.p_message_id_name) };
+    let message = unsafe { lossy_c_str(callback_data.p_message) };
+    let objects = if callback_data.object_count == 0 {
+        &[]
+    } else {
+        unsafe {
+            slice::from_raw_parts(callback_data.p_objects, callback_data.object_count as usize)
+        }
+    };
+    let object_names = objects
+        .iter()
+        .map(|object| {
+            let name = unsafe { lossy_c_str(object.p_object_name) };
+            if name.is_empty() {
+                format!("{:?} {:#x}", object.object_type, object.object_handle)
+            } else {
+                format!(
+                    "{:?} {:#x} \"{}\"",
+                    object.object_type, object.object_handle, name
+                )
+            }
+        })
+        .collect::<Vec<_>>();
+    log::log!(
+        target: "vulkan",
+        level,
+        "[{:?}] {} ({:#x}): {}{}",
+        message_type,
+        message_id_name,
+        callback_data.message_id_number,
+        message,
+        if object_names.is_empty() {
+            String::new()
+        } else {
+            format!(" [objects: {}]", object_names.join(", "))
+        }
+    );
+    if message_severity == vk::DebugUtilsMessageSeverityFlagsEXT::ERROR && !user_data.is_null() {
+        let validation_error = unsafe { &*(user_data as *const AtomicBool) };
+        validation_error.store(true, Ordering::Relaxed);
+    }
     vk::FALSE
 }
 
-impl HelloTriangleApplication {
-    fn check_validation_layer_support(entry: &ash::Entry) -> bool {
-        let layer_properties = entry.enumerate_instance_layer_properties().unwrap();
-        for layer in VALIDATION_LAYERS {
-            if let None = layer_properties.iter().find(|l| {
-                // This horrible construction is because Vulkan operates with C strings and Rust does not
-                unsafe {
-                    &CStr::from_ptr(l.layer_name.as_ptr()).to_str().unwrap()
-                        == &CStr::from_ptr(*layer).to_str().unwrap()
-                }
-            }) {
-                return false;
-            }
-        }
-        true
+// Without a window nothing is presented, so only the extensions that don't depend on a surface are enabled
+pub fn create_instance(
+    entry: &ash::Entry,
+    window: Option<&winit::window::Window>,
+    config: &RendererConfig,
+) -> Result<ash::Instance> {
+    let debug_config = &config.debug;
+    let validation = validation_enabled(entry, debug_config)?;
+    if !validation && debug_config.validation == Validation::IfAvailable {
+        log::warn!("The validation layer isn't installed, running without validation");
     }
-    fn init_vulkan(entry: &ash::Entry) -> prelude::VkResult<ash::Instance> {
-        if !HelloTriangleApplication::check_validation_layer_support(&entry) {
-            panic!("Could not find support for all layers!");
-        }
-        let app_info = vk::ApplicationInfo {
-            api_version: vk::make_api_version(0, 1, 0, 0),
-            ..Default::default()
-        };
-        let mut create_info = vk::InstanceCreateInfo {
-            p_application_info: &app_info,
-            ..Default::default()
-        };
-        create_info.enabled_layer_count = VALIDATION_LAYERS.len() as u32;
-        create_info.pp_enabled_layer_names = VALIDATION_LAYERS.as_ptr();
-        create_info.enabled_extension_count = REQUIRED_EXTENSIONS.len() as u32;
-        create_info.pp_enabled_extension_names = REQUIRED_EXTENSIONS.as_ptr();
-        create_info.p_next =
-            &HelloTriangleApplication::setup_debug_messenger() as *const _ as *const c_void;
-        unsafe { entry.create_instance(&create_info, None) }
-    }
-    fn setup_debug_messenger() -> vk::DebugUtilsMessengerCreateInfoEXT {
-        vk::DebugUtilsMessengerCreateInfoEXT {
-            s_type: vk::StructureType::DEBUG_UTILS_MESSENGER_CREATE_INFO_EXT,
-            message_severity: vk::DebugUtilsMessageSeverityFlagsEXT::VERBOSE
-                | vk::DebugUtilsMessageSeverityFlagsEXT::WARNING
-                | vk::DebugUtilsMessageSeverityFlagsEXT::ERROR,
-            message_type: vk::DebugUtilsMessageTypeFlagsEXT::GENERAL
-                | vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION
-                | vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE,
-            pfn_user_callback: Some(debug_callback),
-            ..Default::default()
+    let layers: &[*const i8] = if validation { VALIDATION_LAYERS } else { &[] };
+    let application_name = CString::new(config.application_name.as_str())
+        .map_err(|_| Error::Config("The application name can't contain a nul".to_string()))?;
+    let engine_name = CString::new(config.engine_name.as_str())
+        .map_err(|_| Error::Config("The engine name can't contain a nul".to_string()))?;
+    let app_info = vk::ApplicationInfo {
+        s_type: vk::StructureType::APPLICATION_INFO,
+        p_application_name: application_name.as_ptr(),
+        application_version: vk::make_api_version(0, 1, 0, 0),
+        p_engine_name: engine_name.as_ptr(),
+        engine_version: vk::make_api_version(0, 1, 0, 0),
+        api_version: api_version(entry)?,
+        ..Default::default()
+    };
+    let extensions = required_extensions(window, validation)?;
+    check_instance_extension_support(entry, &extensions)?;
+    // Chaining the messenger info also reports problems with creating and destroying the instance itself
+    let debug_messenger_info = populate_debug_messenger_create_info(debug_config);
+    let create_info = vk::InstanceCreateInfo {
+        p_application_info: &app_info,
+        enabled_layer_count: layers.len() as u32,
+        pp_enabled_layer_names: layers.as_ptr(),
+        enabled_extension_count: extensions.len() as u32,
+        pp_enabled_extension_names: extensions.as_ptr(),
+        p_next: if validation {
+            &debug_messenger_info as *const _ as *const c_void
+        } else {
+            ptr::null()
+        },
+        ..Default::default()
+    };
+    Ok(unsafe { entry.create_instance(&create_info, None)? })
+}
+
+// Vulkan 1.1 when the loader has it, so device properties can be extended with p_next chains
+pub fn api_version(entry: &ash::Entry) -> Result<u32> {
+    match entry.try_enumerate_instance_version()? {
+        Some(version) if version >= vk::API_VERSION_1_1 => Ok(vk::API_VERSION_1_1),
+        _ => Ok(vk::API_VERSION_1_0),
+    }
+}
+
+pub fn required_extensions(
+    window: Option<&winit::window::Window>,
+    validation: bool,
+) -> Result<Vec<*const i8>> {
+    let mut extensions = Vec::new();
+    if validation {
+        extensions.push(DebugUtils::name().as_ptr());
+    }
+    if let Some(window) = window {
+        extensions.push(Surface::name().as_ptr());
+        extensions.push(surface_extension_name(window.raw_window_handle())?.as_ptr());
+    }
+    Ok(extensions)
+}
+
+// The platform extension create_surface needs to make a surface for this kind of window
+pub fn surface_extension_name(window_handle: RawWindowHandle) -> Result<&'static CStr> {
+    match window_handle {
+        RawWindowHandle::AndroidNdk(_) => Ok(AndroidSurface::name()),
+        RawWindowHandle::Win32(_) => Ok(Win32Surface::name()),
+        RawWindowHandle::Wayland(_) => Ok(WaylandSurface::name()),
+        RawWindowHandle::Xcb(_) => Ok(XcbSurface::name()),
+        RawWindowHandle::Xlib(_) => Ok(XlibSurface::name()),
+        _ => Err(Error::UnsupportedWindowHandle),
+    }
+}
+
+// Whether the validation layer gets loaded, only fails when it is required and not installed
+pub fn validation_enabled(entry: &ash::Entry, debug_config: &DebugConfig) -> Result<bool> {
+    match debug_config.validation {
+        Validation::Disabled => Ok(false),
+        Validation::IfAvailable => match check_validation_layer_support(entry) {
+            Ok(()) => Ok(true),
+            Err(Error::MissingLayer(_)) => Ok(false),
+            Err(error) => Err(error),
+        },
+        Validation::Required => check_validation_layer_support(entry).map(|()| true),
+    }
+}
+
+// Returns the error the debug callback recorded since the last call, when fail_on_error is on
+pub fn check_validation_error() -> Result<()> {
+    if VALIDATION_ERROR.swap(false, Ordering::Relaxed) {
+        Err(Error::Validation)
+    } else {
+        Ok(())
+    }
+}
+
+// Fails with the name of the first validation layer that isn't installed
+pub fn check_validation_layer_support(entry: &ash::Entry) -> Result<()> {
+    let layer_properties = entry.enumerate_instance_layer_properties()?;
+    for layer in VALIDATION_LAYERS {
+        let layer = unsafe { CStr::from_ptr(*layer) };
+        if !layer_properties
+            .iter()
+            .any(|l| unsafe { CStr::from_ptr(l.layer_name.as_ptr()) } == layer)
+        {
+            return Err(Error::MissingLayer(layer.to_string_lossy().into_owned()));
         }
     }
-    pub fn new() -> Self {
-        let entry = Entry::linked();
-        let instance = HelloTriangleApplication::init_vulkan(&entry).unwrap();
-        let debug_messenger = unsafe {
-            DebugUtils::new(&entry, &instance)
-                .create_debug_utils_messenger(
-                    &HelloTriangleApplication::setup_debug_messenger(),
-                    None,
-                )
-                .unwrap()
-        };
-        Self {
-            entry: entry,
-            instance: instance,
-            debug_messenger: debug_messenger,
+    Ok(())
+}
+
+// Fails with the name of the first instance extension the loader doesn't offer
+pub fn check_instance_extension_support(
+    entry: &ash::Entry,
+    extensions: &[*const i8],
+) -> Result<()> {
+    let extension_properties = entry.enumerate_instance_extension_properties(None)?;
+    for extension in extensions {
+        let extension = unsafe { CStr::from_ptr(*extension) };
+        if !extension_properties
+            .iter()
+            .any(|e| unsafe { CStr::from_ptr(e.extension_name.as_ptr()) } == extension)
+        {
+            return Err(Error::MissingExtension(
+                extension.to_string_lossy().into_owned(),
+            ));
         }
     }
-    fn init_window(
-    ) -> Result<(winit::event_loop::EventLoop<()>, winit::window::Window), winit::error::OsError>
-    {
-        let event_loop = EventLoop::new();
-        let window = WindowBuilder::new()
-            .with_resizable(false)
-            .with_inner_size(PhysicalSize::new(WIDTH, HEIGHT))
-            .build(&event_loop)?;
-        Ok((event_loop, window))
-    }
-    fn cleanup(&mut self) {
+    Ok(())
+}
+
+// Returns a null messenger when validation is disabled, there is nothing to report then
+pub fn create_debug_messenger(
+    entry: &ash::Entry,
+    instance: &ash::Instance,
+    debug_config: &DebugConfig,
+) -> Result<vk::DebugUtilsMessengerEXT> {
+    if !validation_enabled(entry, debug_config)? {
+        return Ok(vk::DebugUtilsMessengerEXT::null());
+    }
+    Ok(unsafe {
+        DebugUtils::new(entry, instance).create_debug_utils_messenger(
+            &populate_debug_messenger_create_info(debug_config),
+            None,
+        )?
+    })
+}
+
+pub fn destroy_debug_messenger(
+    entry: &ash::Entry,
+    instance: &ash::Instance,
+    debug_messenger: vk::DebugUtilsMessengerEXT,
+) {
+    if debug_messenger != vk::DebugUtilsMessengerEXT::null() {
         unsafe {
-            DebugUtils::new(&self.entry, &self.instance)
-                .destroy_debug_utils_messenger(self.debug_messenger, None);
-            self.instance.destroy_instance(None);
+            DebugUtils::new(entry, instance).destroy_debug_utils_messenger(debug_messenger, None);
         }
     }
-    pub fn run(mut self) -> ! {
-        let (event_loop, window) = HelloTriangleApplication::init_window().unwrap();
-        event_loop.run(move |event, _, control_flow| {
-            *control_flow = ControlFlow::Wait;
-
-            match event {
-                Event::WindowEvent {
-                    event: WindowEvent::CloseRequested,
-                    window_id,
-                } if window_id == window.id() => {
-                    self.cleanup();
-                    *control_flow = ControlFlow::Exit
-                }
-                _ => (),
-            }
-        });
+}
+
+pub fn populate_debug_messenger_create_info(
+    debug_config: &DebugConfig,
+) -> vk::DebugUtilsMessengerCreateInfoEXT {
+    vk::DebugUtilsMessengerCreateInfoEXT {
+        s_type: vk::StructureType::DEBUG_UTILS_MESSENGER_CREATE_INFO_EXT,
+        message_severity: debug_config.severity_mask(),
+        message_type: debug_config.message_types,
+        pfn_user_callback: Some(debug_callback),
+        p_user_data: if debug_config.fail_on_error {
+            &VALIDATION_ERROR as *const AtomicBool as *mut c_void
+        } else {
+            ptr::null_mut()
+        },
+        ..Default::default()
     }
 }
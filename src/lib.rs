@@ -0,0 +1,22 @@
+pub mod buffer;
+pub mod capture;
+pub mod commands;
+pub mod compressed;
+pub mod config;
+pub mod device;
+pub mod error;
+pub mod image;
+pub mod instance;
+pub mod memory;
+pub mod mesh;
+pub mod pipeline;
+pub mod renderer;
+pub mod scene;
+pub mod surface;
+pub mod swapchain;
+pub mod texture;
+pub mod uniform;
+pub mod upload;
+pub mod vertex;
+
+pub use error::{Error, Result};
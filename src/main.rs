@@ -1,8 +1,44 @@
-mod uniformbuffer;
-
-use uniformbuffer::HelloTriangleApplication;
+use std::error::Error;
+use std::path::PathBuf;
+use vulkanrust::config::RendererConfig;
+use vulkanrust::instance;
+use vulkanrust::renderer::{HeadlessApplication, HelloTriangleApplication};
 
 fn main() {
-    let app = HelloTriangleApplication::new();
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
+    if let Err(error) = run() {
+        log::error!("{}", error);
+        std::process::exit(1);
+    }
+}
+
+fn run() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = std::env::args().collect();
+    let config = RendererConfig::from_args(&args)?;
+    if args.iter().any(|arg| arg == "--headless") {
+        let mut app = HeadlessApplication::new(&config)?;
+        if let Some(index) = args.iter().position(|arg| arg == "--time") {
+            let time = args
+                .get(index + 1)
+                .ok_or("--time needs a number of seconds")?;
+            app.set_time(time.parse()?);
+        }
+        app.render_frame()?;
+        match args.iter().position(|arg| arg == "--output") {
+            Some(index) => {
+                let path = args.get(index + 1).ok_or("--output needs a path")?;
+                app.save_frame(&PathBuf::from(path))?;
+            }
+            None => {
+                let pixels = app.read_frame()?;
+                println!("Rendered an offscreen frame ({} bytes)", pixels.len());
+            }
+        }
+        // Destroying everything is validated too
+        drop(app);
+        instance::check_validation_error()?;
+        return Ok(());
+    }
+    let app = HelloTriangleApplication::new(&config)?;
     app.run();
 }
@@ -0,0 +1,839 @@
+use crate::error::{Error, Result};
+use ash::vk;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ffi::c_void;
+use std::ptr;
+
+// Blocks are this big unless the heap is small, then they take an eighth of it
+const DEFAULT_BLOCK_SIZE: vk::DeviceSize = 64 * 1024 * 1024;
+
+// How allocations are placed within a block
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AllocationStrategy {
+    // Reuses freed ranges, for resources that are freed in any order
+    FreeList,
+    // Only ever moves forward and is reset once everything in the block is freed, for short-lived
+    // resources that go away together, like staging buffers
+    Linear,
+}
+
+// The memory property flags an allocation must have, and the ones it would rather have
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MemoryRequest {
+    pub required: vk::MemoryPropertyFlags,
+    pub preferred: vk::MemoryPropertyFlags,
+}
+
+// Buffers and optimally tiled images are kept in separate blocks, so they never need to be spaced
+// apart by bufferImageGranularity
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum ResourceKind {
+    Buffer,
+    Image,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct PoolKey {
+    memory_type_index: u32,
+    strategy: AllocationStrategy,
+    kind: ResourceKind,
+}
+
+#[derive(Debug)]
+enum Location {
+    Block { key: PoolKey, block_id: u64 },
+    Dedicated,
+}
+
+// A range of device memory. Host visible memory stays mapped for as long as it's allocated, so
+// mapped points at offset and map_memory must not be called on memory.
+#[derive(Debug)]
+pub struct Allocation {
+    pub memory: vk::DeviceMemory,
+    pub offset: vk::DeviceSize,
+    pub size: vk::DeviceSize,
+    pub memory_type_index: u32,
+    pub mapped: *mut u8,
+    location: Location,
+}
+
+struct Block {
+    id: u64,
+    memory: vk::DeviceMemory,
+    size: vk::DeviceSize,
+    mapped: *mut u8,
+    allocation_count: usize,
+    used: vk::DeviceSize,
+    // Free ranges as (offset, size) ordered by offset, only used by free-list blocks
+    free_ranges: Vec<(vk::DeviceSize, vk::DeviceSize)>,
+    // Where the next allocation goes, only used by linear blocks
+    linear_offset: vk::DeviceSize,
+}
+
+// Used and free bytes of one memory heap
+#[derive(Clone, Copy, Debug, Default)]
+pub struct HeapStats {
+    pub heap_index: u32,
+    pub heap_size: vk::DeviceSize,
+    // Every vkDeviceMemory, both blocks and dedicated allocations
+    pub device_memory_count: usize,
+    pub allocated_bytes: vk::DeviceSize,
+    pub used_bytes: vk::DeviceSize,
+    pub allocation_count: usize,
+}
+
+impl HeapStats {
+    pub fn free_bytes(&self) -> vk::DeviceSize {
+        self.allocated_bytes - self.used_bytes
+    }
+}
+
+// What VK_EXT_memory_budget reports for one heap, in bytes. Usage counts other processes too.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct HeapBudget {
+    pub usage: vk::DeviceSize,
+    pub budget: vk::DeviceSize,
+}
+
+#[derive(Default)]
+struct AllocatorState {
+    pools: HashMap<PoolKey, Vec<Block>>,
+    next_block_id: u64,
+    // Dedicated allocations per memory type, as (count, bytes)
+    dedicated: HashMap<u32, (usize, vk::DeviceSize)>,
+}
+
+// Sub-allocates buffers and images from large blocks of device memory, so that a scene doesn't run
+// into maxMemoryAllocationCount. Resources bigger than half a block get memory of their own.
+pub struct MemoryAllocator {
+    instance: ash::Instance,
+    physical_device: vk::PhysicalDevice,
+    device: ash::Device,
+    memory_properties: vk::PhysicalDeviceMemoryProperties,
+    memory_budget: bool,
+    // Flushes of memory that isn't host coherent have to cover whole atoms of this size
+    non_coherent_atom_size: vk::DeviceSize,
+    state: RefCell<AllocatorState>,
+}
+
+fn align_up(offset: vk::DeviceSize, alignment: vk::DeviceSize) -> vk::DeviceSize {
+    offset.div_ceil(alignment.max(1)) * alignment.max(1)
+}
+
+impl Block {
+    fn allocate(
+        &mut self,
+        strategy: AllocationStrategy,
+        size: vk::DeviceSize,
+        alignment: vk::DeviceSize,
+    ) -> Option<vk::DeviceSize> {
+        let offset =
+            match strategy {
+                AllocationStrategy::Linear => {
+                    let offset = align_up(self.linear_offset, alignment);
+                    if offset + size > self.size {
+                        return None;
+                    }
+                    self.linear_offset = offset + size;
+                    offset
+                }
+                AllocationStrategy::FreeList => {
+                    let (index, offset) = self.free_ranges.iter().enumerate().find_map(
+                        |(index, &(start, length))| {
+                            let offset = align_up(start, alignment);
+                            (offset + size <= start + length).then_some((index, offset))
+                        },
+                    )?;
+                    // Whatever is left before and after the allocation stays free
+                    let (start, length) = self.free_ranges.remove(index);
+                    let end = start + length;
+                    if offset + size < end {
+                        self.free_ranges
+                            .insert(index, (offset + size, end - offset - size));
+                    }
+                    if start < offset {
+                        self.free_ranges.insert(index, (start, offset - start));
+                    }
+                    offset
+                }
+            };
+        self.allocation_count += 1;
+        self.used += size;
+        Some(offset)
+    }
+
+    fn free(&mut self, strategy: AllocationStrategy, offset: vk::DeviceSize, size: vk::DeviceSize) {
+        self.allocation_count -= 1;
+        self.used -= size;
+        match strategy {
+            AllocationStrategy::Linear => {
+                if self.allocation_count == 0 {
+                    self.linear_offset = 0;
+                }
+            }
+            AllocationStrategy::FreeList => {
+                let index = self
+                    .free_ranges
+                    .iter()
+                    .position(|&(start, _)| start > offset)
+                    .unwrap_or(self.free_ranges.len());
+                self.free_ranges.insert(index, (offset, size));
+                // Merge with the neighbours it touches, the next one first so index stays valid
+                if index + 1 < self.free_ranges.len() {
+                    let (next_start, next_length) = self.free_ranges[index + 1];
+                    if offset + size == next_start {
+                        self.free_ranges[index].1 += next_length;
+                        self.free_ranges.remove(index + 1);
+                    }
+                }
+                if index > 0 {
+                    let (previous_start, previous_length) = self.free_ranges[index - 1];
+                    if previous_start + previous_length == offset {
+                        self.free_ranges[index - 1].1 += self.free_ranges[index].1;
+                        self.free_ranges.remove(index);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl MemoryRequest {
+    pub fn new(required: vk::MemoryPropertyFlags) -> Self {
+        Self {
+            required,
+            preferred: vk::MemoryPropertyFlags::empty(),
+        }
+    }
+    pub fn prefer(mut self, preferred: vk::MemoryPropertyFlags) -> Self {
+        self.preferred = preferred;
+        self
+    }
+}
+
+// Every memory type allowed by type_filter that has the required flags, best first. Types with more
+// of the preferred flags come first, then types with fewer flags nobody asked for, so memory that's
+// both device local and host visible isn't used up by things that don't need it to be.
+pub fn select_memory_types(
+    memory_properties: &vk::PhysicalDeviceMemoryProperties,
+    type_filter: u32,
+    request: MemoryRequest,
+) -> Vec<u32> {
+    let mut memory_types: Vec<u32> = (0..memory_properties.memory_type_count)
+        .filter(|i| {
+            (type_filter & (1 << i)) != 0
+                && memory_properties.memory_types[*i as usize]
+                    .property_flags
+                    .contains(request.required)
+        })
+        .collect();
+    memory_types.sort_by_key(|i| {
+        let flags = memory_properties.memory_types[*i as usize].property_flags;
+        let unwanted = flags & !(request.required | request.preferred);
+        (
+            std::cmp::Reverse(preferred_count(memory_properties, *i, request)),
+            unwanted.as_raw().count_ones(),
+        )
+    });
+    memory_types
+}
+
+// How many of the preferred flags a memory type has
+fn preferred_count(
+    memory_properties: &vk::PhysicalDeviceMemoryProperties,
+    memory_type_index: u32,
+    request: MemoryRequest,
+) -> u32 {
+    let flags = memory_properties.memory_types[memory_type_index as usize].property_flags;
+    (flags & request.preferred).as_raw().count_ones()
+}
+
+impl MemoryAllocator {
+    // With memory_budget the device has VK_EXT_memory_budget enabled, and new device memory is only
+    // allocated from heaps that have room for it in their budget
+    pub fn new(
+        instance: &ash::Instance,
+        physical_device: &vk::PhysicalDevice,
+        device: &ash::Device,
+        memory_budget: bool,
+    ) -> Self {
+        Self {
+            instance: instance.clone(),
+            physical_device: *physical_device,
+            device: device.clone(),
+            memory_properties: unsafe {
+                instance.get_physical_device_memory_properties(*physical_device)
+            },
+            memory_budget,
+            non_coherent_atom_size: unsafe {
+                instance
+                    .get_physical_device_properties(*physical_device)
+                    .limits
+                    .non_coherent_atom_size
+            },
+            state: RefCell::new(AllocatorState::default()),
+        }
+    }
+
+    // Allocates memory for the buffer and binds it
+    pub fn allocate_buffer(
+        &self,
+        buffer: vk::Buffer,
+        request: MemoryRequest,
+        strategy: AllocationStrategy,
+    ) -> Result<Allocation> {
+        let requirements = unsafe { self.device.get_buffer_memory_requirements(buffer) };
+        let allocation = self.allocate(requirements, request, strategy, ResourceKind::Buffer)?;
+        if let Err(error) = unsafe {
+            self.device
+                .bind_buffer_memory(buffer, allocation.memory, allocation.offset)
+        } {
+            self.free(&allocation);
+            return Err(error.into());
+        }
+        Ok(allocation)
+    }
+
+    // Allocates memory for an optimally tiled image and binds it
+    pub fn allocate_image(&self, image: vk::Image, request: MemoryRequest) -> Result<Allocation> {
+        let requirements = unsafe { self.device.get_image_memory_requirements(image) };
+        let allocation = self.allocate(
+            requirements,
+            request,
+            AllocationStrategy::FreeList,
+            ResourceKind::Image,
+        )?;
+        if let Err(error) = unsafe {
+            self.device
+                .bind_image_memory(image, allocation.memory, allocation.offset)
+        } {
+            self.free(&allocation);
+            return Err(error.into());
+        }
+        Ok(allocation)
+    }
+
+    // How much of each heap is in use and how much the driver says we can use, None without
+    // VK_EXT_memory_budget
+    pub fn budgets(&self) -> Option<Vec<HeapBudget>> {
+        if !self.memory_budget {
+            return None;
+        }
+        let mut budget_properties = vk::PhysicalDeviceMemoryBudgetPropertiesEXT {
+            s_type: vk::StructureType::PHYSICAL_DEVICE_MEMORY_BUDGET_PROPERTIES_EXT,
+            ..Default::default()
+        };
+        let mut memory_properties2 = vk::PhysicalDeviceMemoryProperties2 {
+            s_type: vk::StructureType::PHYSICAL_DEVICE_MEMORY_PROPERTIES_2,
+            p_next: &mut budget_properties as *mut _ as *mut c_void,
+            ..Default::default()
+        };
+        unsafe {
+            self.instance.get_physical_device_memory_properties2(
+                self.physical_device,
+                &mut memory_properties2,
+            )
+        };
+        Some(
+            (0..self.memory_properties.memory_heap_count as usize)
+                .map(|heap_index| HeapBudget {
+                    usage: budget_properties.heap_usage[heap_index],
+                    budget: budget_properties.heap_budget[heap_index],
+                })
+                .collect(),
+        )
+    }
+
+    // Tries every suitable memory type best first. Types with as many of the preferred flags are tried
+    // together, room in their existing blocks before new device memory, and only when none of them
+    // can hold the resource are types with fewer preferred flags tried. A type whose heap is out of
+    // budget or out of memory is skipped for the next one.
+    fn allocate(
+        &self,
+        requirements: vk::MemoryRequirements,
+        request: MemoryRequest,
+        strategy: AllocationStrategy,
+        kind: ResourceKind,
+    ) -> Result<Allocation> {
+        let memory_types = select_memory_types(
+            &self.memory_properties,
+            requirements.memory_type_bits,
+            request,
+        );
+        if memory_types.is_empty() {
+            return Err(Error::NoSuitableMemoryType);
+        }
+        let budgets = self.budgets();
+        let mut result = Err(Error::MemoryBudgetExceeded(requirements.size));
+        let tiers = memory_types.chunk_by(|a, b| {
+            preferred_count(&self.memory_properties, *a, request)
+                == preferred_count(&self.memory_properties, *b, request)
+        });
+        for tier in tiers {
+            for &memory_type_index in tier {
+                let requirements = self.type_requirements(requirements, memory_type_index);
+                if requirements.size <= self.block_size(memory_type_index) / 2 {
+                    let key = PoolKey {
+                        memory_type_index,
+                        strategy,
+                        kind,
+                    };
+                    if let Some(allocation) = self.allocate_from_blocks(key, requirements) {
+                        return Ok(allocation);
+                    }
+                }
+            }
+            for &memory_type_index in tier {
+                let requirements = self.type_requirements(requirements, memory_type_index);
+                let block_size = self.block_size(memory_type_index);
+                let heap_left = budgets.as_ref().map(|budgets| {
+                    let heap = &budgets[self.heap_index(memory_type_index)];
+                    heap.budget.saturating_sub(heap.usage)
+                });
+                if heap_left.is_some_and(|left| left < requirements.size) {
+                    log::debug!(
+                        "Skipping memory type {}, its heap is out of budget",
+                        memory_type_index
+                    );
+                    continue;
+                }
+                // Without room for a whole block the resource may still fit on its own
+                let dedicated = requirements.size > block_size / 2
+                    || heap_left.is_some_and(|left| left < block_size);
+                result = if dedicated {
+                    self.allocate_dedicated(requirements.size, memory_type_index)
+                } else {
+                    let key = PoolKey {
+                        memory_type_index,
+                        strategy,
+                        kind,
+                    };
+                    match self.allocate_block(key, block_size, requirements) {
+                        Err(Error::Vulkan(vk::Result::ERROR_OUT_OF_DEVICE_MEMORY)) => {
+                            self.allocate_dedicated(requirements.size, memory_type_index)
+                        }
+                        result => result,
+                    }
+                };
+                match result {
+                    Err(Error::Vulkan(
+                        vk::Result::ERROR_OUT_OF_DEVICE_MEMORY
+                        | vk::Result::ERROR_OUT_OF_HOST_MEMORY,
+                    )) => continue,
+                    result => return result,
+                }
+            }
+        }
+        result
+    }
+
+    // Allocations in memory that isn't host coherent start and end on atom boundaries, so flushing one
+    // never touches its neighbours
+    fn type_requirements(
+        &self,
+        requirements: vk::MemoryRequirements,
+        memory_type_index: u32,
+    ) -> vk::MemoryRequirements {
+        if !self.needs_flush(memory_type_index) {
+            return requirements;
+        }
+        vk::MemoryRequirements {
+            size: align_up(requirements.size, self.non_coherent_atom_size),
+            alignment: requirements.alignment.max(self.non_coherent_atom_size),
+            ..requirements
+        }
+    }
+
+    fn needs_flush(&self, memory_type_index: u32) -> bool {
+        let property_flags =
+            self.memory_properties.memory_types[memory_type_index as usize].property_flags;
+        property_flags.contains(vk::MemoryPropertyFlags::HOST_VISIBLE)
+            && !property_flags.contains(vk::MemoryPropertyFlags::HOST_COHERENT)
+    }
+
+    // Makes CPU writes to size bytes at offset within the allocation visible to the device. Host
+    // coherent memory needs nothing, otherwise the range is widened to whole atoms.
+    pub fn flush(
+        &self,
+        allocation: &Allocation,
+        offset: vk::DeviceSize,
+        size: vk::DeviceSize,
+    ) -> Result<()> {
+        if !self.needs_flush(allocation.memory_type_index) {
+            return Ok(());
+        }
+        let range = self.mapped_range(allocation, offset, size);
+        unsafe { self.device.flush_mapped_memory_ranges(&[range])? };
+        Ok(())
+    }
+
+    // Makes device writes to size bytes at offset within the allocation visible to the CPU, the
+    // counterpart of flush for memory that is read back
+    pub fn invalidate(
+        &self,
+        allocation: &Allocation,
+        offset: vk::DeviceSize,
+        size: vk::DeviceSize,
+    ) -> Result<()> {
+        if !self.needs_flush(allocation.memory_type_index) {
+            return Ok(());
+        }
+        let range = self.mapped_range(allocation, offset, size);
+        unsafe { self.device.invalidate_mapped_memory_ranges(&[range])? };
+        Ok(())
+    }
+
+    fn mapped_range(
+        &self,
+        allocation: &Allocation,
+        offset: vk::DeviceSize,
+        size: vk::DeviceSize,
+    ) -> vk::MappedMemoryRange {
+        let atom = self.non_coherent_atom_size;
+        let start = (allocation.offset + offset) / atom * atom;
+        let end = align_up(allocation.offset + offset + size, atom)
+            .min(allocation.offset + allocation.size);
+        vk::MappedMemoryRange {
+            s_type: vk::StructureType::MAPPED_MEMORY_RANGE,
+            memory: allocation.memory,
+            offset: start,
+            size: end - start,
+            ..Default::default()
+        }
+    }
+
+    fn allocate_from_blocks(
+        &self,
+        key: PoolKey,
+        requirements: vk::MemoryRequirements,
+    ) -> Option<Allocation> {
+        let mut state = self.state.borrow_mut();
+        let pool = state.pools.get_mut(&key)?;
+        pool.iter_mut().find_map(|block| {
+            let offset = block.allocate(key.strategy, requirements.size, requirements.alignment)?;
+            Some(Allocation {
+                memory: block.memory,
+                offset,
+                size: requirements.size,
+                memory_type_index: key.memory_type_index,
+                mapped: block.mapped_at(offset),
+                location: Location::Block {
+                    key,
+                    block_id: block.id,
+                },
+            })
+        })
+    }
+
+    fn allocate_block(
+        &self,
+        key: PoolKey,
+        block_size: vk::DeviceSize,
+        requirements: vk::MemoryRequirements,
+    ) -> Result<Allocation> {
+        let (memory, mapped) = self.allocate_device_memory(block_size, key.memory_type_index)?;
+        let mut state = self.state.borrow_mut();
+        let id = state.next_block_id;
+        state.next_block_id += 1;
+        let mut block = Block {
+            id,
+            memory,
+            size: block_size,
+            mapped,
+            allocation_count: 0,
+            used: 0,
+            free_ranges: vec![(0, block_size)],
+            linear_offset: 0,
+        };
+        // A fresh block is at least twice as big as the resource, so this can't fail
+        let offset = block
+            .allocate(key.strategy, requirements.size, requirements.alignment)
+            .unwrap();
+        let mapped = block.mapped_at(offset);
+        log::debug!(
+            "Allocated a {} MiB block of memory type {}",
+            block_size / (1024 * 1024),
+            key.memory_type_index
+        );
+        state.pools.entry(key).or_default().push(block);
+        Ok(Allocation {
+            memory,
+            offset,
+            size: requirements.size,
+            memory_type_index: key.memory_type_index,
+            mapped,
+            location: Location::Block { key, block_id: id },
+        })
+    }
+
+    fn allocate_dedicated(
+        &self,
+        size: vk::DeviceSize,
+        memory_type_index: u32,
+    ) -> Result<Allocation> {
+        let (memory, mapped) = self.allocate_device_memory(size, memory_type_index)?;
+        let mut state = self.state.borrow_mut();
+        let (count, bytes) = state.dedicated.entry(memory_type_index).or_default();
+        *count += 1;
+        *bytes += size;
+        Ok(Allocation {
+            memory,
+            offset: 0,
+            size,
+            memory_type_index,
+            mapped,
+            location: Location::Dedicated,
+        })
+    }
+
+    // Host visible memory is mapped right away and stays mapped until it is freed
+    fn allocate_device_memory(
+        &self,
+        size: vk::DeviceSize,
+        memory_type_index: u32,
+    ) -> std::result::Result<(vk::DeviceMemory, *mut u8), vk::Result> {
+        let alloc_info = vk::MemoryAllocateInfo {
+            s_type: vk::StructureType::MEMORY_ALLOCATE_INFO,
+            allocation_size: size,
+            memory_type_index,
+            ..Default::default()
+        };
+        let memory = unsafe { self.device.allocate_memory(&alloc_info, None)? };
+        let property_flags =
+            self.memory_properties.memory_types[memory_type_index as usize].property_flags;
+        if !property_flags.contains(vk::MemoryPropertyFlags::HOST_VISIBLE) {
+            return Ok((memory, ptr::null_mut()));
+        }
+        match unsafe {
+            self.device
+                .map_memory(memory, 0, vk::WHOLE_SIZE, vk::MemoryMapFlags::empty())
+        } {
+            Ok(mapped) => Ok((memory, mapped as *mut u8)),
+            Err(error) => {
+                unsafe { self.device.free_memory(memory, None) };
+                Err(error)
+            }
+        }
+    }
+
+    pub fn free(&self, allocation: &Allocation) {
+        let mut state = self.state.borrow_mut();
+        match &allocation.location {
+            Location::Dedicated => {
+                unsafe { self.device.free_memory(allocation.memory, None) };
+                if let Some((count, bytes)) = state.dedicated.get_mut(&allocation.memory_type_index)
+                {
+                    *count -= 1;
+                    *bytes -= allocation.size;
+                }
+            }
+            Location::Block { key, block_id } => {
+                let Some((pool, index)) = state.pools.get_mut(key).and_then(|pool| {
+                    let index = pool.iter().position(|block| block.id == *block_id)?;
+                    Some((pool, index))
+                }) else {
+                    log::error!("Freed an allocation from block {}, which is gone", block_id);
+                    return;
+                };
+                pool[index].free(key.strategy, allocation.offset, allocation.size);
+                // Empty blocks are given back, except for the last one so the next allocation
+                // doesn't have to make a new block again
+                if pool[index].allocation_count == 0 && pool.len() > 1 {
+                    let block = pool.remove(index);
+                    unsafe { self.device.free_memory(block.memory, None) };
+                }
+            }
+        }
+    }
+
+    pub fn stats(&self) -> Vec<HeapStats> {
+        let mut stats: Vec<HeapStats> = self.memory_properties.memory_heaps
+            [..self.memory_properties.memory_heap_count as usize]
+            .iter()
+            .enumerate()
+            .map(|(heap_index, heap)| HeapStats {
+                heap_index: heap_index as u32,
+                heap_size: heap.size,
+                ..Default::default()
+            })
+            .collect();
+        let state = self.state.borrow();
+        for (key, pool) in &state.pools {
+            let heap = &mut stats[self.heap_index(key.memory_type_index)];
+            for block in pool {
+                heap.device_memory_count += 1;
+                heap.allocated_bytes += block.size;
+                heap.used_bytes += block.used;
+                heap.allocation_count += block.allocation_count;
+            }
+        }
+        for (memory_type_index, (count, bytes)) in &state.dedicated {
+            let heap = &mut stats[self.heap_index(*memory_type_index)];
+            heap.device_memory_count += count;
+            heap.allocated_bytes += bytes;
+            heap.used_bytes += bytes;
+            heap.allocation_count += count;
+        }
+        stats
+    }
+
+    // Frees every block. Whatever is still allocated from them is reported, it was leaked
+    pub fn destroy(&self) {
+        for heap in self.stats() {
+            if heap.allocation_count > 0 {
+                log::warn!(
+                    "{} allocations ({} bytes) are still alive in heap {}",
+                    heap.allocation_count,
+                    heap.used_bytes,
+                    heap.heap_index
+                );
+            }
+        }
+        let mut state = self.state.borrow_mut();
+        for (_, pool) in state.pools.drain() {
+            for block in pool {
+                unsafe { self.device.free_memory(block.memory, None) };
+            }
+        }
+    }
+
+    fn heap_index(&self, memory_type_index: u32) -> usize {
+        self.memory_properties.memory_types[memory_type_index as usize].heap_index as usize
+    }
+
+    fn block_size(&self, memory_type_index: u32) -> vk::DeviceSize {
+        let heap_size =
+            self.memory_properties.memory_heaps[self.heap_index(memory_type_index)].size;
+        DEFAULT_BLOCK_SIZE.min(heap_size / 8)
+    }
+}
+
+impl Block {
+    fn mapped_at(&self, offset: vk::DeviceSize) -> *mut u8 {
+        if self.mapped.is_null() {
+            ptr::null_mut()
+        } else {
+            unsafe { self.mapped.add(offset as usize) }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(size: vk::DeviceSize) -> Block {
+        Block {
+            id: 0,
+            memory: vk::DeviceMemory::null(),
+            size,
+            mapped: ptr::null_mut(),
+            allocation_count: 0,
+            used: 0,
+            free_ranges: vec![(0, size)],
+            linear_offset: 0,
+        }
+    }
+
+    #[test]
+    fn allocations_are_aligned() {
+        let mut block = block(1024);
+        let strategy = AllocationStrategy::FreeList;
+        assert_eq!(block.allocate(strategy, 10, 4), Some(0));
+        assert_eq!(block.allocate(strategy, 10, 256), Some(256));
+        // The gap the alignment left stays free
+        assert_eq!(block.allocate(strategy, 16, 16), Some(16));
+        assert_eq!(block.used, 36);
+    }
+
+    #[test]
+    fn free_merges_with_both_neighbours() {
+        let mut block = block(300);
+        let strategy = AllocationStrategy::FreeList;
+        let first = block.allocate(strategy, 100, 1).unwrap();
+        let second = block.allocate(strategy, 100, 1).unwrap();
+        let third = block.allocate(strategy, 100, 1).unwrap();
+        assert!(block.free_ranges.is_empty());
+        block.free(strategy, first, 100);
+        block.free(strategy, third, 100);
+        assert_eq!(block.free_ranges, [(0, 100), (200, 100)]);
+        block.free(strategy, second, 100);
+        assert_eq!(block.free_ranges, [(0, 300)]);
+        assert_eq!((block.allocation_count, block.used), (0, 0));
+    }
+
+    #[test]
+    fn exhausted_blocks_return_none() {
+        let mut block = block(256);
+        let strategy = AllocationStrategy::FreeList;
+        assert_eq!(block.allocate(strategy, 200, 1), Some(0));
+        assert_eq!(block.allocate(strategy, 100, 1), None);
+        // Room is left, but not once the allocation is aligned
+        assert_eq!(block.allocate(strategy, 50, 128), None);
+        assert_eq!(block.allocate(strategy, 56, 8), Some(200));
+        assert_eq!(block.allocation_count, 2);
+    }
+
+    #[test]
+    fn linear_blocks_reset_when_empty() {
+        let mut block = block(256);
+        let strategy = AllocationStrategy::Linear;
+        let first = block.allocate(strategy, 100, 1).unwrap();
+        let second = block.allocate(strategy, 100, 64).unwrap();
+        assert_eq!(second, 128);
+        block.free(strategy, first, 100);
+        // Freed space isn't reused while anything in the block is alive
+        assert_eq!(block.allocate(strategy, 100, 1), None);
+        block.free(strategy, second, 100);
+        assert_eq!(block.allocate(strategy, 256, 1), Some(0));
+    }
+
+    fn memory_properties(types: &[vk::MemoryPropertyFlags]) -> vk::PhysicalDeviceMemoryProperties {
+        let mut memory_properties = vk::PhysicalDeviceMemoryProperties {
+            memory_type_count: types.len() as u32,
+            memory_heap_count: 1,
+            ..Default::default()
+        };
+        for (memory_type, &property_flags) in memory_properties.memory_types.iter_mut().zip(types) {
+            memory_type.property_flags = property_flags;
+        }
+        memory_properties
+    }
+
+    #[test]
+    fn preferred_flags_rank_first_then_fewest_unwanted() {
+        let device_local = vk::MemoryPropertyFlags::DEVICE_LOCAL;
+        let host_visible = vk::MemoryPropertyFlags::HOST_VISIBLE;
+        let host_coherent = vk::MemoryPropertyFlags::HOST_COHERENT;
+        let host_cached = vk::MemoryPropertyFlags::HOST_CACHED;
+        let memory_properties = memory_properties(&[
+            device_local,
+            host_visible | host_coherent | host_cached,
+            host_visible | host_coherent,
+            device_local | host_visible | host_coherent,
+        ]);
+        let request = MemoryRequest::new(host_visible).prefer(host_coherent);
+        // Every host visible type is coherent, so the one with no other flags wins
+        assert_eq!(
+            select_memory_types(&memory_properties, !0, request),
+            [2, 1, 3]
+        );
+        let request = MemoryRequest::new(host_visible).prefer(device_local | host_coherent);
+        assert_eq!(
+            select_memory_types(&memory_properties, !0, request),
+            [3, 2, 1]
+        );
+        // The type filter comes from the resource and rules types out whatever their flags
+        assert_eq!(
+            select_memory_types(&memory_properties, 0b0110, request),
+            [2, 1]
+        );
+        assert!(select_memory_types(
+            &memory_properties,
+            !0,
+            MemoryRequest::new(vk::MemoryPropertyFlags::PROTECTED)
+        )
+        .is_empty());
+    }
+}
@@ -0,0 +1,226 @@
+use crate::error::{Error, Result};
+use crate::vertex::{Vertex, INDICES, VERTICES};
+use ash::vk;
+use glam::{Vec2, Vec3};
+use std::collections::HashMap;
+use std::path::Path;
+
+// 16 bit indices take half the memory and bandwidth, so they are used whenever every vertex fits
+pub enum Indices {
+    U16(Vec<u16>),
+    U32(Vec<u32>),
+}
+
+impl Indices {
+    // Narrowed to 16 bits when no index needs more
+    pub fn new(indices: Vec<u32>) -> Self {
+        if indices.iter().all(|&index| index <= u16::MAX as u32) {
+            Indices::U16(indices.into_iter().map(|index| index as u16).collect())
+        } else {
+            Indices::U32(indices)
+        }
+    }
+    pub fn len(&self) -> usize {
+        match self {
+            Indices::U16(indices) => indices.len(),
+            Indices::U32(indices) => indices.len(),
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    pub fn index_type(&self) -> vk::IndexType {
+        match self {
+            Indices::U16(_) => vk::IndexType::UINT16,
+            Indices::U32(_) => vk::IndexType::UINT32,
+        }
+    }
+}
+
+// Geometry ready to go into a vertex and an index buffer
+pub struct Mesh {
+    pub vertices: Vec<Vertex>,
+    pub indices: Indices,
+}
+
+impl Mesh {
+    // The textured quad drawn when no mesh is configured
+    pub fn quad() -> Self {
+        Self {
+            vertices: VERTICES.to_vec(),
+            indices: Indices::U16(INDICES.to_vec()),
+        }
+    }
+
+    // Takes three vertices per triangle and stores every distinct vertex once, the triangles
+    // refer to them through the index buffer instead
+    pub fn from_triangles(triangle_vertices: impl IntoIterator<Item = Vertex>) -> Self {
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        // Keyed on the bits of every component, as floats are neither Eq nor Hash
+        let mut unique_vertices = HashMap::new();
+        for vertex in triangle_vertices {
+            let index = *unique_vertices
+                .entry(vertex_key(&vertex))
+                .or_insert_with(|| {
+                    vertices.push(vertex);
+                    vertices.len() as u32 - 1
+                });
+            indices.push(index);
+        }
+        Self {
+            vertices,
+            indices: Indices::new(indices),
+        }
+    }
+}
+
+fn vertex_key(vertex: &Vertex) -> [u32; 11] {
+    let Vertex {
+        pos,
+        color,
+        tex_coord,
+        normal,
+    } = vertex;
+    [
+        pos.x,
+        pos.y,
+        pos.z,
+        color.x,
+        color.y,
+        color.z,
+        tex_coord.x,
+        tex_coord.y,
+        normal.x,
+        normal.y,
+        normal.z,
+    ]
+    .map(f32::to_bits)
+}
+
+// Reads every object in a Wavefront OBJ file into one mesh. Faces with more than three corners
+// are split into triangles, materials are ignored. Vertices without a color are white, and without
+// a normal or texture coordinate get zeros.
+pub fn load_obj(path: &Path) -> Result<Mesh> {
+    let load_options = tobj::LoadOptions {
+        triangulate: true,
+        ignore_points: true,
+        ignore_lines: true,
+        ..Default::default()
+    };
+    let (models, _) = tobj::load_obj(path, &load_options).map_err(|error| Error::MeshLoad {
+        path: path.to_path_buf(),
+        reason: error.to_string(),
+    })?;
+    let mut triangle_vertices = Vec::new();
+    for model in &models {
+        let mesh = &model.mesh;
+        for (corner, &position_index) in mesh.indices.iter().enumerate() {
+            let position_index = position_index as usize;
+            let color = if mesh.vertex_color.is_empty() {
+                Vec3::ONE
+            } else {
+                Vec3::from_slice(&mesh.vertex_color[3 * position_index..])
+            };
+            // Texture coordinates and normals have indices of their own, unless the file leaves them out
+            let tex_coord = match mesh.texcoord_indices.get(corner) {
+                Some(&index) => {
+                    let index = index as usize;
+                    // OBJ puts v = 0 at the bottom of the image, Vulkan at the top
+                    Vec2::new(
+                        mesh.texcoords[2 * index],
+                        1.0 - mesh.texcoords[2 * index + 1],
+                    )
+                }
+                None => Vec2::ZERO,
+            };
+            let normal = match mesh.normal_indices.get(corner) {
+                Some(&index) => Vec3::from_slice(&mesh.normals[3 * index as usize..]),
+                None => Vec3::ZERO,
+            };
+            triangle_vertices.push(Vertex {
+                pos: Vec3::from_slice(&mesh.positions[3 * position_index..]),
+                color,
+                tex_coord,
+                normal,
+            });
+        }
+    }
+    if triangle_vertices.is_empty() {
+        return Err(Error::MeshLoad {
+            path: path.to_path_buf(),
+            reason: "the file has no faces".to_string(),
+        });
+    }
+    Ok(Mesh::from_triangles(triangle_vertices))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vertex(x: f32, y: f32) -> Vertex {
+        Vertex {
+            pos: Vec3::new(x, y, 0.0),
+            color: Vec3::ONE,
+            tex_coord: Vec2::new(x, y),
+            normal: Vec3::Z,
+        }
+    }
+
+    #[test]
+    fn shared_corners_are_stored_once() {
+        // Two triangles making a square share the diagonal
+        let mesh = Mesh::from_triangles([
+            vertex(0.0, 0.0),
+            vertex(1.0, 0.0),
+            vertex(1.0, 1.0),
+            vertex(1.0, 1.0),
+            vertex(0.0, 1.0),
+            vertex(0.0, 0.0),
+        ]);
+        assert_eq!(mesh.vertices.len(), 4);
+        match mesh.indices {
+            Indices::U16(indices) => assert_eq!(indices, [0, 1, 2, 2, 3, 0]),
+            Indices::U32(_) => panic!("four vertices fit in 16 bit indices"),
+        }
+    }
+
+    #[test]
+    fn any_difference_keeps_vertices_apart() {
+        let mut flipped = vertex(0.0, 0.0);
+        flipped.normal = -Vec3::Z;
+        let mut red = vertex(0.0, 0.0);
+        red.color = Vec3::X;
+        let mesh = Mesh::from_triangles([vertex(0.0, 0.0), flipped, red]);
+        assert_eq!(mesh.vertices.len(), 3);
+    }
+
+    #[test]
+    fn indices_narrow_when_they_fit() {
+        let indices = Indices::new(vec![0, 1, u16::MAX as u32]);
+        assert_eq!(indices.index_type(), vk::IndexType::UINT16);
+        assert_eq!(indices.len(), 3);
+        let indices = Indices::new(vec![0, u16::MAX as u32 + 1]);
+        assert_eq!(indices.index_type(), vk::IndexType::UINT32);
+        assert!(Indices::new(Vec::new()).is_empty());
+    }
+
+    #[test]
+    fn just_over_16_bits_of_vertices_widens() {
+        // 65537 distinct vertices, the last one is index 65536 which needs 32 bits
+        let count = u16::MAX as u32 + 2;
+        let vertices = (0..count).map(|i| vertex(i as f32, 0.0));
+        let mesh = Mesh::from_triangles(vertices.clone().chain(vertices.take(1)));
+        assert_eq!(mesh.vertices.len(), count as usize);
+        match mesh.indices {
+            Indices::U32(indices) => {
+                assert_eq!(indices.len(), count as usize + 1);
+                assert_eq!(indices[count as usize - 1], count - 1);
+                // The repeated first vertex refers back to it
+                assert_eq!(indices[count as usize], 0);
+            }
+            Indices::U16(_) => panic!("index 65536 doesn't fit in 16 bits"),
+        }
+    }
+}
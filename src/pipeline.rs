@@ -0,0 +1,445 @@
+use crate::error::{Error, Result};
+use ash::vk;
+use std::fs;
+use std::mem::size_of;
+use std::path::Path;
+use std::ptr;
+use std::slice;
+
+// Everything that differs between the pipelines of the different chapters
+pub struct GraphicsPipelineInfo<'a> {
+    pub vert_shader_path: &'a str,
+    pub frag_shader_path: &'a str,
+    pub vertex_binding_descriptions: &'a [vk::VertexInputBindingDescription],
+    pub vertex_attribute_descriptions: &'a [vk::VertexInputAttributeDescription],
+    pub descriptor_set_layouts: &'a [vk::DescriptorSetLayout],
+    // Ranges made with push_constant_range, one per stage or group of stages reading them
+    pub push_constant_ranges: &'a [vk::PushConstantRange],
+    // The device's maxPushConstantsSize, every range has to end within it
+    pub max_push_constants_size: u32,
+    // Only for render passes with a depth attachment
+    pub depth: Option<DepthTest>,
+    // Has to match the samples the render pass was created with
+    pub samples: vk::SampleCountFlags,
+    // Shades at least this fraction of the samples of each pixel, needs sampleRateShading
+    pub min_sample_shading: Option<f32>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DepthTest {
+    pub compare_op: vk::CompareOp,
+    pub write: bool,
+}
+
+impl DepthTest {
+    // The depth buffer is cleared to whatever no fragment can fail against
+    pub fn clear_depth(&self) -> f32 {
+        match self.compare_op {
+            vk::CompareOp::GREATER | vk::CompareOp::GREATER_OR_EQUAL => 0.0,
+            _ => 1.0,
+        }
+    }
+}
+
+pub fn create_render_pass(
+    device: &ash::Device,
+    swap_chain_image_format: &vk::Format,
+    final_layout: vk::ImageLayout,
+    depth_format: Option<vk::Format>,
+    samples: vk::SampleCountFlags,
+) -> Result<vk::RenderPass> {
+    // With multisampling the color attachment is only resolved into the swap chain image, so its
+    // own samples are thrown away at the end of the pass
+    let multisampled = samples != vk::SampleCountFlags::TYPE_1;
+    let color_attachment = vk::AttachmentDescription {
+        format: *swap_chain_image_format,
+        samples,
+        load_op: vk::AttachmentLoadOp::CLEAR,
+        store_op: if multisampled {
+            vk::AttachmentStoreOp::DONT_CARE
+        } else {
+            vk::AttachmentStoreOp::STORE
+        },
+        stencil_load_op: vk::AttachmentLoadOp::DONT_CARE,
+        stencil_store_op: vk::AttachmentStoreOp::DONT_CARE,
+        initial_layout: vk::ImageLayout::UNDEFINED,
+        final_layout: if multisampled {
+            vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL
+        } else {
+            final_layout
+        },
+        ..Default::default()
+    };
+
+    let color_attachment_ref = vk::AttachmentReference {
+        attachment: 0,
+        layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+    };
+
+    let mut attachments = vec![color_attachment];
+    // The depth image is cleared every frame and thrown away after it
+    if let Some(depth_format) = depth_format {
+        attachments.push(vk::AttachmentDescription {
+            format: depth_format,
+            samples,
+            load_op: vk::AttachmentLoadOp::CLEAR,
+            store_op: vk::AttachmentStoreOp::DONT_CARE,
+            stencil_load_op: vk::AttachmentLoadOp::DONT_CARE,
+            stencil_store_op: vk::AttachmentStoreOp::DONT_CARE,
+            initial_layout: vk::ImageLayout::UNDEFINED,
+            final_layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+            ..Default::default()
+        });
+    }
+
+    let depth_attachment_ref = vk::AttachmentReference {
+        attachment: 1,
+        layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+    };
+
+    // The swap chain image comes last when it's the resolve target
+    let resolve_attachment_ref = vk::AttachmentReference {
+        attachment: attachments.len() as u32,
+        layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+    };
+    if multisampled {
+        attachments.push(vk::AttachmentDescription {
+            format: *swap_chain_image_format,
+            samples: vk::SampleCountFlags::TYPE_1,
+            load_op: vk::AttachmentLoadOp::DONT_CARE,
+            store_op: vk::AttachmentStoreOp::STORE,
+            stencil_load_op: vk::AttachmentLoadOp::DONT_CARE,
+            stencil_store_op: vk::AttachmentStoreOp::DONT_CARE,
+            initial_layout: vk::ImageLayout::UNDEFINED,
+            final_layout,
+            ..Default::default()
+        });
+    }
+
+    let subpass = vk::SubpassDescription {
+        pipeline_bind_point: vk::PipelineBindPoint::GRAPHICS,
+        color_attachment_count: 1,
+        p_color_attachments: &color_attachment_ref,
+        p_resolve_attachments: if multisampled {
+            &resolve_attachment_ref
+        } else {
+            ptr::null()
+        },
+        p_depth_stencil_attachment: if depth_format.is_some() {
+            &depth_attachment_ref
+        } else {
+            ptr::null()
+        },
+        ..Default::default()
+    };
+
+    // The previous frame may still be testing against the depth image when this one clears it
+    let (depth_stages, depth_access) = if depth_format.is_some() {
+        (
+            vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS
+                | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
+            vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+        )
+    } else {
+        (vk::PipelineStageFlags::empty(), vk::AccessFlags::empty())
+    };
+    let dependency = vk::SubpassDependency {
+        src_subpass: vk::SUBPASS_EXTERNAL,
+        dst_subpass: 0,
+        src_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT | depth_stages,
+        src_access_mask: depth_access,
+        dst_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT | depth_stages,
+        dst_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_WRITE | depth_access,
+        ..Default::default()
+    };
+
+    let render_pass_info = vk::RenderPassCreateInfo {
+        s_type: vk::StructureType::RENDER_PASS_CREATE_INFO,
+        attachment_count: attachments.len() as u32,
+        p_attachments: attachments.as_ptr(),
+        subpass_count: 1,
+        p_subpasses: &subpass,
+        dependency_count: 1,
+        p_dependencies: &dependency,
+        ..Default::default()
+    };
+    Ok(unsafe { device.create_render_pass(&render_pass_info, None)? })
+}
+
+pub fn create_graphics_pipeline(
+    device: &ash::Device,
+    render_pass: &vk::RenderPass,
+    info: &GraphicsPipelineInfo,
+) -> Result<(vk::PipelineLayout, vk::Pipeline)> {
+    check_push_constant_ranges(info.push_constant_ranges, info.max_push_constants_size)?;
+    let vert_shader_code = read_shader(info.vert_shader_path)?;
+    let frag_shader_code = read_shader(info.frag_shader_path)?;
+
+    let vert_shader_module = create_shader_module(device, vert_shader_code)?;
+    // The modules are only needed while the pipeline is created, whether that works or not
+    let result = create_shader_module(device, frag_shader_code).and_then(|frag_shader_module| {
+        let result = create_pipeline_from_modules(
+            device,
+            render_pass,
+            info,
+            vert_shader_module,
+            frag_shader_module,
+        );
+        unsafe { device.destroy_shader_module(frag_shader_module, None) };
+        result
+    });
+    unsafe { device.destroy_shader_module(vert_shader_module, None) };
+    result
+}
+
+// Fails when a range ends past what the device can push
+fn check_push_constant_ranges(ranges: &[vk::PushConstantRange], max_size: u32) -> Result<()> {
+    match ranges
+        .iter()
+        .find(|range| range.offset + range.size > max_size)
+    {
+        Some(range) => Err(Error::Config(format!(
+            "Push constants up to byte {} don't fit in the {} bytes the device has",
+            range.offset + range.size,
+            max_size
+        ))),
+        None => Ok(()),
+    }
+}
+
+fn create_pipeline_from_modules(
+    device: &ash::Device,
+    render_pass: &vk::RenderPass,
+    info: &GraphicsPipelineInfo,
+    vert_shader_module: vk::ShaderModule,
+    frag_shader_module: vk::ShaderModule,
+) -> Result<(vk::PipelineLayout, vk::Pipeline)> {
+    let vert_shader_stage_info = vk::PipelineShaderStageCreateInfo {
+        s_type: vk::StructureType::PIPELINE_SHADER_STAGE_CREATE_INFO,
+        stage: vk::ShaderStageFlags::VERTEX,
+        module: vert_shader_module,
+        p_name: c"main".as_ptr(),
+        ..Default::default()
+    };
+
+    let frag_shader_stage_info = vk::PipelineShaderStageCreateInfo {
+        s_type: vk::StructureType::PIPELINE_SHADER_STAGE_CREATE_INFO,
+        stage: vk::ShaderStageFlags::FRAGMENT,
+        module: frag_shader_module,
+        p_name: c"main".as_ptr(),
+        ..Default::default()
+    };
+
+    let shader_stages = [vert_shader_stage_info, frag_shader_stage_info];
+
+    let vertex_input_info = vk::PipelineVertexInputStateCreateInfo {
+        s_type: vk::StructureType::PIPELINE_VERTEX_INPUT_STATE_CREATE_INFO,
+        vertex_binding_description_count: info.vertex_binding_descriptions.len() as u32,
+        p_vertex_binding_descriptions: info.vertex_binding_descriptions.as_ptr(),
+        vertex_attribute_description_count: info.vertex_attribute_descriptions.len() as u32,
+        p_vertex_attribute_descriptions: info.vertex_attribute_descriptions.as_ptr(),
+        ..Default::default()
+    };
+
+    let input_assembly = vk::PipelineInputAssemblyStateCreateInfo {
+        s_type: vk::StructureType::PIPELINE_INPUT_ASSEMBLY_STATE_CREATE_INFO,
+        topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+        primitive_restart_enable: vk::FALSE,
+        ..Default::default()
+    };
+
+    let viewport_state = vk::PipelineViewportStateCreateInfo {
+        s_type: vk::StructureType::PIPELINE_VIEWPORT_STATE_CREATE_INFO,
+        viewport_count: 1,
+        scissor_count: 1,
+        ..Default::default()
+    };
+
+    let rasterizer = vk::PipelineRasterizationStateCreateInfo {
+        s_type: vk::StructureType::PIPELINE_RASTERIZATION_STATE_CREATE_INFO,
+        depth_clamp_enable: vk::FALSE,
+        rasterizer_discard_enable: vk::FALSE,
+        polygon_mode: vk::PolygonMode::FILL,
+        line_width: 1.0,
+        cull_mode: vk::CullModeFlags::BACK,
+        front_face: vk::FrontFace::CLOCKWISE,
+        depth_bias_enable: vk::FALSE,
+        depth_bias_constant_factor: 0.0,
+        depth_bias_clamp: 0.0,
+        depth_bias_slope_factor: 0.0,
+        ..Default::default()
+    };
+
+    let multisampling = vk::PipelineMultisampleStateCreateInfo {
+        s_type: vk::StructureType::PIPELINE_MULTISAMPLE_STATE_CREATE_INFO,
+        sample_shading_enable: info.min_sample_shading.is_some() as vk::Bool32,
+        rasterization_samples: info.samples,
+        min_sample_shading: info.min_sample_shading.unwrap_or(1.0),
+        p_sample_mask: ptr::null(),
+        alpha_to_coverage_enable: vk::FALSE,
+        alpha_to_one_enable: vk::FALSE,
+        ..Default::default()
+    };
+
+    let color_blend_attachment = vk::PipelineColorBlendAttachmentState {
+        color_write_mask: vk::ColorComponentFlags::R
+            | vk::ColorComponentFlags::G
+            | vk::ColorComponentFlags::B
+            | vk::ColorComponentFlags::A,
+        blend_enable: vk::FALSE,
+        src_color_blend_factor: vk::BlendFactor::ONE,
+        dst_color_blend_factor: vk::BlendFactor::ZERO,
+        color_blend_op: vk::BlendOp::ADD,
+        src_alpha_blend_factor: vk::BlendFactor::ONE,
+        dst_alpha_blend_factor: vk::BlendFactor::ZERO,
+        alpha_blend_op: vk::BlendOp::ADD,
+    };
+
+    let color_blending = vk::PipelineColorBlendStateCreateInfo {
+        s_type: vk::StructureType::PIPELINE_COLOR_BLEND_STATE_CREATE_INFO,
+        logic_op_enable: vk::FALSE,
+        logic_op: vk::LogicOp::COPY,
+        attachment_count: 1,
+        p_attachments: &color_blend_attachment,
+        blend_constants: [0.0, 0.0, 0.0, 0.0],
+        ..Default::default()
+    };
+
+    let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+
+    let dynamic_state = vk::PipelineDynamicStateCreateInfo {
+        s_type: vk::StructureType::PIPELINE_DYNAMIC_STATE_CREATE_INFO,
+        dynamic_state_count: dynamic_states.len() as u32,
+        p_dynamic_states: dynamic_states.as_ptr(),
+        ..Default::default()
+    };
+
+    let depth_stencil = info
+        .depth
+        .map(|depth| vk::PipelineDepthStencilStateCreateInfo {
+            s_type: vk::StructureType::PIPELINE_DEPTH_STENCIL_STATE_CREATE_INFO,
+            depth_test_enable: vk::TRUE,
+            depth_write_enable: depth.write as vk::Bool32,
+            depth_compare_op: depth.compare_op,
+            depth_bounds_test_enable: vk::FALSE,
+            stencil_test_enable: vk::FALSE,
+            ..Default::default()
+        });
+
+    let pipeline_layout_info = vk::PipelineLayoutCreateInfo {
+        s_type: vk::StructureType::PIPELINE_LAYOUT_CREATE_INFO,
+        set_layout_count: info.descriptor_set_layouts.len() as u32,
+        p_set_layouts: info.descriptor_set_layouts.as_ptr(),
+        push_constant_range_count: info.push_constant_ranges.len() as u32,
+        p_push_constant_ranges: info.push_constant_ranges.as_ptr(),
+        ..Default::default()
+    };
+
+    let pipeline_layout = unsafe { device.create_pipeline_layout(&pipeline_layout_info, None)? };
+
+    let pipeline_info = vk::GraphicsPipelineCreateInfo {
+        s_type: vk::StructureType::GRAPHICS_PIPELINE_CREATE_INFO,
+        stage_count: 2,
+        p_stages: shader_stages.as_ptr(),
+        p_vertex_input_state: &vertex_input_info,
+        p_input_assembly_state: &input_assembly,
+        p_viewport_state: &viewport_state,
+        p_rasterization_state: &rasterizer,
+        p_multisample_state: &multisampling,
+        p_depth_stencil_state: depth_stencil
+            .as_ref()
+            .map_or(ptr::null(), |depth_stencil| depth_stencil),
+        p_color_blend_state: &color_blending,
+        p_dynamic_state: &dynamic_state,
+        layout: pipeline_layout,
+        render_pass: *render_pass,
+        subpass: 0,
+        base_pipeline_handle: vk::Pipeline::null(),
+        base_pipeline_index: -1,
+        ..Default::default()
+    };
+
+    let graphics_pipelines = unsafe {
+        device.create_graphics_pipelines(vk::PipelineCache::null(), &[pipeline_info], None)
+    };
+    match graphics_pipelines {
+        Ok(graphics_pipelines) => Ok((pipeline_layout, graphics_pipelines[0])),
+        Err((_, result)) => {
+            unsafe { device.destroy_pipeline_layout(pipeline_layout, None) };
+            Err(result.into())
+        }
+    }
+}
+
+// A range for a T pushed at offset. Every device takes at least 128 bytes of push constants.
+pub fn push_constant_range<T: Copy>(
+    stage_flags: vk::ShaderStageFlags,
+    offset: u32,
+) -> vk::PushConstantRange {
+    const {
+        assert!(
+            size_of::<T>().is_multiple_of(4),
+            "Push constants come in multiples of 4 bytes"
+        )
+    };
+    vk::PushConstantRange {
+        stage_flags,
+        offset,
+        size: size_of::<T>() as u32,
+    }
+}
+
+// Records an update of the push constants at offset. T has to be laid out like the shader's
+// push_constant block, and stage_flags must match the range it was declared with.
+pub fn cmd_push_constants<T: Copy>(
+    device: &ash::Device,
+    command_buffer: vk::CommandBuffer,
+    pipeline_layout: vk::PipelineLayout,
+    stage_flags: vk::ShaderStageFlags,
+    offset: u32,
+    value: &T,
+) {
+    const {
+        assert!(
+            size_of::<T>().is_multiple_of(4),
+            "Push constants come in multiples of 4 bytes"
+        )
+    };
+    let bytes = unsafe { slice::from_raw_parts(value as *const T as *const u8, size_of::<T>()) };
+    unsafe {
+        device.cmd_push_constants(command_buffer, pipeline_layout, stage_flags, offset, bytes)
+    };
+}
+
+pub fn read_shader(path: &str) -> Result<Vec<u8>> {
+    fs::read(path).map_err(|source| Error::ShaderLoad {
+        path: Path::new(path).to_path_buf(),
+        source,
+    })
+}
+
+pub fn create_shader_module(device: &ash::Device, code: Vec<u8>) -> Result<vk::ShaderModule> {
+    let create_info = vk::ShaderModuleCreateInfo {
+        s_type: vk::StructureType::SHADER_MODULE_CREATE_INFO,
+        code_size: code.len(),
+        p_code: code.as_ptr() as *const u32,
+        ..Default::default()
+    };
+    Ok(unsafe { device.create_shader_module(&create_info, None)? })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn push_constants_have_to_fit() {
+        let tint = push_constant_range::<[f32; 4]>(vk::ShaderStageFlags::FRAGMENT, 0);
+        assert_eq!(tint.size, 16);
+        assert!(check_push_constant_ranges(&[tint], 128).is_ok());
+        let model = push_constant_range::<[f32; 16]>(vk::ShaderStageFlags::VERTEX, 16);
+        assert!(check_push_constant_ranges(&[tint, model], 128).is_ok());
+        assert!(check_push_constant_ranges(&[tint, model], 64).is_err());
+        assert!(check_push_constant_ranges(&[], 0).is_ok());
+    }
+}
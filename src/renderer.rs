@@ -0,0 +1,1318 @@
+use crate::capture;
+use crate::config::RendererConfig;
+use crate::error::{Error, Result};
+use crate::memory::{self, Allocation, MemoryAllocator, MemoryRequest};
+use crate::mesh::Mesh;
+use crate::scene::{self, Scene, SceneData};
+use crate::swapchain::{SwapchainSupportDetails, OFFSCREEN_IMAGE_FORMAT};
+use crate::texture::{Texture, TextureData, TextureSupport};
+use crate::uniform::{DynamicUniformBuffer, UniformBuffer};
+use crate::upload::StagingUploader;
+use crate::vertex::Vertex;
+use crate::{
+    buffer, commands, device, image, instance, pipeline, std140_struct, surface, swapchain,
+};
+use ash::extensions::khr::{Surface, Swapchain};
+use ash::{vk, Entry};
+use std::mem::size_of;
+use std::path::{Path, PathBuf};
+use std::ptr;
+use std::rc::Rc;
+use std::time::{Duration, SystemTime};
+use std::vec::Vec;
+use winit::{
+    dpi::PhysicalSize,
+    event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent},
+    event_loop::{ControlFlow, EventLoop},
+    window::WindowBuilder,
+};
+
+std140_struct! {
+    struct UniformBufferObject {
+        view: glam::Mat4,
+        proj: glam::Mat4,
+    }
+}
+
+std140_struct! {
+    struct ObjectUniform {
+        model: glam::Mat4,
+    }
+}
+
+// Pushed before each draw, laid out like the push_constant block in shader.frag
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct ObjectPushConstants {
+    tint: glam::Vec4,
+}
+
+// Devices are picked by the limits in REQUIRED_LIMITS, so they have to cover what is used here
+const _: () = assert!(
+    size_of::<ObjectPushConstants>() as u32 <= device::REQUIRED_LIMITS.push_constants_size
+        && size_of::<UniformBufferObject>() as u32 <= device::REQUIRED_LIMITS.uniform_buffer_range
+);
+
+// One of the things a frame draws. The tint multiplies its vertex colors.
+#[derive(Clone, Copy, Debug)]
+pub struct Object {
+    pub placement: glam::Mat4,
+    pub tint: glam::Vec4,
+}
+
+impl Object {
+    pub fn new(placement: glam::Mat4) -> Self {
+        Self {
+            placement,
+            tint: glam::Vec4::ONE,
+        }
+    }
+}
+
+pub struct VulkanDetails {
+    config: RendererConfig,
+    entry: ash::Entry,
+    instance: ash::Instance,
+    debug_messenger: vk::DebugUtilsMessengerEXT,
+    surface: vk::SurfaceKHR,
+    physical_device: vk::PhysicalDevice,
+    device: ash::Device,
+    // Every buffer and image gets its memory from here
+    allocator: Rc<MemoryAllocator>,
+    queue_families: device::QueueFamilyIndices,
+    graphics_queue: vk::Queue,
+    present_queue: vk::Queue,
+    transfer_queue: vk::Queue,
+    compute_queue: vk::Queue,
+    swap_chain: vk::SwapchainKHR,
+    swap_chain_images: Vec<vk::Image>,
+    swap_chain_image_format: vk::Format,
+    swap_chain_extent: vk::Extent2D,
+    swap_chain_image_views: Vec<vk::ImageView>,
+    // Only used in headless mode, where the swap chain images are offscreen images we allocated ourselves
+    offscreen_image_memory: Vec<Allocation>,
+    // Sized like the swap chain, so it's recreated along with it
+    depth_format: vk::Format,
+    depth_image: vk::Image,
+    depth_image_memory: Allocation,
+    depth_image_view: vk::ImageView,
+    // The multisampled image that's resolved into the swap chain image, when there is more than one sample
+    msaa_samples: vk::SampleCountFlags,
+    color_target: Option<(vk::Image, Allocation, vk::ImageView)>,
+    render_pass: vk::RenderPass,
+    descriptor_set_layout: vk::DescriptorSetLayout,
+    pipeline_layout: vk::PipelineLayout,
+    graphics_pipeline: vk::Pipeline,
+    swap_chain_framebuffers: Vec<vk::Framebuffer>,
+    command_pool: vk::CommandPool,
+    // Uploads are recorded here, it belongs to the transfer family
+    transfer_command_pool: vk::CommandPool,
+    uploader: StagingUploader,
+    // What every object draws, the configured mesh or scene or else a quad
+    scene: Scene,
+    // Sampled by materials without a base color texture, a single white texel when no texture is
+    // configured
+    texture: Texture,
+    // One per frame in flight, so a frame's buffer is only written once that frame is done
+    uniform_buffers: Vec<UniformBuffer<UniformBufferObject>>,
+    // The model matrix of every object, bound at a dynamic offset per draw. Also one per frame in flight.
+    object_buffers: Vec<DynamicUniformBuffer<ObjectUniform>>,
+    // Where each object is placed, the animation spins them around their own origin
+    objects: Vec<Object>,
+    descriptor_pool: vk::DescriptorPool,
+    // One per frame in flight for each of the scene's materials, indexed by material first
+    descriptor_sets: Vec<Vec<vk::DescriptorSet>>,
+    command_buffers: Vec<vk::CommandBuffer>,
+    image_available_semaphores: Vec<vk::Semaphore>,
+    render_finished_semaphores: Vec<vk::Semaphore>,
+    in_flight_fences: Vec<vk::Fence>,
+    framebuffer_resized: bool,
+    current_frame: usize,
+    last_image_index: usize,
+    screenshot_path: Option<PathBuf>,
+    pending_readback: Option<(vk::Buffer, Allocation)>,
+    start_time: SystemTime,
+    // Pins the animation clock, so that rendered frames are reproducible
+    fixed_time: Option<Duration>,
+}
+
+pub struct HelloTriangleApplication {
+    event_loop: winit::event_loop::EventLoop<()>,
+    window: winit::window::Window,
+    vulkan_details: VulkanDetails,
+}
+
+pub struct HeadlessApplication {
+    vulkan_details: VulkanDetails,
+}
+
+impl VulkanDetails {
+    pub fn new(window: &winit::window::Window, config: &RendererConfig) -> Result<Self> {
+        VulkanDetails::init(Some(window), config)
+    }
+    pub fn new_headless(config: &RendererConfig) -> Result<Self> {
+        VulkanDetails::init(None, config)
+    }
+    // Without a window we render into offscreen images of the configured size instead of a swap chain
+    fn init(window: Option<&winit::window::Window>, config: &RendererConfig) -> Result<Self> {
+        let config = config.clone();
+        let entry = Entry::linked();
+        let instance = instance::create_instance(&entry, window, &config)?;
+        let debug_messenger = instance::create_debug_messenger(&entry, &instance, &config.debug)?;
+        let surface = match window {
+            Some(window) => surface::create_surface(window, &entry, &instance)?,
+            None => vk::SurfaceKHR::null(),
+        };
+        let physical_device = device::pick_physical_device(&entry, &instance, &surface, &config)?;
+        let device = device::create_logical_device(&entry, &instance, &physical_device, &surface)?;
+        let allocator = Rc::new(MemoryAllocator::new(
+            &instance,
+            &physical_device,
+            &device,
+            device::supports_memory_budget(&entry, &instance, &physical_device)?,
+        ));
+        let queue_families =
+            device::find_queue_familes(&entry, &instance, &physical_device, &surface)?;
+        let graphics_queue_index = queue_families.graphics.ok_or(Error::NoSuitableDevice)? as u32;
+        let graphics_queue = unsafe { device.get_device_queue(graphics_queue_index, 0) };
+        let present_queue = match queue_families.present {
+            Some(index) => unsafe { device.get_device_queue(index as u32, 0) },
+            None => vk::Queue::null(),
+        };
+        // Without dedicated families these are the graphics queue again
+        let transfer_queue_index = queue_families
+            .transfer_or_graphics()
+            .ok_or(Error::NoSuitableDevice)? as u32;
+        let transfer_queue = unsafe { device.get_device_queue(transfer_queue_index, 0) };
+        let compute_queue_index = queue_families
+            .compute_or_graphics()
+            .ok_or(Error::NoSuitableDevice)? as u32;
+        let compute_queue = unsafe { device.get_device_queue(compute_queue_index, 0) };
+        let (
+            swap_chain,
+            swap_chain_images,
+            swap_chain_image_format,
+            swap_chain_extent,
+            offscreen_image_memory,
+        ) = match window {
+            Some(window) => {
+                let (swap_chain, swap_chain_images, swap_chain_image_format, swap_chain_extent) =
+                    swapchain::create_swap_chain(
+                        window,
+                        &entry,
+                        &instance,
+                        &physical_device,
+                        &device,
+                        &surface,
+                        config.present_mode,
+                    )?;
+                (
+                    swap_chain,
+                    swap_chain_images,
+                    swap_chain_image_format,
+                    swap_chain_extent,
+                    Vec::new(),
+                )
+            }
+            None => {
+                let headless_extent = vk::Extent2D {
+                    width: config.width,
+                    height: config.height,
+                };
+                let (offscreen_images, offscreen_image_memory) =
+                    swapchain::create_offscreen_images(
+                        &device,
+                        &allocator,
+                        &headless_extent,
+                        config.max_frames_in_flight,
+                    )?;
+                (
+                    vk::SwapchainKHR::null(),
+                    offscreen_images,
+                    OFFSCREEN_IMAGE_FORMAT,
+                    headless_extent,
+                    offscreen_image_memory,
+                )
+            }
+        };
+        let swap_chain_image_views =
+            swapchain::create_image_views(&device, &swap_chain_images, &swap_chain_image_format)?;
+        let msaa_samples =
+            device::clamp_sample_count(&instance, &physical_device, config.msaa_samples);
+        if msaa_samples.as_raw() != config.msaa_samples {
+            log::warn!(
+                "{} samples were requested, using {:?}",
+                config.msaa_samples,
+                msaa_samples
+            );
+        }
+        let min_sample_shading = match config.min_sample_shading {
+            Some(_) if !device::supports_sample_rate_shading(&instance, &physical_device) => {
+                log::warn!("Sample shading isn't supported by the device, shading once per pixel");
+                None
+            }
+            min_sample_shading => min_sample_shading,
+        };
+        let depth_format = image::find_depth_format(&instance, &physical_device)?;
+        let (depth_image, depth_image_memory, depth_image_view) = image::create_depth_resources(
+            &device,
+            &allocator,
+            depth_format,
+            &swap_chain_extent,
+            msaa_samples,
+        )?;
+        let color_target = VulkanDetails::create_color_target(
+            &device,
+            &allocator,
+            swap_chain_image_format,
+            &swap_chain_extent,
+            msaa_samples,
+        )?;
+        let render_pass = pipeline::create_render_pass(
+            &device,
+            &swap_chain_image_format,
+            if window.is_some() {
+                vk::ImageLayout::PRESENT_SRC_KHR
+            } else {
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL
+            },
+            Some(depth_format),
+            msaa_samples,
+        )?;
+        let descriptor_set_layout = VulkanDetails::create_descriptor_set_layout(&device)?;
+        let (pipeline_layout, graphics_pipeline) = pipeline::create_graphics_pipeline(
+            &device,
+            &render_pass,
+            &pipeline::GraphicsPipelineInfo {
+                vert_shader_path: &config.vert_shader_path,
+                frag_shader_path: &config.frag_shader_path,
+                vertex_binding_descriptions: &[Vertex::get_binding_description()],
+                vertex_attribute_descriptions: &Vertex::get_attribute_descriptions(),
+                descriptor_set_layouts: &[descriptor_set_layout],
+                push_constant_ranges: &[pipeline::push_constant_range::<ObjectPushConstants>(
+                    vk::ShaderStageFlags::FRAGMENT,
+                    0,
+                )],
+                max_push_constants_size: device::max_push_constants_size(
+                    &instance,
+                    &physical_device,
+                ),
+                depth: Some(config.depth_test()),
+                samples: msaa_samples,
+                min_sample_shading,
+            },
+        )?;
+        let swap_chain_framebuffers = swapchain::create_framebuffers(
+            &device,
+            &swap_chain_image_views,
+            &swap_chain_extent,
+            &render_pass,
+            Some(depth_image_view),
+            color_target.as_ref().map(|(_, _, view)| *view),
+        )?;
+        let command_pool = commands::create_command_pool_for_family(&device, graphics_queue_index)?;
+        let transfer_command_pool =
+            commands::create_command_pool_for_family(&device, transfer_queue_index)?;
+        let upload_queues = commands::UploadQueues {
+            transfer_command_pool,
+            transfer_queue,
+            transfer_family: transfer_queue_index,
+            graphics_command_pool: command_pool,
+            graphics_queue,
+            graphics_family: graphics_queue_index,
+        };
+        let mut uploader = StagingUploader::new(
+            &device,
+            allocator.clone(),
+            upload_queues,
+            config.staging_ring_size,
+        )?;
+        let scene_data = match &config.mesh_path {
+            Some(path) => scene::load_scene(Path::new(path))?,
+            None => SceneData::from_mesh(Mesh::quad()),
+        };
+        if scene_data.draws.len() > config.max_objects {
+            return Err(Error::Config(format!(
+                "The scene has {} draw records, but max_objects is {}",
+                scene_data.draws.len(),
+                config.max_objects
+            )));
+        }
+        let texture_support = TextureSupport::query(&instance, &physical_device);
+        let scene = Scene::new(
+            &device,
+            allocator.clone(),
+            &mut uploader,
+            &scene_data,
+            &texture_support,
+        )?;
+        let (texture, _) = match &config.texture_path {
+            Some(path) => Texture::load(
+                &device,
+                allocator.clone(),
+                &mut uploader,
+                Path::new(path),
+                &texture_support,
+            )?,
+            None => Texture::new(
+                &device,
+                allocator.clone(),
+                &mut uploader,
+                &TextureData::solid([255, 255, 255, 255]),
+                &texture_support,
+            )?,
+        };
+        if texture.view_type() != vk::ImageViewType::TYPE_2D {
+            return Err(Error::Config(format!(
+                "The texture is a {:?} image, but the fragment shader samples a sampler2D",
+                texture.view_type()
+            )));
+        }
+        // The uploads go in one batch, the first frame is submitted after it so it needs no wait
+        uploader.flush()?;
+        let uniform_buffers = (0..config.max_frames_in_flight)
+            .map(|_| UniformBuffer::new(&device, allocator.clone()))
+            .collect::<Result<Vec<_>>>()?;
+        let min_offset_alignment = unsafe {
+            instance
+                .get_physical_device_properties(physical_device)
+                .limits
+                .min_uniform_buffer_offset_alignment
+        };
+        let object_buffers = (0..config.max_frames_in_flight)
+            .map(|_| {
+                DynamicUniformBuffer::new(
+                    &device,
+                    allocator.clone(),
+                    config.max_objects,
+                    min_offset_alignment,
+                )
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let descriptor_pool = VulkanDetails::create_descriptor_pool(
+            &device,
+            config.max_frames_in_flight * scene.materials().len(),
+        )?;
+        let descriptor_sets = (0..scene.materials().len())
+            .map(|material| {
+                VulkanDetails::create_descriptor_sets(
+                    &device,
+                    &uniform_buffers
+                        .iter()
+                        .map(UniformBuffer::buffer)
+                        .collect::<Vec<_>>(),
+                    &object_buffers
+                        .iter()
+                        .map(DynamicUniformBuffer::buffer)
+                        .collect::<Vec<_>>(),
+                    &scene.base_color_image_info(material, &texture),
+                    &descriptor_set_layout,
+                    &descriptor_pool,
+                )
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let command_buffers =
+            commands::create_command_buffers(&device, &command_pool, config.max_frames_in_flight)?;
+        let (image_available_semaphores, render_finished_semaphores, in_flight_fences) =
+            commands::create_sync_objects(&device, config.max_frames_in_flight)?;
+        Ok(Self {
+            config,
+            entry,
+            instance,
+            debug_messenger,
+            surface,
+            physical_device,
+            device,
+            allocator,
+            queue_families,
+            graphics_queue,
+            present_queue,
+            transfer_queue,
+            compute_queue,
+            swap_chain,
+            swap_chain_images,
+            swap_chain_image_format,
+            swap_chain_extent,
+            swap_chain_image_views,
+            offscreen_image_memory,
+            depth_format,
+            depth_image,
+            depth_image_memory,
+            depth_image_view,
+            msaa_samples,
+            color_target,
+            render_pass,
+            descriptor_set_layout,
+            pipeline_layout,
+            graphics_pipeline,
+            swap_chain_framebuffers,
+            command_pool,
+            transfer_command_pool,
+            uploader,
+            scene,
+            texture,
+            uniform_buffers,
+            object_buffers,
+            objects: vec![Object::new(glam::Mat4::IDENTITY)],
+            descriptor_pool,
+            descriptor_sets,
+            command_buffers,
+            image_available_semaphores,
+            render_finished_semaphores,
+            in_flight_fences,
+            framebuffer_resized: false,
+            current_frame: 0,
+            last_image_index: 0,
+            screenshot_path: None,
+            pending_readback: None,
+            start_time: SystemTime::UNIX_EPOCH,
+            fixed_time: None,
+        })
+    }
+    pub fn queue_families(&self) -> device::QueueFamilyIndices {
+        self.queue_families
+    }
+    // Queues for work that should overlap with rendering. Buffers they share with the graphics queue
+    // change hands with commands::buffer_ownership_barrier, unless the families are the same.
+    pub fn transfer_queue(&self) -> vk::Queue {
+        self.transfer_queue
+    }
+    pub fn compute_queue(&self) -> vk::Queue {
+        self.compute_queue
+    }
+    // Replaces the objects drawn from the next frame on. Each object draws every draw record of the
+    // scene, which takes at most max_objects draws together.
+    pub fn set_objects(&mut self, objects: &[Object]) -> Result<()> {
+        let draw_count = objects.len() * self.scene.draws().len();
+        if draw_count > self.config.max_objects {
+            return Err(Error::Config(format!(
+                "{} objects were given, drawing the scene {} times, but max_objects is {}",
+                objects.len(),
+                draw_count,
+                self.config.max_objects
+            )));
+        }
+        self.objects = objects.to_vec();
+        Ok(())
+    }
+    pub fn memory_stats(&self) -> Vec<memory::HeapStats> {
+        self.allocator.stats()
+    }
+    fn is_headless(&self) -> bool {
+        self.swap_chain == vk::SwapchainKHR::null()
+    }
+    // The layout the render pass leaves the color attachment in
+    fn color_attachment_final_layout(&self) -> vk::ImageLayout {
+        if self.is_headless() {
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL
+        } else {
+            vk::ImageLayout::PRESENT_SRC_KHR
+        }
+    }
+    fn create_descriptor_set_layout(device: &ash::Device) -> Result<vk::DescriptorSetLayout> {
+        let bindings = [
+            vk::DescriptorSetLayoutBinding {
+                binding: 0,
+                descriptor_type: vk::DescriptorType::UNIFORM_BUFFER,
+                descriptor_count: 1,
+                stage_flags: vk::ShaderStageFlags::VERTEX,
+                p_immutable_samplers: ptr::null(),
+            },
+            vk::DescriptorSetLayoutBinding {
+                binding: 1,
+                descriptor_type: vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC,
+                descriptor_count: 1,
+                stage_flags: vk::ShaderStageFlags::VERTEX,
+                p_immutable_samplers: ptr::null(),
+            },
+            vk::DescriptorSetLayoutBinding {
+                binding: 2,
+                descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                descriptor_count: 1,
+                stage_flags: vk::ShaderStageFlags::FRAGMENT,
+                p_immutable_samplers: ptr::null(),
+            },
+        ];
+
+        let layout_info = vk::DescriptorSetLayoutCreateInfo {
+            s_type: vk::StructureType::DESCRIPTOR_SET_LAYOUT_CREATE_INFO,
+            binding_count: bindings.len() as u32,
+            p_bindings: bindings.as_ptr(),
+            ..Default::default()
+        };
+
+        Ok(unsafe { device.create_descriptor_set_layout(&layout_info, None)? })
+    }
+    // Room for set_count descriptor sets, each with one descriptor of every binding
+    fn create_descriptor_pool(
+        device: &ash::Device,
+        set_count: usize,
+    ) -> Result<vk::DescriptorPool> {
+        let pool_sizes = [
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::UNIFORM_BUFFER,
+                descriptor_count: set_count as u32,
+            },
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC,
+                descriptor_count: set_count as u32,
+            },
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                descriptor_count: set_count as u32,
+            },
+        ];
+
+        let pool_info = vk::DescriptorPoolCreateInfo {
+            s_type: vk::StructureType::DESCRIPTOR_POOL_CREATE_INFO,
+            pool_size_count: pool_sizes.len() as u32,
+            p_pool_sizes: pool_sizes.as_ptr(),
+            max_sets: set_count as u32,
+            ..Default::default()
+        };
+
+        Ok(unsafe { device.create_descriptor_pool(&pool_info, None)? })
+    }
+    fn create_descriptor_sets(
+        device: &ash::Device,
+        uniform_buffers: &[vk::Buffer],
+        object_buffers: &[vk::Buffer],
+        image_info: &vk::DescriptorImageInfo,
+        descriptor_set_layout: &vk::DescriptorSetLayout,
+        descriptor_pool: &vk::DescriptorPool,
+    ) -> Result<Vec<vk::DescriptorSet>> {
+        let layouts = vec![*descriptor_set_layout; uniform_buffers.len()];
+        let alloc_info = vk::DescriptorSetAllocateInfo {
+            s_type: vk::StructureType::DESCRIPTOR_SET_ALLOCATE_INFO,
+            descriptor_pool: *descriptor_pool,
+            descriptor_set_count: layouts.len() as u32,
+            p_set_layouts: layouts.as_ptr(),
+            ..Default::default()
+        };
+
+        let descriptor_sets = unsafe { device.allocate_descriptor_sets(&alloc_info)? };
+
+        for i in 0..uniform_buffers.len() {
+            let buffer_info = vk::DescriptorBufferInfo {
+                buffer: uniform_buffers[i],
+                offset: 0,
+                range: size_of::<UniformBufferObject>() as u64,
+            };
+            // The range covers one object, the dynamic offset given when binding says which
+            let object_buffer_info = vk::DescriptorBufferInfo {
+                buffer: object_buffers[i],
+                offset: 0,
+                range: size_of::<ObjectUniform>() as u64,
+            };
+
+            let descriptor_writes = [
+                vk::WriteDescriptorSet {
+                    s_type: vk::StructureType::WRITE_DESCRIPTOR_SET,
+                    dst_set: descriptor_sets[i],
+                    dst_binding: 0,
+                    dst_array_element: 0,
+                    descriptor_type: vk::DescriptorType::UNIFORM_BUFFER,
+                    descriptor_count: 1,
+                    p_buffer_info: &buffer_info,
+                    p_image_info: ptr::null(),
+                    p_texel_buffer_view: ptr::null(),
+                    ..Default::default()
+                },
+                vk::WriteDescriptorSet {
+                    s_type: vk::StructureType::WRITE_DESCRIPTOR_SET,
+                    dst_set: descriptor_sets[i],
+                    dst_binding: 1,
+                    dst_array_element: 0,
+                    descriptor_type: vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC,
+                    descriptor_count: 1,
+                    p_buffer_info: &object_buffer_info,
+                    p_image_info: ptr::null(),
+                    p_texel_buffer_view: ptr::null(),
+                    ..Default::default()
+                },
+                vk::WriteDescriptorSet {
+                    s_type: vk::StructureType::WRITE_DESCRIPTOR_SET,
+                    dst_set: descriptor_sets[i],
+                    dst_binding: 2,
+                    dst_array_element: 0,
+                    descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                    descriptor_count: 1,
+                    p_buffer_info: ptr::null(),
+                    p_image_info: image_info,
+                    p_texel_buffer_view: ptr::null(),
+                    ..Default::default()
+                },
+            ];
+
+            unsafe {
+                device.update_descriptor_sets(&descriptor_writes, &[]);
+            }
+        }
+        Ok(descriptor_sets)
+    }
+    fn record_command_buffer(&self, image_index: usize) -> Result<()> {
+        let begin_info = vk::CommandBufferBeginInfo {
+            s_type: vk::StructureType::COMMAND_BUFFER_BEGIN_INFO,
+            ..Default::default()
+        };
+        unsafe {
+            self.device
+                .begin_command_buffer(self.command_buffers[self.current_frame], &begin_info)?;
+        }
+        let clear_values = [
+            vk::ClearValue {
+                color: vk::ClearColorValue {
+                    float32: [0.0, 0.0, 0.0, 1.0],
+                },
+            },
+            vk::ClearValue {
+                depth_stencil: vk::ClearDepthStencilValue {
+                    depth: self.config.depth_test().clear_depth(),
+                    stencil: 0,
+                },
+            },
+        ];
+        let render_pass_info = vk::RenderPassBeginInfo {
+            s_type: vk::StructureType::RENDER_PASS_BEGIN_INFO,
+            render_pass: self.render_pass,
+            framebuffer: self.swap_chain_framebuffers[image_index],
+            render_area: vk::Rect2D {
+                offset: vk::Offset2D { x: 0, y: 0 },
+                extent: self.swap_chain_extent,
+            },
+            clear_value_count: clear_values.len() as u32,
+            p_clear_values: clear_values.as_ptr(),
+            ..Default::default()
+        };
+        unsafe {
+            self.device.cmd_begin_render_pass(
+                self.command_buffers[self.current_frame],
+                &render_pass_info,
+                vk::SubpassContents::INLINE,
+            );
+            self.device.cmd_bind_pipeline(
+                self.command_buffers[self.current_frame],
+                vk::PipelineBindPoint::GRAPHICS,
+                self.graphics_pipeline,
+            );
+        }
+        let viewport = vk::Viewport {
+            x: 0.0,
+            y: 0.0,
+            width: self.swap_chain_extent.width as f32,
+            height: self.swap_chain_extent.height as f32,
+            min_depth: 0.0,
+            max_depth: 1.0,
+        };
+        unsafe {
+            self.device
+                .cmd_set_viewport(self.command_buffers[self.current_frame], 0, &[viewport]);
+        }
+        let scissor = vk::Rect2D {
+            offset: vk::Offset2D { x: 0, y: 0 },
+            extent: self.swap_chain_extent,
+        };
+        unsafe {
+            self.device
+                .cmd_set_scissor(self.command_buffers[self.current_frame], 0, &[scissor]);
+        }
+        self.scene
+            .cmd_bind_buffers(self.command_buffers[self.current_frame]);
+        // One descriptor set for every material, only the dynamic offset of the model matrix changes
+        // between draws. The material's base color factor goes into the tint.
+        let object_buffer = &self.object_buffers[self.current_frame];
+        let draws = self.scene.draws();
+        for (object_index, object) in self.objects.iter().enumerate() {
+            for (draw_index, draw) in draws.iter().enumerate() {
+                let material = &self.scene.materials()[draw.material];
+                pipeline::cmd_push_constants(
+                    &self.device,
+                    self.command_buffers[self.current_frame],
+                    self.pipeline_layout,
+                    vk::ShaderStageFlags::FRAGMENT,
+                    0,
+                    &ObjectPushConstants {
+                        tint: object.tint * material.base_color_factor,
+                    },
+                );
+                unsafe {
+                    self.device.cmd_bind_descriptor_sets(
+                        self.command_buffers[self.current_frame],
+                        vk::PipelineBindPoint::GRAPHICS,
+                        self.pipeline_layout,
+                        0,
+                        [self.descriptor_sets[draw.material][self.current_frame]].as_ref(),
+                        &[object_buffer.offset(object_index * draws.len() + draw_index)],
+                    );
+                }
+                self.scene
+                    .cmd_draw(self.command_buffers[self.current_frame], draw);
+            }
+        }
+        unsafe {
+            self.device
+                .cmd_end_render_pass(self.command_buffers[self.current_frame]);
+        }
+        if let Some((readback_buffer, _)) = &self.pending_readback {
+            capture::record_image_readback(
+                &self.device,
+                self.command_buffers[self.current_frame],
+                self.swap_chain_images[image_index],
+                self.color_attachment_final_layout(),
+                &self.swap_chain_extent,
+                *readback_buffer,
+            );
+        }
+        unsafe {
+            self.device
+                .end_command_buffer(self.command_buffers[self.current_frame])?;
+        }
+        Ok(())
+    }
+    fn update_uniform_buffer(&mut self, current_image: usize) -> Result<()> {
+        if self.start_time == SystemTime::UNIX_EPOCH {
+            self.start_time = SystemTime::now();
+        }
+
+        let current_time = SystemTime::now();
+
+        let time = match self.fixed_time {
+            Some(fixed_time) => fixed_time,
+            // The clock may go backwards, in which case we just stay at the start of the animation
+            None => current_time
+                .duration_since(self.start_time)
+                .unwrap_or_default(),
+        };
+
+        let spin = glam::Mat4::from_rotation_z(time.as_secs_f32() * 90f32.to_radians());
+        // Laid out like the draws in record_command_buffer, every draw record of one object after another
+        let draws = self.scene.draws();
+        let objects: Vec<ObjectUniform> = self
+            .objects
+            .iter()
+            .flat_map(|object| {
+                draws.iter().map(move |draw| ObjectUniform {
+                    model: object.placement * spin * draw.transform,
+                })
+            })
+            .collect();
+        self.object_buffers[current_image].write_all(&objects)?;
+
+        let mut ubo = UniformBufferObject {
+            view: glam::Mat4::look_at_lh(
+                glam::vec3(2.0f32, 2.0f32, 2.0f32),
+                glam::vec3(0.0f32, 0.0f32, 0.0f32),
+                glam::vec3(0.0f32, 0.0f32, 1.0f32),
+            ),
+            proj: glam::Mat4::perspective_lh(
+                45.0f32.to_radians(),
+                (self.swap_chain_extent.width as f32) / (self.swap_chain_extent.height as f32),
+                0.1f32,
+                10.0f32,
+            ),
+        };
+
+        ubo.proj.y_axis.y *= -1.0f32;
+
+        self.uniform_buffers[current_image].write(&ubo)
+    }
+    fn draw_frame(&mut self, window: &winit::window::Window) -> Result<()> {
+        unsafe {
+            self.device.wait_for_fences(
+                &[self.in_flight_fences[self.current_frame]],
+                true,
+                u64::MAX,
+            )?;
+            let swap_chain_handle = Swapchain::new(&self.instance, &self.device);
+            let (image_index, _) = match swap_chain_handle.acquire_next_image(
+                self.swap_chain,
+                u64::MAX,
+                self.image_available_semaphores[self.current_frame],
+                vk::Fence::null(),
+            ) {
+                Ok(value) => value,
+                Err(error) => match error {
+                    vk::Result::ERROR_OUT_OF_DATE_KHR => {
+                        return self.recreate_swap_chain(window);
+                    }
+                    _ => return Err(error.into()),
+                },
+            };
+            self.device
+                .reset_fences(&[self.in_flight_fences[self.current_frame]])?;
+            self.device.reset_command_buffer(
+                self.command_buffers[self.current_frame],
+                vk::CommandBufferResetFlags::empty(),
+            )?;
+            if self.screenshot_path.is_some() {
+                self.pending_readback = Some(self.create_readback_buffer()?);
+            }
+            self.record_command_buffer(image_index as usize)?;
+            self.update_uniform_buffer(self.current_frame)?;
+            self.last_image_index = image_index as usize;
+            let submit_info = vk::SubmitInfo {
+                s_type: vk::StructureType::SUBMIT_INFO,
+                wait_semaphore_count: 1,
+                p_wait_semaphores: [self.image_available_semaphores[self.current_frame]].as_ptr(),
+                p_wait_dst_stage_mask: [vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT].as_ptr(),
+                command_buffer_count: 1,
+                p_command_buffers: [self.command_buffers[self.current_frame]].as_ptr(),
+                signal_semaphore_count: 1,
+                p_signal_semaphores: [self.render_finished_semaphores[self.current_frame]].as_ptr(),
+                ..Default::default()
+            };
+            self.device.queue_submit(
+                self.graphics_queue,
+                &[submit_info],
+                self.in_flight_fences[self.current_frame],
+            )?;
+            instance::check_validation_error()?;
+            // Read the screenshot back before presenting, as presenting may recreate the swap chain
+            if let (Some(path), Some(readback)) =
+                (self.screenshot_path.take(), self.pending_readback.take())
+            {
+                self.device.wait_for_fences(
+                    &[self.in_flight_fences[self.current_frame]],
+                    true,
+                    u64::MAX,
+                )?;
+                let pixels = self.finish_readback(readback)?;
+                if let Err(error) = capture::write_png(&path, self.swap_chain_extent, &pixels) {
+                    log::error!(
+                        "Unable to write screenshot to {}: {}",
+                        path.display(),
+                        error
+                    );
+                }
+            }
+            let present_info = vk::PresentInfoKHR {
+                s_type: vk::StructureType::PRESENT_INFO_KHR,
+                wait_semaphore_count: 1,
+                p_wait_semaphores: [self.render_finished_semaphores[self.current_frame]].as_ptr(),
+                swapchain_count: 1,
+                p_swapchains: [self.swap_chain].as_ptr(),
+                p_image_indices: &image_index,
+                ..Default::default()
+            };
+            match swap_chain_handle.queue_present(self.present_queue, &present_info) {
+                Ok(should_recreate) => {
+                    if should_recreate || self.framebuffer_resized {
+                        self.framebuffer_resized = false;
+                        self.recreate_swap_chain(window)?;
+                    }
+                }
+                Err(error) => match error {
+                    vk::Result::ERROR_OUT_OF_DATE_KHR => self.recreate_swap_chain(window)?,
+                    _ => return Err(error.into()),
+                },
+            };
+            self.current_frame = (self.current_frame + 1) % self.config.max_frames_in_flight;
+        }
+        Ok(())
+    }
+    fn draw_offscreen_frame(&mut self) -> Result<()> {
+        // Each frame in flight owns its own offscreen image, so there is nothing to acquire
+        let image_index = self.current_frame;
+        unsafe {
+            self.device.wait_for_fences(
+                &[self.in_flight_fences[self.current_frame]],
+                true,
+                u64::MAX,
+            )?;
+            self.device
+                .reset_fences(&[self.in_flight_fences[self.current_frame]])?;
+            self.device.reset_command_buffer(
+                self.command_buffers[self.current_frame],
+                vk::CommandBufferResetFlags::empty(),
+            )?;
+            self.record_command_buffer(image_index)?;
+            self.update_uniform_buffer(self.current_frame)?;
+            self.last_image_index = image_index;
+            let submit_info = vk::SubmitInfo {
+                s_type: vk::StructureType::SUBMIT_INFO,
+                command_buffer_count: 1,
+                p_command_buffers: [self.command_buffers[self.current_frame]].as_ptr(),
+                ..Default::default()
+            };
+            self.device.queue_submit(
+                self.graphics_queue,
+                &[submit_info],
+                self.in_flight_fences[self.current_frame],
+            )?;
+            instance::check_validation_error()?;
+            self.current_frame = (self.current_frame + 1) % self.config.max_frames_in_flight;
+        }
+        Ok(())
+    }
+    // Asks for the next frame to be copied out of the swap chain and written to the given PNG file
+    fn request_screenshot(&mut self, path: PathBuf) -> Result<()> {
+        let swap_chain_support = SwapchainSupportDetails::new(
+            &self.entry,
+            &self.instance,
+            &self.physical_device,
+            &self.surface,
+        )?;
+        if !swap_chain_support
+            .capabilities
+            .supported_usage_flags
+            .contains(vk::ImageUsageFlags::TRANSFER_SRC)
+        {
+            log::warn!("Swap chain images can't be copied from on this surface!");
+            return Ok(());
+        }
+        if capture::convert_to_rgba8(self.swap_chain_image_format, Vec::new()).is_none() {
+            log::warn!(
+                "Unable to take screenshots of {:?} images!",
+                self.swap_chain_image_format
+            );
+            return Ok(());
+        }
+        self.screenshot_path = Some(path);
+        Ok(())
+    }
+    fn create_readback_buffer(&self) -> Result<(vk::Buffer, Allocation)> {
+        buffer::create_buffer(
+            &self.device,
+            &self.allocator,
+            (self.swap_chain_extent.width * self.swap_chain_extent.height * 4) as u64,
+            vk::BufferUsageFlags::TRANSFER_DST,
+            // The CPU reads it back, which is much faster from cached memory. That may not be
+            // coherent, finish_readback invalidates it.
+            MemoryRequest::new(vk::MemoryPropertyFlags::HOST_VISIBLE)
+                .prefer(vk::MemoryPropertyFlags::HOST_CACHED),
+        )
+    }
+    // Reads the readback buffer once its copy has completed, frees it and returns its contents as RGBA8
+    fn finish_readback(&self, readback: (vk::Buffer, Allocation)) -> Result<Vec<u8>> {
+        let (readback_buffer, readback_buffer_memory) = readback;
+        let buffer_size = (self.swap_chain_extent.width * self.swap_chain_extent.height * 4) as u64;
+        let mut pixels = vec![0u8; buffer_size as usize];
+        let invalidated = self
+            .allocator
+            .invalidate(&readback_buffer_memory, 0, buffer_size);
+        if invalidated.is_ok() {
+            unsafe {
+                ptr::copy_nonoverlapping(
+                    readback_buffer_memory.mapped as *const u8,
+                    pixels.as_mut_ptr(),
+                    pixels.len(),
+                );
+            }
+        }
+        buffer::destroy_buffer(
+            &self.device,
+            &self.allocator,
+            readback_buffer,
+            &readback_buffer_memory,
+        );
+        invalidated?;
+        capture::convert_to_rgba8(self.swap_chain_image_format, pixels).ok_or_else(|| {
+            Error::UnsupportedFormat(format!(
+                "{:?} images can't be converted to RGBA8",
+                self.swap_chain_image_format
+            ))
+        })
+    }
+    // Copies the most recently rendered offscreen image into host memory as RGBA8
+    fn read_offscreen_frame(&mut self) -> Result<Vec<u8>> {
+        let readback = self.create_readback_buffer()?;
+
+        let command_buffer =
+            commands::begin_single_time_commands(&self.device, &self.command_pool)?;
+        capture::record_image_readback(
+            &self.device,
+            command_buffer,
+            self.swap_chain_images[self.last_image_index],
+            self.color_attachment_final_layout(),
+            &self.swap_chain_extent,
+            readback.0,
+        );
+        commands::end_single_time_commands(
+            &self.device,
+            &self.command_pool,
+            &self.graphics_queue,
+            command_buffer,
+        )?;
+        instance::check_validation_error()?;
+
+        self.finish_readback(readback)
+    }
+    // Nothing to resolve with a single sample, so the swap chain image is rendered to directly
+    fn create_color_target(
+        device: &ash::Device,
+        allocator: &MemoryAllocator,
+        format: vk::Format,
+        extent: &vk::Extent2D,
+        samples: vk::SampleCountFlags,
+    ) -> Result<Option<(vk::Image, Allocation, vk::ImageView)>> {
+        if samples == vk::SampleCountFlags::TYPE_1 {
+            return Ok(None);
+        }
+        Ok(Some(image::create_color_resources(
+            device, allocator, format, extent, samples,
+        )?))
+    }
+    fn cleanup_swap_chain(&mut self) {
+        unsafe {
+            for framebuffer in &self.swap_chain_framebuffers {
+                self.device.destroy_framebuffer(*framebuffer, None);
+            }
+            for image_view in &self.swap_chain_image_views {
+                self.device.destroy_image_view(*image_view, None);
+            }
+            self.device.destroy_image_view(self.depth_image_view, None);
+            image::destroy_image(
+                &self.device,
+                &self.allocator,
+                self.depth_image,
+                &self.depth_image_memory,
+            );
+            if let Some((color_image, color_image_memory, color_image_view)) = &self.color_target {
+                self.device.destroy_image_view(*color_image_view, None);
+                image::destroy_image(
+                    &self.device,
+                    &self.allocator,
+                    *color_image,
+                    color_image_memory,
+                );
+            }
+            if self.is_headless() {
+                for i in 0..self.swap_chain_images.len() {
+                    self.device.destroy_image(self.swap_chain_images[i], None);
+                    self.allocator.free(&self.offscreen_image_memory[i]);
+                }
+            } else {
+                Swapchain::new(&self.instance, &self.device)
+                    .destroy_swapchain(self.swap_chain, None);
+            }
+        }
+    }
+    fn recreate_swap_chain(&mut self, window: &winit::window::Window) -> Result<()> {
+        unsafe { self.device.device_wait_idle()? };
+
+        self.cleanup_swap_chain();
+
+        (
+            self.swap_chain,
+            self.swap_chain_images,
+            self.swap_chain_image_format,
+            self.swap_chain_extent,
+        ) = swapchain::create_swap_chain(
+            window,
+            &self.entry,
+            &self.instance,
+            &self.physical_device,
+            &self.device,
+            &self.surface,
+            self.config.present_mode,
+        )?;
+
+        self.swap_chain_image_views = swapchain::create_image_views(
+            &self.device,
+            &self.swap_chain_images,
+            &self.swap_chain_image_format,
+        )?;
+
+        (
+            self.depth_image,
+            self.depth_image_memory,
+            self.depth_image_view,
+        ) = image::create_depth_resources(
+            &self.device,
+            &self.allocator,
+            self.depth_format,
+            &self.swap_chain_extent,
+            self.msaa_samples,
+        )?;
+        self.color_target = VulkanDetails::create_color_target(
+            &self.device,
+            &self.allocator,
+            self.swap_chain_image_format,
+            &self.swap_chain_extent,
+            self.msaa_samples,
+        )?;
+
+        self.swap_chain_framebuffers = swapchain::create_framebuffers(
+            &self.device,
+            &self.swap_chain_image_views,
+            &self.swap_chain_extent,
+            &self.render_pass,
+            Some(self.depth_image_view),
+            self.color_target.as_ref().map(|(_, _, view)| *view),
+        )?;
+        Ok(())
+    }
+    fn cleanup(&mut self) {
+        // What the scene used, before everything is freed
+        for heap in self.allocator.stats() {
+            log::debug!(
+                "Heap {}: {} of {} allocated bytes used by {} allocations in {} device memory objects",
+                heap.heap_index,
+                heap.used_bytes,
+                heap.allocated_bytes,
+                heap.allocation_count,
+                heap.device_memory_count
+            );
+        }
+        unsafe {
+            self.cleanup_swap_chain();
+            for uniform_buffer in &self.uniform_buffers {
+                uniform_buffer.destroy();
+            }
+            for object_buffer in &self.object_buffers {
+                object_buffer.destroy();
+            }
+            self.texture.destroy();
+            self.device
+                .destroy_descriptor_pool(self.descriptor_pool, None);
+            self.device
+                .destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+            self.scene.destroy();
+            self.device.destroy_pipeline(self.graphics_pipeline, None);
+            self.device
+                .destroy_pipeline_layout(self.pipeline_layout, None);
+            self.device.destroy_render_pass(self.render_pass, None);
+            for i in 0..self.config.max_frames_in_flight {
+                self.device
+                    .destroy_semaphore(self.image_available_semaphores[i], None);
+                self.device
+                    .destroy_semaphore(self.render_finished_semaphores[i], None);
+                self.device.destroy_fence(self.in_flight_fences[i], None);
+            }
+            self.uploader.destroy();
+            self.device
+                .destroy_command_pool(self.transfer_command_pool, None);
+            self.device.destroy_command_pool(self.command_pool, None);
+            self.allocator.destroy();
+            self.device.destroy_device(None);
+            instance::destroy_debug_messenger(&self.entry, &self.instance, self.debug_messenger);
+            if !self.is_headless() {
+                Surface::new(&self.entry, &self.instance).destroy_surface(self.surface, None);
+            }
+            self.instance.destroy_instance(None);
+        }
+    }
+}
+
+impl HelloTriangleApplication {
+    pub fn new(config: &RendererConfig) -> Result<Self> {
+        let (event_loop, window) = HelloTriangleApplication::init_window(config)?;
+        let vulkan_details = VulkanDetails::new(&window, config)?;
+        Ok(Self {
+            event_loop,
+            window,
+            vulkan_details,
+        })
+    }
+    pub fn set_objects(&mut self, objects: &[Object]) -> Result<()> {
+        self.vulkan_details.set_objects(objects)
+    }
+    pub fn run(mut self) -> ! {
+        self.event_loop.run(move |event, _, control_flow| {
+            *control_flow = ControlFlow::Poll;
+
+            match event {
+                Event::WindowEvent {
+                    event: WindowEvent::CloseRequested,
+                    window_id,
+                } if window_id == self.window.id() => *control_flow = ControlFlow::Exit,
+                Event::WindowEvent {
+                    event:
+                        WindowEvent::KeyboardInput {
+                            input:
+                                KeyboardInput {
+                                    state: ElementState::Pressed,
+                                    virtual_keycode: Some(VirtualKeyCode::F12),
+                                    ..
+                                },
+                            ..
+                        },
+                    window_id,
+                } if window_id == self.window.id() => {
+                    if let Err(error) = self
+                        .vulkan_details
+                        .request_screenshot(PathBuf::from("screenshot.png"))
+                    {
+                        log::error!("Unable to take a screenshot: {}", error);
+                    }
+                }
+                Event::WindowEvent {
+                    event: WindowEvent::Resized(size),
+                    window_id,
+                } if window_id == self.window.id() => {
+                    self.vulkan_details.framebuffer_resized = true;
+                    if size.width > 0 && size.height > 0 {
+                        if let Err(error) = self.vulkan_details.draw_frame(&self.window) {
+                            log::error!("Unable to draw a frame: {}", error);
+                            *control_flow = ControlFlow::ExitWithCode(1);
+                        }
+                    }
+                }
+                Event::LoopDestroyed => {
+                    // Even when the device is lost we still want to release everything we created
+                    if let Err(error) = unsafe { self.vulkan_details.device.device_wait_idle() } {
+                        log::error!("Unable to wait for the device to go idle: {}", error);
+                    }
+                    self.vulkan_details.cleanup();
+                    // Destroying objects is validated too, and the loop is gone so exit directly
+                    if let Err(error) = instance::check_validation_error() {
+                        log::error!("{}", error);
+                        std::process::exit(1);
+                    }
+                }
+                _ => {
+                    if self.window.inner_size().width > 0 && self.window.inner_size().height > 0 {
+                        if let Err(error) = self.vulkan_details.draw_frame(&self.window) {
+                            log::error!("Unable to draw a frame: {}", error);
+                            *control_flow = ControlFlow::ExitWithCode(1);
+                        }
+                    }
+                }
+            }
+        });
+    }
+    fn init_window(
+        config: &RendererConfig,
+    ) -> Result<(winit::event_loop::EventLoop<()>, winit::window::Window)> {
+        let event_loop = EventLoop::new();
+        let window = WindowBuilder::new()
+            .with_resizable(true)
+            .with_inner_size(PhysicalSize::new(config.width, config.height))
+            .build(&event_loop)?;
+        Ok((event_loop, window))
+    }
+}
+
+impl HeadlessApplication {
+    pub fn new(config: &RendererConfig) -> Result<Self> {
+        Ok(Self {
+            vulkan_details: VulkanDetails::new_headless(config)?,
+        })
+    }
+    pub fn set_time(&mut self, seconds: f32) {
+        self.vulkan_details.fixed_time = Some(Duration::from_secs_f32(seconds));
+    }
+    pub fn set_objects(&mut self, objects: &[Object]) -> Result<()> {
+        self.vulkan_details.set_objects(objects)
+    }
+    pub fn render_frame(&mut self) -> Result<()> {
+        self.vulkan_details.draw_offscreen_frame()
+    }
+    // Returns the pixels of the last rendered frame, tightly packed as RGBA8
+    pub fn read_frame(&mut self) -> Result<Vec<u8>> {
+        self.vulkan_details.read_offscreen_frame()
+    }
+    pub fn save_frame(&mut self, path: &Path) -> Result<()> {
+        let pixels = self.vulkan_details.read_offscreen_frame()?;
+        capture::write_png(path, self.vulkan_details.swap_chain_extent, &pixels)?;
+        Ok(())
+    }
+}
+
+impl Drop for HeadlessApplication {
+    fn drop(&mut self) {
+        // A lost device can't be waited on, but everything still has to be destroyed
+        unsafe { self.vulkan_details.device.device_wait_idle().ok() };
+        self.vulkan_details.cleanup();
+    }
+}
@@ -0,0 +1,619 @@
+use crate::buffer;
+use crate::error::{Error, Result};
+use crate::memory::{Allocation, MemoryAllocator};
+use crate::mesh::{self, Indices, Mesh};
+use crate::texture::{self, Texture, TextureData, TextureSupport};
+use crate::upload::StagingUploader;
+use crate::vertex::Vertex;
+use ash::vk;
+use base64::Engine;
+use glam::{Mat4, Vec2, Vec3, Vec4};
+use std::collections::HashMap;
+use std::path::Path;
+use std::rc::Rc;
+
+// One indexed draw out of the scene's shared vertex and index buffers
+#[derive(Clone, Copy, Debug)]
+pub struct DrawRecord {
+    pub first_index: u32,
+    pub index_count: u32,
+    // Added to every index, so each primitive's indices start at 0
+    pub vertex_offset: i32,
+    pub material: usize,
+    // Places the primitive in the scene, it goes between the object's placement and the vertices
+    pub transform: Mat4,
+}
+
+// The metallic-roughness material of glTF. Textures are indices into the scene's textures, a
+// material without a base color texture samples the renderer's default texture instead. The
+// renderer only binds the base color so far, the other textures are there for the shaders to come.
+#[derive(Clone, Copy, Debug)]
+pub struct Material {
+    pub base_color_factor: Vec4,
+    pub base_color_texture: Option<usize>,
+    pub metallic_factor: f32,
+    pub roughness_factor: f32,
+    // Metalness in blue, roughness in green
+    pub metallic_roughness_texture: Option<usize>,
+    pub normal_texture: Option<usize>,
+    pub normal_scale: f32,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self {
+            base_color_factor: Vec4::ONE,
+            base_color_texture: None,
+            metallic_factor: 1.0,
+            roughness_factor: 1.0,
+            metallic_roughness_texture: None,
+            normal_texture: None,
+            normal_scale: 1.0,
+        }
+    }
+}
+
+// Decoded texels, with TEXTURE_FORMAT for color or LINEAR_TEXTURE_FORMAT for data
+pub struct SceneTexture {
+    pub data: TextureData,
+    pub format: vk::Format,
+}
+
+// Everything a scene file holds, read into memory but not on the GPU yet
+pub struct SceneData {
+    pub mesh: Mesh,
+    pub textures: Vec<SceneTexture>,
+    pub materials: Vec<Material>,
+    pub draws: Vec<DrawRecord>,
+}
+
+impl SceneData {
+    // A single draw of the whole mesh with the default material
+    pub fn from_mesh(mesh: Mesh) -> Self {
+        let draw = DrawRecord {
+            first_index: 0,
+            index_count: mesh.indices.len() as u32,
+            vertex_offset: 0,
+            material: 0,
+            transform: Mat4::IDENTITY,
+        };
+        Self {
+            mesh,
+            textures: Vec::new(),
+            materials: vec![Material::default()],
+            draws: vec![draw],
+        }
+    }
+}
+
+// glTF and GLB files are imported as scenes, anything else is read as an OBJ mesh
+pub fn load_scene(path: &Path) -> Result<SceneData> {
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase());
+    match extension.as_deref() {
+        Some("gltf") | Some("glb") => load_gltf(path),
+        _ => Ok(SceneData::from_mesh(mesh::load_obj(path)?)),
+    }
+}
+
+// Reads the default scene of a glTF 2.0 file, or its first scene when there's no default. Every
+// triangle primitive becomes a draw record for each node it's placed by, other primitives are
+// skipped. Samplers are ignored, textures are always filtered linearly and repeated.
+pub fn load_gltf(path: &Path) -> Result<SceneData> {
+    let load_error = |reason: String| Error::MeshLoad {
+        path: path.to_path_buf(),
+        reason,
+    };
+    let bytes = std::fs::read(path).map_err(|error| load_error(error.to_string()))?;
+    // Handles both the JSON and the binary container
+    let gltf = gltf::Gltf::from_slice(&bytes).map_err(|error| load_error(error.to_string()))?;
+    let base_dir = path.parent().unwrap_or_else(|| Path::new(""));
+    let buffers = gltf
+        .buffers()
+        .map(|buffer| match buffer.source() {
+            gltf::buffer::Source::Bin => gltf
+                .blob
+                .clone()
+                .ok_or_else(|| "the BIN chunk is missing".to_string()),
+            gltf::buffer::Source::Uri(uri) => read_uri(base_dir, uri),
+        })
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(load_error)?;
+
+    let mut importer = GltfImporter {
+        base_dir,
+        buffers: &buffers,
+        vertices: Vec::new(),
+        indices: Vec::new(),
+        textures: Vec::new(),
+        texture_indices: HashMap::new(),
+    };
+    let mut materials = Vec::new();
+    for material in gltf.materials() {
+        materials.push(importer.material(&material).map_err(load_error)?);
+    }
+    // Primitives without a material of their own use the default one at the end
+    let default_material = materials.len();
+    materials.push(Material::default());
+
+    // Meshes can be placed by more than one node, their geometry is only stored once
+    let mut mesh_draws = Vec::new();
+    for gltf_mesh in gltf.meshes() {
+        let mut draws = Vec::new();
+        for primitive in gltf_mesh.primitives() {
+            if primitive.mode() != gltf::mesh::Mode::Triangles {
+                log::warn!(
+                    "Skipping a primitive of mesh {} drawn as {:?}, only triangles are supported",
+                    gltf_mesh.index(),
+                    primitive.mode()
+                );
+                continue;
+            }
+            let mut draw = importer.primitive(&primitive).map_err(load_error)?;
+            draw.material = primitive.material().index().unwrap_or(default_material);
+            draws.push(draw);
+        }
+        mesh_draws.push(draws);
+    }
+
+    let scene = gltf
+        .default_scene()
+        .or_else(|| gltf.scenes().next())
+        .ok_or_else(|| load_error("the file has no scenes".to_string()))?;
+    let mut draws = Vec::new();
+    for node in scene.nodes() {
+        place_node(&node, Mat4::IDENTITY, &mesh_draws, &mut draws);
+    }
+    if draws.is_empty() {
+        return Err(load_error("the scene has no triangles".to_string()));
+    }
+    Ok(SceneData {
+        mesh: Mesh {
+            vertices: importer.vertices,
+            indices: Indices::new(importer.indices),
+        },
+        textures: importer.textures,
+        materials,
+        draws,
+    })
+}
+
+// Adds the draws of the node's mesh and of all its children, each with the transform from the root
+fn place_node(
+    node: &gltf::Node,
+    parent_transform: Mat4,
+    mesh_draws: &[Vec<DrawRecord>],
+    draws: &mut Vec<DrawRecord>,
+) {
+    let transform = parent_transform * Mat4::from_cols_array_2d(&node.transform().matrix());
+    if let Some(mesh) = node.mesh() {
+        draws.extend(
+            mesh_draws[mesh.index()]
+                .iter()
+                .map(|draw| DrawRecord { transform, ..*draw }),
+        );
+    }
+    for child in node.children() {
+        place_node(&child, transform, mesh_draws, draws);
+    }
+}
+
+// Buffers and images are either embedded as base64 data URIs or files next to the glTF file
+fn read_uri(base_dir: &Path, uri: &str) -> std::result::Result<Vec<u8>, String> {
+    if let Some(data) = uri.strip_prefix("data:") {
+        let (_, base64) = data
+            .split_once(";base64,")
+            .ok_or_else(|| "only base64 data URIs are supported".to_string())?;
+        base64::engine::general_purpose::STANDARD
+            .decode(base64)
+            .map_err(|error| error.to_string())
+    } else {
+        std::fs::read(base_dir.join(uri)).map_err(|error| format!("{}: {}", uri, error))
+    }
+}
+
+// Collects the geometry of every primitive and the textures the materials use
+struct GltfImporter<'a> {
+    base_dir: &'a Path,
+    buffers: &'a [Vec<u8>],
+    vertices: Vec<Vertex>,
+    indices: Vec<u32>,
+    textures: Vec<SceneTexture>,
+    // A glTF image can be used as color and as data, which are different textures
+    texture_indices: HashMap<(usize, vk::Format), usize>,
+}
+
+impl GltfImporter<'_> {
+    fn material(&mut self, material: &gltf::Material) -> std::result::Result<Material, String> {
+        let pbr = material.pbr_metallic_roughness();
+        let base_color_texture = match pbr.base_color_texture() {
+            Some(info) => Some(self.texture(&info.texture(), texture::TEXTURE_FORMAT)?),
+            None => None,
+        };
+        let metallic_roughness_texture = match pbr.metallic_roughness_texture() {
+            Some(info) => Some(self.texture(&info.texture(), texture::LINEAR_TEXTURE_FORMAT)?),
+            None => None,
+        };
+        let normal_texture = match material.normal_texture() {
+            Some(normal) => Some(self.texture(&normal.texture(), texture::LINEAR_TEXTURE_FORMAT)?),
+            None => None,
+        };
+        Ok(Material {
+            base_color_factor: Vec4::from(pbr.base_color_factor()),
+            base_color_texture,
+            metallic_factor: pbr.metallic_factor(),
+            roughness_factor: pbr.roughness_factor(),
+            metallic_roughness_texture,
+            normal_texture,
+            normal_scale: material
+                .normal_texture()
+                .map_or(1.0, |normal| normal.scale()),
+        })
+    }
+
+    fn texture(
+        &mut self,
+        texture: &gltf::Texture,
+        format: vk::Format,
+    ) -> std::result::Result<usize, String> {
+        let image = texture.source();
+        if let Some(&index) = self.texture_indices.get(&(image.index(), format)) {
+            return Ok(index);
+        }
+        let bytes = match image.source() {
+            gltf::image::Source::View { view, .. } => {
+                let buffer = &self.buffers[view.buffer().index()];
+                buffer
+                    .get(view.offset()..view.offset() + view.length())
+                    .ok_or_else(|| format!("image {} is outside of its buffer", image.index()))?
+                    .to_vec()
+            }
+            gltf::image::Source::Uri { uri, .. } => read_uri(self.base_dir, uri)?,
+        };
+        let data = texture::decode_rgba(&bytes)
+            .map_err(|reason| format!("image {}: {}", image.index(), reason))?;
+        self.textures.push(SceneTexture { data, format });
+        self.texture_indices
+            .insert((image.index(), format), self.textures.len() - 1);
+        Ok(self.textures.len() - 1)
+    }
+
+    // Appends the primitive's vertices and indices, the material is left for the caller
+    fn primitive(
+        &mut self,
+        primitive: &gltf::Primitive,
+    ) -> std::result::Result<DrawRecord, String> {
+        let reader = primitive.reader(|buffer| self.buffers.get(buffer.index()).map(Vec::as_slice));
+        let positions: Vec<Vec3> = reader
+            .read_positions()
+            .ok_or_else(|| format!("primitive {} has no positions", primitive.index()))?
+            .map(Vec3::from)
+            .collect();
+        let normals: Vec<Vec3> = reader
+            .read_normals()
+            .map_or_else(Vec::new, |normals| normals.map(Vec3::from).collect());
+        let tex_coords: Vec<Vec2> = reader
+            .read_tex_coords(0)
+            .map_or_else(Vec::new, |tex_coords| {
+                tex_coords.into_f32().map(Vec2::from).collect()
+            });
+        let colors: Vec<Vec3> = reader.read_colors(0).map_or_else(Vec::new, |colors| {
+            colors.into_rgb_f32().map(Vec3::from).collect()
+        });
+        let vertex_offset = self.vertices.len() as i32;
+        let first_index = self.indices.len() as u32;
+        for (index, &pos) in positions.iter().enumerate() {
+            self.vertices.push(Vertex {
+                pos,
+                color: colors.get(index).copied().unwrap_or(Vec3::ONE),
+                tex_coord: tex_coords.get(index).copied().unwrap_or(Vec2::ZERO),
+                normal: normals.get(index).copied().unwrap_or(Vec3::ZERO),
+            });
+        }
+        // Primitives without indices draw their vertices in order
+        match reader.read_indices() {
+            Some(indices) => self.indices.extend(indices.into_u32()),
+            None => self.indices.extend(0..positions.len() as u32),
+        }
+        Ok(DrawRecord {
+            first_index,
+            index_count: self.indices.len() as u32 - first_index,
+            vertex_offset,
+            material: 0,
+            transform: Mat4::IDENTITY,
+        })
+    }
+}
+
+// A scene's geometry and textures on the GPU, with the draw records that render it
+pub struct Scene {
+    device: ash::Device,
+    allocator: Rc<MemoryAllocator>,
+    vertex_buffer: vk::Buffer,
+    vertex_buffer_memory: Allocation,
+    index_buffer: vk::Buffer,
+    index_buffer_memory: Allocation,
+    index_type: vk::IndexType,
+    textures: Vec<Texture>,
+    materials: Vec<Material>,
+    draws: Vec<DrawRecord>,
+}
+
+impl Scene {
+    // Records the uploads of the buffers and textures, the scene can be drawn by anything
+    // submitted after their batch
+    pub fn new(
+        device: &ash::Device,
+        allocator: Rc<MemoryAllocator>,
+        uploader: &mut StagingUploader,
+        data: &SceneData,
+        support: &TextureSupport,
+    ) -> Result<Self> {
+        let (vertex_buffer, vertex_buffer_memory, _) =
+            buffer::create_vertex_buffer(device, &allocator, uploader, &data.mesh.vertices)?;
+        let index_buffer = match &data.mesh.indices {
+            Indices::U16(indices) => {
+                buffer::create_index_buffer(device, &allocator, uploader, indices)
+            }
+            Indices::U32(indices) => {
+                buffer::create_index_buffer(device, &allocator, uploader, indices)
+            }
+        };
+        let (index_buffer, index_buffer_memory, _) = match index_buffer {
+            Ok(index_buffer) => index_buffer,
+            Err(error) => {
+                // The vertex upload is recorded already, it has to finish before the buffer goes
+                uploader.wait_idle()?;
+                buffer::destroy_buffer(device, &allocator, vertex_buffer, &vertex_buffer_memory);
+                return Err(error);
+            }
+        };
+        let mut scene = Self {
+            device: device.clone(),
+            allocator,
+            vertex_buffer,
+            vertex_buffer_memory,
+            index_buffer,
+            index_buffer_memory,
+            index_type: data.mesh.indices.index_type(),
+            textures: Vec::new(),
+            materials: data.materials.clone(),
+            draws: data.draws.clone(),
+        };
+        for scene_texture in &data.textures {
+            match Texture::with_format(
+                device,
+                scene.allocator.clone(),
+                uploader,
+                &scene_texture.data,
+                scene_texture.format,
+                support,
+            ) {
+                Ok((texture, _)) => scene.textures.push(texture),
+                Err(error) => {
+                    // Nothing draws the scene yet, but the recorded uploads still write to it
+                    uploader.wait_idle()?;
+                    scene.destroy();
+                    return Err(error);
+                }
+            }
+        }
+        Ok(scene)
+    }
+
+    pub fn draws(&self) -> &[DrawRecord] {
+        self.draws.as_slice()
+    }
+
+    pub fn materials(&self) -> &[Material] {
+        self.materials.as_slice()
+    }
+
+    // The base color texture of the material, or default_texture when it has none
+    pub fn base_color_image_info(
+        &self,
+        material: usize,
+        default_texture: &Texture,
+    ) -> vk::DescriptorImageInfo {
+        match self.materials[material].base_color_texture {
+            Some(texture) => self.textures[texture].descriptor_image_info(),
+            None => default_texture.descriptor_image_info(),
+        }
+    }
+
+    pub fn cmd_bind_buffers(&self, command_buffer: vk::CommandBuffer) {
+        unsafe {
+            self.device
+                .cmd_bind_vertex_buffers(command_buffer, 0, &[self.vertex_buffer], &[0]);
+            self.device.cmd_bind_index_buffer(
+                command_buffer,
+                self.index_buffer,
+                0,
+                self.index_type,
+            );
+        }
+    }
+
+    pub fn cmd_draw(&self, command_buffer: vk::CommandBuffer, draw: &DrawRecord) {
+        unsafe {
+            self.device.cmd_draw_indexed(
+                command_buffer,
+                draw.index_count,
+                1,
+                draw.first_index,
+                draw.vertex_offset,
+                0,
+            );
+        }
+    }
+
+    // The GPU must be done with the scene
+    pub fn destroy(&self) {
+        for texture in &self.textures {
+            texture.destroy();
+        }
+        buffer::destroy_buffer(
+            &self.device,
+            &self.allocator,
+            self.index_buffer,
+            &self.index_buffer_memory,
+        );
+        buffer::destroy_buffer(
+            &self.device,
+            &self.allocator,
+            self.vertex_buffer,
+            &self.vertex_buffer_memory,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // One mesh with two primitives, placed by a child node and by a second root node. extra holds
+    // more top level members, each followed by a comma.
+    fn write_gltf(name: &str, extra: &str) -> std::path::PathBuf {
+        let mut bytes = Vec::new();
+        for value in [
+            0.0f32, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, // first primitive
+            0.0, 0.0, 1.0, 1.0, 0.0, 1.0, 0.0, 1.0, 1.0, // second primitive
+        ] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        for index in [2u16, 1, 0] {
+            bytes.extend_from_slice(&index.to_le_bytes());
+        }
+        let json = format!(
+            r#"{{
+                {extra}
+                "asset": {{ "version": "2.0" }},
+                "buffers": [{{ "byteLength": {length}, "uri": "data:application/octet-stream;base64,{data}" }}],
+                "bufferViews": [
+                    {{ "buffer": 0, "byteOffset": 0, "byteLength": 72 }},
+                    {{ "buffer": 0, "byteOffset": 72, "byteLength": 6 }}
+                ],
+                "accessors": [
+                    {{ "bufferView": 0, "byteOffset": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+                       "min": [0, 0, 0], "max": [1, 1, 0] }},
+                    {{ "bufferView": 0, "byteOffset": 36, "componentType": 5126, "count": 3, "type": "VEC3",
+                       "min": [0, 0, 1], "max": [1, 1, 1] }},
+                    {{ "bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR" }}
+                ],
+                "meshes": [{{ "primitives": [
+                    {{ "attributes": {{ "POSITION": 0 }}, "indices": 2 }},
+                    {{ "attributes": {{ "POSITION": 1 }} }}
+                ] }}],
+                "nodes": [
+                    {{ "translation": [1, 0, 0], "children": [1] }},
+                    {{ "scale": [2, 2, 2], "mesh": 0 }},
+                    {{ "translation": [0, 0, 5], "mesh": 0 }}
+                ],
+                "scenes": [{{ "nodes": [0, 2] }}],
+                "scene": 0
+            }}"#,
+            extra = extra,
+            length = bytes.len(),
+            data = base64::engine::general_purpose::STANDARD.encode(&bytes),
+        );
+        let path =
+            std::env::temp_dir().join(format!("vulkanrust-{}-{}.gltf", name, std::process::id()));
+        std::fs::write(&path, json).unwrap();
+        path
+    }
+
+    #[test]
+    fn node_transforms_compose_from_the_root() {
+        let path = write_gltf("transforms", "");
+        let scene = load_gltf(&path);
+        std::fs::remove_file(&path).unwrap();
+        let scene = scene.unwrap();
+        // Every primitive of the mesh is drawn once per node placing it
+        assert_eq!(scene.draws.len(), 4);
+        let child = Mat4::from_translation(Vec3::X) * Mat4::from_scale(Vec3::splat(2.0));
+        let root = Mat4::from_translation(Vec3::new(0.0, 0.0, 5.0));
+        for draw in &scene.draws[..2] {
+            assert_eq!(draw.transform, child);
+        }
+        for draw in &scene.draws[2..] {
+            assert_eq!(draw.transform, root);
+        }
+        // The parent's transform applies after the child's
+        assert_eq!(child.transform_point3(Vec3::ONE), Vec3::new(3.0, 2.0, 2.0));
+    }
+
+    #[test]
+    fn primitives_share_the_buffers() {
+        let path = write_gltf("offsets", "");
+        let scene = load_gltf(&path);
+        std::fs::remove_file(&path).unwrap();
+        let scene = scene.unwrap();
+        // The geometry is stored once, however many nodes place the mesh
+        assert_eq!(scene.mesh.vertices.len(), 6);
+        assert_eq!(scene.mesh.indices.len(), 6);
+        let ranges: Vec<_> = scene
+            .draws
+            .iter()
+            .map(|draw| (draw.first_index, draw.index_count, draw.vertex_offset))
+            .collect();
+        assert_eq!(ranges, [(0, 3, 0), (3, 3, 3), (0, 3, 0), (3, 3, 3)]);
+        // Indices stay relative to their primitive, the second one without indices counts from 0
+        match &scene.mesh.indices {
+            Indices::U16(indices) => assert_eq!(indices, &[2, 1, 0, 0, 1, 2]),
+            Indices::U32(_) => panic!("six vertices fit in 16 bit indices"),
+        }
+        assert_eq!(scene.mesh.vertices[3].pos, Vec3::Z);
+        // Both primitives fall back to the default material at the end
+        assert_eq!(scene.materials.len(), 1);
+        assert!(scene.draws.iter().all(|draw| draw.material == 0));
+    }
+
+    #[test]
+    fn data_textures_are_linear() {
+        let mut png_bytes = Vec::new();
+        let mut encoder = png::Encoder::new(&mut png_bytes, 1, 1);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(&[128, 128, 255, 255]).unwrap();
+        drop(writer);
+        // The one image is the base color, the metallic-roughness and the normal texture
+        let extra = format!(
+            r#""images": [{{ "uri": "data:image/png;base64,{}" }}],
+            "textures": [{{ "source": 0 }}],
+            "materials": [{{
+                "pbrMetallicRoughness": {{
+                    "baseColorTexture": {{ "index": 0 }},
+                    "metallicRoughnessTexture": {{ "index": 0 }},
+                    "metallicFactor": 0.25
+                }},
+                "normalTexture": {{ "index": 0, "scale": 0.5 }}
+            }}],"#,
+            base64::engine::general_purpose::STANDARD.encode(&png_bytes)
+        );
+        let path = write_gltf("materials", &extra);
+        let scene = load_gltf(&path);
+        std::fs::remove_file(&path).unwrap();
+        let scene = scene.unwrap();
+
+        // Color and data need their own textures, the two data uses share one
+        let formats: Vec<_> = scene
+            .textures
+            .iter()
+            .map(|texture| texture.format)
+            .collect();
+        assert_eq!(
+            formats,
+            [texture::TEXTURE_FORMAT, texture::LINEAR_TEXTURE_FORMAT]
+        );
+        let material = scene.materials[0];
+        assert_eq!(material.base_color_texture, Some(0));
+        assert_eq!(material.metallic_roughness_texture, Some(1));
+        assert_eq!(material.normal_texture, Some(1));
+        assert_eq!(material.normal_scale, 0.5);
+        assert_eq!(material.metallic_factor, 0.25);
+        // Followed by the default material
+        assert_eq!(scene.materials.len(), 2);
+    }
+}
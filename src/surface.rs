@@ -0,0 +1,67 @@
+use crate::error::{Error, Result};
+use ash::extensions::khr::{AndroidSurface, WaylandSurface, Win32Surface, XcbSurface, XlibSurface};
+use ash::vk;
+use raw_window_handle::{
+    HasRawDisplayHandle, HasRawWindowHandle, RawDisplayHandle, RawWindowHandle,
+};
+
+pub fn create_surface(
+    window: &winit::window::Window,
+    entry: &ash::Entry,
+    instance: &ash::Instance,
+) -> Result<vk::SurfaceKHR> {
+    // On Linux the window handle alone isn't enough, the surface also needs the connection to the display server
+    let surface = match (window.raw_display_handle(), window.raw_window_handle()) {
+        (_, RawWindowHandle::AndroidNdk(handle)) => {
+            let surface_create_info = vk::AndroidSurfaceCreateInfoKHR {
+                s_type: vk::StructureType::ANDROID_SURFACE_CREATE_INFO_KHR,
+                window: handle.a_native_window,
+                ..Default::default()
+            };
+            let android_surface = AndroidSurface::new(entry, instance);
+            unsafe { android_surface.create_android_surface(&surface_create_info, None) }
+        }
+        (_, RawWindowHandle::Win32(handle)) => {
+            let surface_create_info = vk::Win32SurfaceCreateInfoKHR {
+                s_type: vk::StructureType::WIN32_SURFACE_CREATE_INFO_KHR,
+                hwnd: handle.hwnd,
+                hinstance: handle.hinstance,
+                ..Default::default()
+            };
+            let win32_surface = Win32Surface::new(entry, instance);
+            unsafe { win32_surface.create_win32_surface(&surface_create_info, None) }
+        }
+        (RawDisplayHandle::Wayland(display), RawWindowHandle::Wayland(handle)) => {
+            let surface_create_info = vk::WaylandSurfaceCreateInfoKHR {
+                s_type: vk::StructureType::WAYLAND_SURFACE_CREATE_INFO_KHR,
+                display: display.display,
+                surface: handle.surface,
+                ..Default::default()
+            };
+            let wayland_surface = WaylandSurface::new(entry, instance);
+            unsafe { wayland_surface.create_wayland_surface(&surface_create_info, None) }
+        }
+        (RawDisplayHandle::Xcb(display), RawWindowHandle::Xcb(handle)) => {
+            let surface_create_info = vk::XcbSurfaceCreateInfoKHR {
+                s_type: vk::StructureType::XCB_SURFACE_CREATE_INFO_KHR,
+                connection: display.connection,
+                window: handle.window,
+                ..Default::default()
+            };
+            let xcb_surface = XcbSurface::new(entry, instance);
+            unsafe { xcb_surface.create_xcb_surface(&surface_create_info, None) }
+        }
+        (RawDisplayHandle::Xlib(display), RawWindowHandle::Xlib(handle)) => {
+            let surface_create_info = vk::XlibSurfaceCreateInfoKHR {
+                s_type: vk::StructureType::XLIB_SURFACE_CREATE_INFO_KHR,
+                dpy: display.display as *mut vk::Display,
+                window: handle.window,
+                ..Default::default()
+            };
+            let xlib_surface = XlibSurface::new(entry, instance);
+            unsafe { xlib_surface.create_xlib_surface(&surface_create_info, None) }
+        }
+        _ => return Err(Error::UnsupportedWindowHandle),
+    };
+    Ok(surface?)
+}
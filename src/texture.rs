@@ -0,0 +1,812 @@
+use crate::commands;
+use crate::compressed::{self, CompressedTexture};
+use crate::device;
+use crate::error::{Error, Result};
+use crate::image::{self, ImageInfo};
+use crate::memory::{Allocation, MemoryAllocator, MemoryRequest};
+use crate::upload::{ImageUpload, StagingUploader, UploadHandle};
+use ash::vk;
+use std::path::Path;
+use std::rc::Rc;
+
+// Textures hold color, so they are sampled as sRGB and come out linear in the shader
+pub const TEXTURE_FORMAT: vk::Format = vk::Format::R8G8B8A8_SRGB;
+// Normal and metallic-roughness maps hold data rather than color, so they are sampled as they are
+pub const LINEAR_TEXTURE_FORMAT: vk::Format = vk::Format::R8G8B8A8_UNORM;
+
+// Decoded texels, four bytes each in RGBA order, rows top to bottom
+pub struct TextureData {
+    pub width: u32,
+    pub height: u32,
+    pub rgba: Vec<u8>,
+}
+
+impl TextureData {
+    // A single texel, for when there is nothing to load
+    pub fn solid(rgba: [u8; 4]) -> Self {
+        Self {
+            width: 1,
+            height: 1,
+            rgba: rgba.to_vec(),
+        }
+    }
+}
+
+// Decodes a PNG or JPEG file, telling them apart by their first bytes rather than the extension
+pub fn load_rgba(path: &Path) -> Result<TextureData> {
+    let load_error = |reason: String| Error::TextureLoad {
+        path: path.to_path_buf(),
+        reason,
+    };
+    let bytes = std::fs::read(path).map_err(|error| load_error(error.to_string()))?;
+    decode_rgba(&bytes).map_err(load_error)
+}
+
+// Decodes PNG or JPEG file contents that were read some other way, such as from inside a glTF file
+pub fn decode_rgba(bytes: &[u8]) -> std::result::Result<TextureData, String> {
+    if bytes.starts_with(b"\x89PNG") {
+        decode_png(bytes).map_err(|error| error.to_string())
+    } else if bytes.starts_with(&[0xff, 0xd8, 0xff]) {
+        decode_jpeg(bytes)
+    } else {
+        Err("not a PNG or JPEG file".to_string())
+    }
+}
+
+fn decode_png(bytes: &[u8]) -> std::result::Result<TextureData, png::DecodingError> {
+    let mut decoder = png::Decoder::new(bytes);
+    // Palettes and bit depths other than 8 are expanded, leaving only the color types below
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info()?;
+    let mut texels = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut texels)?;
+    texels.truncate(info.buffer_size());
+    let rgba = match info.color_type {
+        png::ColorType::Rgba => texels,
+        png::ColorType::Rgb => texels
+            .chunks_exact(3)
+            .flat_map(|rgb| [rgb[0], rgb[1], rgb[2], 255])
+            .collect(),
+        png::ColorType::GrayscaleAlpha => texels
+            .chunks_exact(2)
+            .flat_map(|la| [la[0], la[0], la[0], la[1]])
+            .collect(),
+        png::ColorType::Grayscale | png::ColorType::Indexed => {
+            texels.iter().flat_map(|&l| [l, l, l, 255]).collect()
+        }
+    };
+    Ok(TextureData {
+        width: info.width,
+        height: info.height,
+        rgba,
+    })
+}
+
+fn decode_jpeg(bytes: &[u8]) -> std::result::Result<TextureData, String> {
+    let mut decoder = jpeg_decoder::Decoder::new(bytes);
+    let texels = decoder.decode().map_err(|error| error.to_string())?;
+    let info = decoder.info().ok_or("missing image header")?;
+    let rgba = match info.pixel_format {
+        jpeg_decoder::PixelFormat::RGB24 => texels
+            .chunks_exact(3)
+            .flat_map(|rgb| [rgb[0], rgb[1], rgb[2], 255])
+            .collect(),
+        jpeg_decoder::PixelFormat::L8 => texels.iter().flat_map(|&l| [l, l, l, 255]).collect(),
+        // Big endian, so the high byte comes first
+        jpeg_decoder::PixelFormat::L16 => texels
+            .chunks_exact(2)
+            .flat_map(|l| [l[0], l[0], l[0], 255])
+            .collect(),
+        jpeg_decoder::PixelFormat::CMYK32 => texels
+            .chunks_exact(4)
+            .flat_map(|cmyk| {
+                let k = 255 - cmyk[3] as u32;
+                let channel = |c: u8| ((255 - c as u32) * k / 255) as u8;
+                [channel(cmyk[0]), channel(cmyk[1]), channel(cmyk[2]), 255]
+            })
+            .collect(),
+    };
+    Ok(TextureData {
+        width: info.width as u32,
+        height: info.height as u32,
+        rgba,
+    })
+}
+
+// Enough levels to halve the larger side down to a single texel
+pub fn mip_level_count(width: u32, height: u32) -> u32 {
+    u32::BITS - width.max(height).max(1).leading_zeros()
+}
+
+// Halves both sides, averaging each 2x2 block. Color channels that are sRGB are averaged as linear
+// values and converted back.
+fn downsample(level: &TextureData, srgb: bool) -> TextureData {
+    let width = (level.width / 2).max(1);
+    let height = (level.height / 2).max(1);
+    let mut rgba = Vec::with_capacity((width * height * 4) as usize);
+    for y in 0..height {
+        for x in 0..width {
+            for channel in 0..4 {
+                let sum: f32 = [(0, 0), (1, 0), (0, 1), (1, 1)]
+                    .iter()
+                    .map(|(dx, dy)| {
+                        // Sides of one texel are repeated rather than read past
+                        let source_x = (x * 2 + dx).min(level.width - 1);
+                        let source_y = (y * 2 + dy).min(level.height - 1);
+                        let value = level.rgba
+                            [((source_y * level.width + source_x) * 4 + channel) as usize];
+                        if channel == 3 || !srgb {
+                            value as f32 / 255.0
+                        } else {
+                            srgb_to_linear(value)
+                        }
+                    })
+                    .sum();
+                let average = sum / 4.0;
+                rgba.push(if channel == 3 || !srgb {
+                    (average * 255.0).round() as u8
+                } else {
+                    linear_to_srgb(average)
+                });
+            }
+        }
+    }
+    TextureData {
+        width,
+        height,
+        rgba,
+    }
+}
+
+fn srgb_to_linear(value: u8) -> f32 {
+    let value = value as f32 / 255.0;
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(value: f32) -> u8 {
+    let value = if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    };
+    (value * 255.0).round() as u8
+}
+
+fn color_levels(base_mip_level: u32, level_count: u32) -> vk::ImageSubresourceRange {
+    vk::ImageSubresourceRange {
+        aspect_mask: vk::ImageAspectFlags::COLOR,
+        base_mip_level,
+        level_count,
+        base_array_layer: 0,
+        layer_count: 1,
+    }
+}
+
+fn color_level(mip_level: u32) -> vk::ImageSubresourceLayers {
+    vk::ImageSubresourceLayers {
+        aspect_mask: vk::ImageAspectFlags::COLOR,
+        mip_level,
+        base_array_layer: 0,
+        layer_count: 1,
+    }
+}
+
+// Copies a whole, tightly packed level that starts at buffer_offset
+fn level_copy(
+    mip_level: u32,
+    buffer_offset: vk::DeviceSize,
+    level: &TextureData,
+) -> vk::BufferImageCopy {
+    vk::BufferImageCopy {
+        buffer_offset,
+        buffer_row_length: 0,
+        buffer_image_height: 0,
+        image_subresource: color_level(mip_level),
+        image_offset: vk::Offset3D { x: 0, y: 0, z: 0 },
+        image_extent: vk::Extent3D {
+            width: level.width,
+            height: level.height,
+            depth: 1,
+        },
+    }
+}
+
+// What the device offers textures, looked up once and shared by all of them
+#[derive(Clone, Debug)]
+pub struct TextureSupport {
+    pub max_anisotropy: Option<f32>,
+    // Mipmaps are blitted on the GPU when both formats allow it, and downsampled on the CPU otherwise
+    pub linear_blit: bool,
+    // The block compressed formats that can be sampled with linear filtering
+    pub compressed_formats: Vec<vk::Format>,
+    // Files with more than one cube map can only be viewed as a cube array with this
+    pub cube_arrays: bool,
+    pub max_image_dimension_2d: u32,
+    pub max_image_dimension_cube: u32,
+    pub max_image_array_layers: u32,
+}
+
+impl TextureSupport {
+    pub fn query(instance: &ash::Instance, physical_device: &vk::PhysicalDevice) -> Self {
+        let compressed_formats = compressed::BLOCK_COMPRESSED_FORMATS
+            .map(vk::Format::from_raw)
+            .filter(|format| {
+                image::find_supported_format(
+                    instance,
+                    physical_device,
+                    &[*format],
+                    vk::ImageTiling::OPTIMAL,
+                    vk::FormatFeatureFlags::SAMPLED_IMAGE
+                        | vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR,
+                )
+                .is_ok()
+            })
+            .collect();
+        let limits = unsafe { instance.get_physical_device_properties(*physical_device) }.limits;
+        Self {
+            max_anisotropy: device::max_sampler_anisotropy(instance, physical_device),
+            linear_blit: image::supports_linear_blit(instance, physical_device, TEXTURE_FORMAT)
+                && image::supports_linear_blit(instance, physical_device, LINEAR_TEXTURE_FORMAT),
+            compressed_formats,
+            cube_arrays: device::supports_image_cube_array(instance, physical_device),
+            max_image_dimension_2d: limits.max_image_dimension2_d,
+            max_image_dimension_cube: limits.max_image_dimension_cube,
+            max_image_array_layers: limits.max_image_array_layers,
+        }
+    }
+
+    // Files are checked before their image is created, so a texture the device can't hold is an
+    // error that names the reason
+    pub fn check_compressed(&self, compressed: &CompressedTexture) -> Result<()> {
+        let unsupported = |reason: String| Err(Error::UnsupportedFormat(reason));
+        if !self.compressed_formats.contains(&compressed.format) {
+            return unsupported(format!(
+                "{:?} can't be sampled by this device",
+                compressed.format
+            ));
+        }
+        if compressed.cube && compressed.array_layers > 6 && !self.cube_arrays {
+            return unsupported("Cube map arrays can't be sampled by this device".to_string());
+        }
+        let max_dimension = if compressed.cube {
+            self.max_image_dimension_cube
+        } else {
+            self.max_image_dimension_2d
+        };
+        if compressed.width.max(compressed.height) > max_dimension {
+            return unsupported(format!(
+                "{}x{} is larger than the {} texels this device allows",
+                compressed.width, compressed.height, max_dimension
+            ));
+        }
+        if compressed.array_layers > self.max_image_array_layers {
+            return unsupported(format!(
+                "{} array layers are more than the {} this device allows",
+                compressed.array_layers, self.max_image_array_layers
+            ));
+        }
+        if compressed.mip_levels > mip_level_count(compressed.width, compressed.height) {
+            return unsupported(format!(
+                "{} mip levels are too many for {}x{}",
+                compressed.mip_levels, compressed.width, compressed.height
+            ));
+        }
+        Ok(())
+    }
+}
+
+// Linear filtering between texels and mip levels, repeating addressing, and anisotropic when
+// max_anisotropy is given. Every one of the mip_levels can be sampled.
+pub fn create_sampler(
+    device: &ash::Device,
+    max_anisotropy: Option<f32>,
+    mip_levels: u32,
+) -> Result<vk::Sampler> {
+    let sampler_info = vk::SamplerCreateInfo {
+        s_type: vk::StructureType::SAMPLER_CREATE_INFO,
+        mag_filter: vk::Filter::LINEAR,
+        min_filter: vk::Filter::LINEAR,
+        mipmap_mode: vk::SamplerMipmapMode::LINEAR,
+        address_mode_u: vk::SamplerAddressMode::REPEAT,
+        address_mode_v: vk::SamplerAddressMode::REPEAT,
+        address_mode_w: vk::SamplerAddressMode::REPEAT,
+        mip_lod_bias: 0.0,
+        anisotropy_enable: max_anisotropy.is_some() as vk::Bool32,
+        max_anisotropy: max_anisotropy.unwrap_or(1.0),
+        compare_enable: vk::FALSE,
+        compare_op: vk::CompareOp::ALWAYS,
+        min_lod: 0.0,
+        max_lod: mip_levels as f32,
+        border_color: vk::BorderColor::INT_OPAQUE_BLACK,
+        unnormalized_coordinates: vk::FALSE,
+        ..Default::default()
+    };
+    Ok(unsafe { device.create_sampler(&sampler_info, None)? })
+}
+
+// A sampled image with a full mip chain, its view and sampler, ready for a COMBINED_IMAGE_SAMPLER
+// descriptor
+pub struct Texture {
+    device: ash::Device,
+    allocator: Rc<MemoryAllocator>,
+    image: vk::Image,
+    allocation: Allocation,
+    view: vk::ImageView,
+    sampler: vk::Sampler,
+    view_type: vk::ImageViewType,
+}
+
+impl Texture {
+    // Records the upload of data, the texture can be sampled by anything submitted after the upload's
+    // batch. Mipmaps blitted on the GPU are recorded into the same batch, after level 0 is copied.
+    pub fn new(
+        device: &ash::Device,
+        allocator: Rc<MemoryAllocator>,
+        uploader: &mut StagingUploader,
+        data: &TextureData,
+        support: &TextureSupport,
+    ) -> Result<(Self, UploadHandle)> {
+        Self::with_format(device, allocator, uploader, data, TEXTURE_FORMAT, support)
+    }
+
+    // Like new, but format says whether the texels are sRGB color, TEXTURE_FORMAT, or data,
+    // LINEAR_TEXTURE_FORMAT
+    pub fn with_format(
+        device: &ash::Device,
+        allocator: Rc<MemoryAllocator>,
+        uploader: &mut StagingUploader,
+        data: &TextureData,
+        format: vk::Format,
+        support: &TextureSupport,
+    ) -> Result<(Self, UploadHandle)> {
+        let mip_levels = mip_level_count(data.width, data.height);
+        let blit_mipmaps = support.linear_blit && mip_levels > 1;
+        let texture = Self::create(
+            device,
+            allocator,
+            &ImageInfo {
+                extent: vk::Extent2D {
+                    width: data.width,
+                    height: data.height,
+                },
+                format,
+                tiling: vk::ImageTiling::OPTIMAL,
+                usage: if blit_mipmaps {
+                    vk::ImageUsageFlags::TRANSFER_SRC
+                        | vk::ImageUsageFlags::TRANSFER_DST
+                        | vk::ImageUsageFlags::SAMPLED
+                } else {
+                    vk::ImageUsageFlags::TRANSFER_DST | vk::ImageUsageFlags::SAMPLED
+                },
+                samples: vk::SampleCountFlags::TYPE_1,
+                mip_levels,
+                array_layers: 1,
+                cube: false,
+            },
+            support,
+        )?;
+        let result = if blit_mipmaps {
+            texture.upload_and_blit_mipmaps(uploader, data, mip_levels)
+        } else {
+            texture.upload_with_mipmaps(uploader, data, mip_levels, format == TEXTURE_FORMAT)
+        };
+        match result {
+            Ok(upload) => Ok((texture, upload)),
+            Err(error) => {
+                texture.destroy();
+                Err(error)
+            }
+        }
+    }
+
+    // Records the upload of every subresource in one copy, the file's mip levels are used as they are
+    pub fn from_compressed(
+        device: &ash::Device,
+        allocator: Rc<MemoryAllocator>,
+        uploader: &mut StagingUploader,
+        compressed: &CompressedTexture,
+        support: &TextureSupport,
+    ) -> Result<(Self, UploadHandle)> {
+        support.check_compressed(compressed)?;
+        let texture = Self::create(
+            device,
+            allocator,
+            &ImageInfo {
+                extent: vk::Extent2D {
+                    width: compressed.width,
+                    height: compressed.height,
+                },
+                format: compressed.format,
+                tiling: vk::ImageTiling::OPTIMAL,
+                usage: vk::ImageUsageFlags::TRANSFER_DST | vk::ImageUsageFlags::SAMPLED,
+                samples: vk::SampleCountFlags::TYPE_1,
+                mip_levels: compressed.mip_levels,
+                array_layers: compressed.array_layers,
+                cube: compressed.cube,
+            },
+            support,
+        )?;
+        let result = uploader.upload_image(
+            &compressed.data,
+            &ImageUpload {
+                image: texture.image,
+                regions: &compressed.regions,
+                subresource_range: vk::ImageSubresourceRange {
+                    layer_count: compressed.array_layers,
+                    ..color_levels(0, compressed.mip_levels)
+                },
+                final_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                dst_stage: vk::PipelineStageFlags::FRAGMENT_SHADER,
+                dst_access: vk::AccessFlags::SHADER_READ,
+            },
+        );
+        match result {
+            Ok(upload) => Ok((texture, upload)),
+            Err(error) => {
+                texture.destroy();
+                Err(error)
+            }
+        }
+    }
+
+    // KTX2 and DDS files are uploaded compressed, anything else is decoded as PNG or JPEG
+    pub fn load(
+        device: &ash::Device,
+        allocator: Rc<MemoryAllocator>,
+        uploader: &mut StagingUploader,
+        path: &Path,
+        support: &TextureSupport,
+    ) -> Result<(Self, UploadHandle)> {
+        let extension = path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase());
+        match extension.as_deref() {
+            Some("ktx2") | Some("dds") => {
+                let compressed = compressed::load_compressed(path)?;
+                Self::from_compressed(device, allocator, uploader, &compressed, support)
+            }
+            _ => Self::new(device, allocator, uploader, &load_rgba(path)?, support),
+        }
+    }
+
+    // Only TYPE_2D textures can be sampled as sampler2D, the others need the matching sampler type
+    pub fn view_type(&self) -> vk::ImageViewType {
+        self.view_type
+    }
+
+    pub fn descriptor_image_info(&self) -> vk::DescriptorImageInfo {
+        vk::DescriptorImageInfo {
+            sampler: self.sampler,
+            image_view: self.view,
+            image_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        }
+    }
+
+    // The GPU must be done with the texture, null handles are skipped
+    pub fn destroy(&self) {
+        unsafe {
+            if self.sampler != vk::Sampler::null() {
+                self.device.destroy_sampler(self.sampler, None);
+            }
+            if self.view != vk::ImageView::null() {
+                self.device.destroy_image_view(self.view, None);
+            }
+        }
+        image::destroy_image(&self.device, &self.allocator, self.image, &self.allocation);
+    }
+
+    // The image with its view and sampler, everything is destroyed again when one of them fails
+    fn create(
+        device: &ash::Device,
+        allocator: Rc<MemoryAllocator>,
+        info: &ImageInfo,
+        support: &TextureSupport,
+    ) -> Result<Self> {
+        let (image, allocation) = image::create_image(
+            device,
+            &allocator,
+            info,
+            MemoryRequest::new(vk::MemoryPropertyFlags::DEVICE_LOCAL),
+        )?;
+        let mut texture = Self {
+            device: device.clone(),
+            allocator,
+            image,
+            allocation,
+            view: vk::ImageView::null(),
+            sampler: vk::Sampler::null(),
+            view_type: image::view_type(info.array_layers, info.cube),
+        };
+        if let Err(error) = texture.create_views(info, support) {
+            texture.destroy();
+            return Err(error);
+        }
+        Ok(texture)
+    }
+
+    fn create_views(&mut self, info: &ImageInfo, support: &TextureSupport) -> Result<()> {
+        self.view = image::create_image_view(
+            &self.device,
+            self.image,
+            info.format,
+            vk::ImageAspectFlags::COLOR,
+            self.view_type,
+            info.mip_levels,
+            info.array_layers,
+        )?;
+        self.sampler = create_sampler(&self.device, support.max_anisotropy, info.mip_levels)?;
+        Ok(())
+    }
+
+    // Every level is downsampled on the CPU and they all go up in one copy
+    fn upload_with_mipmaps(
+        &self,
+        uploader: &mut StagingUploader,
+        data: &TextureData,
+        mip_levels: u32,
+        srgb: bool,
+    ) -> Result<UploadHandle> {
+        let mut texels = data.rgba.clone();
+        let mut regions = vec![level_copy(0, 0, data)];
+        let mut level = None;
+        for mip_level in 1..mip_levels {
+            let next = downsample(level.as_ref().unwrap_or(data), srgb);
+            regions.push(level_copy(mip_level, texels.len() as vk::DeviceSize, &next));
+            texels.extend_from_slice(&next.rgba);
+            level = Some(next);
+        }
+        uploader.upload_image(
+            &texels,
+            &ImageUpload {
+                image: self.image,
+                regions: &regions,
+                subresource_range: color_levels(0, mip_levels),
+                final_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                dst_stage: vk::PipelineStageFlags::FRAGMENT_SHADER,
+                dst_access: vk::AccessFlags::SHADER_READ,
+            },
+        )
+    }
+
+    // Only level 0 is uploaded, the blits run on the graphics queue once the batch has copied it
+    fn upload_and_blit_mipmaps(
+        &self,
+        uploader: &mut StagingUploader,
+        data: &TextureData,
+        mip_levels: u32,
+    ) -> Result<UploadHandle> {
+        // The blits go in the same batch, so their handle covers the copy as well
+        let _ = uploader.upload_image(
+            &data.rgba,
+            &ImageUpload {
+                image: self.image,
+                regions: &[level_copy(0, 0, data)],
+                subresource_range: color_levels(0, 1),
+                final_layout: vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                dst_stage: vk::PipelineStageFlags::TRANSFER,
+                dst_access: vk::AccessFlags::TRANSFER_READ,
+            },
+        )?;
+        uploader.record_graphics(|command_buffer| {
+            self.record_mip_blits(command_buffer, data.width, data.height, mip_levels)
+        })
+    }
+
+    // Blits every level into the next one, then moves each to SHADER_READ_ONLY_OPTIMAL once it has
+    // been read from. Level 0 has to be in TRANSFER_SRC_OPTIMAL already.
+    fn record_mip_blits(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        width: u32,
+        height: u32,
+        mip_levels: u32,
+    ) {
+        let mut level_width = width as i32;
+        let mut level_height = height as i32;
+        unsafe {
+            self.device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::TOP_OF_PIPE,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[commands::image_layout_barrier(
+                    self.image,
+                    color_levels(1, mip_levels - 1),
+                    vk::ImageLayout::UNDEFINED,
+                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    vk::AccessFlags::empty(),
+                    vk::AccessFlags::TRANSFER_WRITE,
+                )],
+            );
+            for mip_level in 1..mip_levels {
+                let next_width = (level_width / 2).max(1);
+                let next_height = (level_height / 2).max(1);
+                let blit = vk::ImageBlit {
+                    src_subresource: color_level(mip_level - 1),
+                    src_offsets: [
+                        vk::Offset3D { x: 0, y: 0, z: 0 },
+                        vk::Offset3D {
+                            x: level_width,
+                            y: level_height,
+                            z: 1,
+                        },
+                    ],
+                    dst_subresource: color_level(mip_level),
+                    dst_offsets: [
+                        vk::Offset3D { x: 0, y: 0, z: 0 },
+                        vk::Offset3D {
+                            x: next_width,
+                            y: next_height,
+                            z: 1,
+                        },
+                    ],
+                };
+                self.device.cmd_blit_image(
+                    command_buffer,
+                    self.image,
+                    vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                    self.image,
+                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    &[blit],
+                    vk::Filter::LINEAR,
+                );
+                // The level read from is done, the one written is read from by the next blit
+                self.device.cmd_pipeline_barrier(
+                    command_buffer,
+                    vk::PipelineStageFlags::TRANSFER,
+                    vk::PipelineStageFlags::TRANSFER | vk::PipelineStageFlags::FRAGMENT_SHADER,
+                    vk::DependencyFlags::empty(),
+                    &[],
+                    &[],
+                    &[
+                        commands::image_layout_barrier(
+                            self.image,
+                            color_levels(mip_level - 1, 1),
+                            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                            vk::AccessFlags::TRANSFER_READ,
+                            vk::AccessFlags::SHADER_READ,
+                        ),
+                        commands::image_layout_barrier(
+                            self.image,
+                            color_levels(mip_level, 1),
+                            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                            vk::AccessFlags::TRANSFER_WRITE,
+                            vk::AccessFlags::TRANSFER_READ,
+                        ),
+                    ],
+                );
+                level_width = next_width;
+                level_height = next_height;
+            }
+            // The last level is never blitted from
+            self.device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::FRAGMENT_SHADER,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[commands::image_layout_barrier(
+                    self.image,
+                    color_levels(mip_levels - 1, 1),
+                    vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                    vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                    vk::AccessFlags::TRANSFER_READ,
+                    vk::AccessFlags::SHADER_READ,
+                )],
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mip_levels_halve_the_larger_side() {
+        assert_eq!(mip_level_count(1, 1), 1);
+        assert_eq!(mip_level_count(2, 1), 2);
+        assert_eq!(mip_level_count(256, 256), 9);
+        assert_eq!(mip_level_count(300, 20), 9);
+        assert_eq!(mip_level_count(0, 0), 1);
+    }
+
+    #[test]
+    fn srgb_round_trips() {
+        assert_eq!(srgb_to_linear(0), 0.0);
+        assert_eq!(srgb_to_linear(255), 1.0);
+        // Mid grey in sRGB is only about a fifth of the light
+        assert!((srgb_to_linear(128) - 0.2158).abs() < 0.001);
+        for value in 0..=255 {
+            assert_eq!(linear_to_srgb(srgb_to_linear(value)), value);
+        }
+    }
+
+    #[test]
+    fn downsample_averages_blocks() {
+        let level = TextureData {
+            width: 2,
+            height: 2,
+            rgba: [[0, 0, 0, 0], [255, 255, 255, 255]].repeat(2).concat(),
+        };
+        let linear = downsample(&level, false);
+        assert_eq!((linear.width, linear.height), (1, 1));
+        assert_eq!(linear.rgba, [128, 128, 128, 128]);
+        // Averaged as light, black and white come out brighter than the middle value, alpha doesn't
+        let srgb = downsample(&level, true);
+        assert_eq!(srgb.rgba, [188, 188, 188, 128]);
+    }
+
+    #[test]
+    fn downsample_repeats_odd_edges() {
+        let level = TextureData {
+            width: 3,
+            height: 1,
+            rgba: [[10, 20, 30, 40], [30, 40, 50, 60], [90, 90, 90, 90]].concat(),
+        };
+        let next = downsample(&level, false);
+        assert_eq!((next.width, next.height), (1, 1));
+        assert_eq!(next.rgba, [20, 30, 40, 50]);
+    }
+
+    fn support() -> TextureSupport {
+        TextureSupport {
+            max_anisotropy: None,
+            linear_blit: true,
+            compressed_formats: vec![vk::Format::BC1_RGBA_UNORM_BLOCK],
+            cube_arrays: false,
+            max_image_dimension_2d: 4096,
+            max_image_dimension_cube: 1024,
+            max_image_array_layers: 12,
+        }
+    }
+
+    fn compressed(size: u32, mip_levels: u32, array_layers: u32, cube: bool) -> CompressedTexture {
+        CompressedTexture {
+            format: vk::Format::BC1_RGBA_UNORM_BLOCK,
+            width: size,
+            height: size,
+            mip_levels,
+            array_layers,
+            cube,
+            data: Vec::new(),
+            regions: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn compressed_files_are_checked_against_the_limits() {
+        let support = support();
+        assert!(support
+            .check_compressed(&compressed(4096, 13, 12, false))
+            .is_ok());
+        assert!(support
+            .check_compressed(&compressed(1024, 11, 6, true))
+            .is_ok());
+        let rejected = |texture| {
+            matches!(
+                support.check_compressed(&texture),
+                Err(Error::UnsupportedFormat(_))
+            )
+        };
+        assert!(rejected(CompressedTexture {
+            format: vk::Format::BC7_UNORM_BLOCK,
+            ..compressed(64, 1, 1, false)
+        }));
+        assert!(rejected(compressed(8192, 1, 1, false)));
+        // Cube faces have a lower limit of their own
+        assert!(rejected(compressed(2048, 1, 6, true)));
+        assert!(rejected(compressed(64, 1, 13, false)));
+        assert!(rejected(compressed(64, 1, 12, true)));
+        assert!(rejected(compressed(64, 8, 1, false)));
+    }
+}
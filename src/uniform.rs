@@ -0,0 +1,264 @@
+use crate::buffer;
+use crate::error::{Error, Result};
+use crate::memory::{Allocation, MemoryAllocator, MemoryRequest};
+use ash::vk;
+use std::marker::PhantomData;
+use std::mem::size_of;
+use std::rc::Rc;
+
+/// Types whose memory layout matches GLSL's std140 rules, so they can be copied into a uniform buffer
+/// as they are. ALIGNMENT is the std140 base alignment and SIZE the bytes std140 gives the type, the
+/// next member can start right after them. Structs get this from std140_struct!, which checks their
+/// layout when they are compiled.
+///
+/// # Safety
+/// The type must be laid out exactly as std140 lays out the GLSL type it stands for, in its first
+/// SIZE bytes.
+pub unsafe trait Std140: Copy {
+    const ALIGNMENT: usize;
+    const SIZE: usize = size_of::<Self>();
+}
+
+unsafe impl Std140 for f32 {
+    const ALIGNMENT: usize = 4;
+}
+unsafe impl Std140 for i32 {
+    const ALIGNMENT: usize = 4;
+}
+unsafe impl Std140 for u32 {
+    const ALIGNMENT: usize = 4;
+}
+unsafe impl Std140 for glam::Vec2 {
+    const ALIGNMENT: usize = 8;
+}
+// A vec3 takes 12 bytes, a float after it fills the rest of the 16 that Vec3A always takes
+unsafe impl Std140 for glam::Vec3A {
+    const ALIGNMENT: usize = 16;
+    const SIZE: usize = 12;
+}
+unsafe impl Std140 for glam::Vec4 {
+    const ALIGNMENT: usize = 16;
+}
+unsafe impl Std140 for glam::IVec4 {
+    const ALIGNMENT: usize = 16;
+}
+unsafe impl Std140 for glam::UVec4 {
+    const ALIGNMENT: usize = 16;
+}
+unsafe impl Std140 for glam::Mat4 {
+    const ALIGNMENT: usize = 16;
+}
+
+// std140 pads every array element to 16 bytes, so only arrays of types that already are a multiple
+// of 16 bytes have the same layout in Rust
+unsafe impl<T: Std140, const N: usize> Std140 for [T; N] {
+    const ALIGNMENT: usize = {
+        assert!(
+            size_of::<T>().is_multiple_of(16),
+            "std140 array elements have to be a multiple of 16 bytes"
+        );
+        16
+    };
+}
+
+// Declares a #[repr(C)] struct and implements Std140 for it. Compilation fails when a field isn't
+// Std140, or sits at an offset std140 wouldn't put it at. Padding has to be added as explicit fields.
+#[macro_export]
+macro_rules! std140_struct {
+    (
+        $(#[$attr:meta])*
+        $vis:vis struct $name:ident {
+            $($field_vis:vis $field:ident: $ty:ty),* $(,)?
+        }
+    ) => {
+        $(#[$attr])*
+        #[repr(C)]
+        #[derive(Clone, Copy, Debug)]
+        $vis struct $name {
+            $($field_vis $field: $ty),*
+        }
+
+        unsafe impl $crate::uniform::Std140 for $name {
+            const ALIGNMENT: usize = 16;
+        }
+
+        const _: () = {
+            // Each member starts at the end of the one before, rounded up to its alignment
+            let mut offset: usize = 0;
+            $(
+                offset = offset.next_multiple_of(<$ty as $crate::uniform::Std140>::ALIGNMENT);
+                assert!(
+                    ::std::mem::offset_of!($name, $field) == offset,
+                    concat!(
+                        "Field ",
+                        stringify!($field),
+                        " of ",
+                        stringify!($name),
+                        " is not at its std140 offset"
+                    )
+                );
+                offset += <$ty as $crate::uniform::Std140>::SIZE;
+            )*
+            assert!(::std::mem::size_of::<$name>() >= offset);
+            assert!(
+                ::std::mem::size_of::<$name>().is_multiple_of(16),
+                concat!(stringify!($name), " has to be padded to a multiple of 16 bytes")
+            );
+        };
+    };
+}
+
+// A uniform buffer holding one T, mapped for as long as it exists. Memory that isn't host coherent
+// is flushed after every write.
+pub struct UniformBuffer<T: Std140> {
+    device: ash::Device,
+    allocator: Rc<MemoryAllocator>,
+    buffer: vk::Buffer,
+    allocation: Allocation,
+    value: PhantomData<T>,
+}
+
+impl<T: Std140> UniformBuffer<T> {
+    pub fn new(device: &ash::Device, allocator: Rc<MemoryAllocator>) -> Result<Self> {
+        let (buffer, allocation) = buffer::create_buffer(
+            device,
+            &allocator,
+            size_of::<T>() as vk::DeviceSize,
+            vk::BufferUsageFlags::UNIFORM_BUFFER,
+            // Device local as well on ReBAR and integrated GPUs, so shaders read it faster
+            MemoryRequest::new(vk::MemoryPropertyFlags::HOST_VISIBLE).prefer(
+                vk::MemoryPropertyFlags::DEVICE_LOCAL | vk::MemoryPropertyFlags::HOST_COHERENT,
+            ),
+        )?;
+        Ok(Self {
+            device: device.clone(),
+            allocator,
+            buffer,
+            allocation,
+            value: PhantomData,
+        })
+    }
+
+    pub fn buffer(&self) -> vk::Buffer {
+        self.buffer
+    }
+
+    // The buffer must not be read by a frame that's still in flight
+    pub fn write(&self, value: &T) -> Result<()> {
+        unsafe { (self.allocation.mapped as *mut T).write_unaligned(*value) };
+        self.allocator
+            .flush(&self.allocation, 0, size_of::<T>() as vk::DeviceSize)
+    }
+
+    pub fn destroy(&self) {
+        buffer::destroy_buffer(&self.device, &self.allocator, self.buffer, &self.allocation);
+    }
+}
+
+// Holds capacity values of T, each aligned to minUniformBufferOffsetAlignment. It's bound as a
+// UNIFORM_BUFFER_DYNAMIC descriptor covering one T, and offset(index) picks the value per draw.
+pub struct DynamicUniformBuffer<T: Std140> {
+    device: ash::Device,
+    allocator: Rc<MemoryAllocator>,
+    buffer: vk::Buffer,
+    allocation: Allocation,
+    stride: vk::DeviceSize,
+    capacity: usize,
+    value: PhantomData<T>,
+}
+
+impl<T: Std140> DynamicUniformBuffer<T> {
+    pub fn new(
+        device: &ash::Device,
+        allocator: Rc<MemoryAllocator>,
+        capacity: usize,
+        min_offset_alignment: vk::DeviceSize,
+    ) -> Result<Self> {
+        let stride =
+            (size_of::<T>() as vk::DeviceSize).next_multiple_of(min_offset_alignment.max(1));
+        let (buffer, allocation) = buffer::create_buffer(
+            device,
+            &allocator,
+            stride * capacity as vk::DeviceSize,
+            vk::BufferUsageFlags::UNIFORM_BUFFER,
+            MemoryRequest::new(vk::MemoryPropertyFlags::HOST_VISIBLE).prefer(
+                vk::MemoryPropertyFlags::DEVICE_LOCAL | vk::MemoryPropertyFlags::HOST_COHERENT,
+            ),
+        )?;
+        Ok(Self {
+            device: device.clone(),
+            allocator,
+            buffer,
+            allocation,
+            stride,
+            capacity,
+            value: PhantomData,
+        })
+    }
+
+    pub fn buffer(&self) -> vk::Buffer {
+        self.buffer
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    // The dynamic offset to bind the descriptor set with for the value at index
+    pub fn offset(&self, index: usize) -> u32 {
+        (self.stride * index as vk::DeviceSize) as u32
+    }
+
+    // Writes values from the start of the buffer, flushing them all at once. The buffer must not be
+    // read by a frame that's still in flight.
+    pub fn write_all(&self, values: &[T]) -> Result<()> {
+        if values.len() > self.capacity {
+            return Err(Error::Config(format!(
+                "{} values don't fit in a dynamic uniform buffer of {}",
+                values.len(),
+                self.capacity
+            )));
+        }
+        for (index, value) in values.iter().enumerate() {
+            unsafe {
+                (self.allocation.mapped.add(self.offset(index) as usize) as *mut T)
+                    .write_unaligned(*value)
+            };
+        }
+        self.allocator.flush(
+            &self.allocation,
+            0,
+            self.stride * values.len() as vk::DeviceSize,
+        )
+    }
+
+    pub fn destroy(&self) {
+        buffer::destroy_buffer(&self.device, &self.allocator, self.buffer, &self.allocation);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Compiling is most of the test, every member sits where std140 puts it
+    std140_struct! {
+        #[allow(dead_code)]
+        struct Light {
+            direction: glam::Vec3A,
+            color: glam::Vec4,
+            uv: glam::Vec2,
+            intensity: f32,
+            range: f32,
+            tints: [glam::Vec4; 2],
+        }
+    }
+
+    #[test]
+    fn vec3_leaves_room_for_a_scalar() {
+        assert_eq!(<glam::Vec3A as Std140>::SIZE, 12);
+        assert_eq!(std::mem::offset_of!(Light, color), 16);
+        assert_eq!(std::mem::offset_of!(Light, tints), 48);
+        assert_eq!(<Light as Std140>::SIZE, 80);
+    }
+}
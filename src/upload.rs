@@ -0,0 +1,756 @@
+use crate::buffer;
+use crate::commands::{self, UploadQueues};
+use crate::error::Result;
+use crate::memory::{Allocation, AllocationStrategy, MemoryAllocator, MemoryRequest};
+use ash::vk;
+use std::collections::VecDeque;
+use std::mem::size_of_val;
+use std::ptr;
+use std::rc::Rc;
+
+// Copies that haven't been submitted yet are batched into one command buffer. Their data sits in a
+// persistently mapped staging ring until the batch's fence says the copies are done.
+pub struct StagingUploader {
+    device: ash::Device,
+    allocator: Rc<MemoryAllocator>,
+    queues: UploadQueues,
+    ring_buffer: vk::Buffer,
+    ring_allocation: Allocation,
+    ring: Ring,
+    recording: Option<Recording>,
+    in_flight: VecDeque<Batch>,
+    // Serial numbers of the batches, every one below completed_serial has finished
+    next_serial: u64,
+    completed_serial: u64,
+}
+
+// Refers to one upload, which is finished once its batch is. Check it with is_complete, or wait on it
+#[must_use]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct UploadHandle {
+    serial: u64,
+}
+
+// Copies into an image. buffer_offset of the regions counts from the start of the uploaded data. Every
+// subresource in subresource_range starts out UNDEFINED and is left in final_layout, ready for dst_stage
+// to access it with dst_access.
+pub struct ImageUpload<'a> {
+    pub image: vk::Image,
+    pub regions: &'a [vk::BufferImageCopy],
+    pub subresource_range: vk::ImageSubresourceRange,
+    pub final_layout: vk::ImageLayout,
+    pub dst_stage: vk::PipelineStageFlags,
+    pub dst_access: vk::AccessFlags,
+}
+
+struct Recording {
+    serial: u64,
+    command_buffer: vk::CommandBuffer,
+    uses_ring: bool,
+    // How rendering uses each destination next, so the batch can make its writes visible there
+    destinations: Vec<(vk::Buffer, vk::PipelineStageFlags, vk::AccessFlags)>,
+    images: Vec<ImageDestination>,
+    // Uploads too big for the ring get a staging buffer of their own for the life of the batch
+    dedicated_staging: Vec<(vk::Buffer, Allocation)>,
+    // Follow-up work such as mipmap blits, run on the graphics queue once the copies are done
+    graphics_command_buffer: Option<vk::CommandBuffer>,
+}
+
+// Images also need a layout transition once their copies are done
+struct ImageDestination {
+    image: vk::Image,
+    subresource_range: vk::ImageSubresourceRange,
+    layout: vk::ImageLayout,
+    stage: vk::PipelineStageFlags,
+    access: vk::AccessFlags,
+}
+
+struct Batch {
+    serial: u64,
+    fence: vk::Fence,
+    command_buffers: Vec<(vk::CommandPool, vk::CommandBuffer)>,
+    semaphore: vk::Semaphore,
+    // Where the ring tail moves to once the batch is done, None when it didn't use the ring
+    ring_end: Option<vk::DeviceSize>,
+    dedicated_staging: Vec<(vk::Buffer, Allocation)>,
+}
+
+// Which part of the staging ring is in use. It is used from tail to head, wrapping around at size.
+struct Ring {
+    size: vk::DeviceSize,
+    head: vk::DeviceSize,
+    tail: vk::DeviceSize,
+    empty: bool,
+}
+
+impl Ring {
+    fn new(size: vk::DeviceSize) -> Self {
+        Self {
+            size,
+            head: 0,
+            tail: 0,
+            empty: true,
+        }
+    }
+
+    // None when there is no room until older batches are released
+    fn allocate(&mut self, size: vk::DeviceSize) -> Option<vk::DeviceSize> {
+        if self.empty {
+            self.head = 0;
+            self.tail = 0;
+        }
+        let aligned_head = align_up(self.head, STAGING_ALIGNMENT);
+        let offset = if self.empty || self.head > self.tail {
+            // The free space is after the head and before the tail, wrapping around in between
+            if aligned_head + size <= self.size {
+                aligned_head
+            } else if size <= self.tail {
+                0
+            } else {
+                return None;
+            }
+        } else if aligned_head + size <= self.tail {
+            aligned_head
+        } else {
+            return None;
+        };
+        self.head = offset + size;
+        self.empty = false;
+        Some(offset)
+    }
+
+    // Frees everything up to the end of a finished batch. The ring is empty unless something
+    // recorded after that batch still uses it.
+    fn release(&mut self, end: vk::DeviceSize, still_used: bool) {
+        self.tail = end;
+        if !still_used {
+            self.empty = true;
+        }
+    }
+}
+
+// Copy offsets are kept at this alignment, it covers the texel sizes images will need
+const STAGING_ALIGNMENT: vk::DeviceSize = 16;
+
+// Staging memory is only written by the CPU, so nothing is preferred and plain host memory wins over
+// the small device local and host visible heap
+fn staging_memory_request() -> MemoryRequest {
+    MemoryRequest::new(
+        vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+    )
+}
+
+fn align_up(offset: vk::DeviceSize, alignment: vk::DeviceSize) -> vk::DeviceSize {
+    offset.div_ceil(alignment) * alignment
+}
+
+impl StagingUploader {
+    pub fn new(
+        device: &ash::Device,
+        allocator: Rc<MemoryAllocator>,
+        queues: UploadQueues,
+        ring_size: vk::DeviceSize,
+    ) -> Result<Self> {
+        // The allocator keeps host visible memory mapped, so the ring can be written at any time
+        let (ring_buffer, ring_allocation) = buffer::create_buffer(
+            device,
+            &allocator,
+            ring_size,
+            vk::BufferUsageFlags::TRANSFER_SRC,
+            staging_memory_request(),
+        )?;
+        Ok(Self {
+            device: device.clone(),
+            allocator,
+            queues,
+            ring_buffer,
+            ring_allocation,
+            ring: Ring::new(ring_size),
+            recording: None,
+            in_flight: VecDeque::new(),
+            next_serial: 1,
+            completed_serial: 0,
+        })
+    }
+
+    pub fn queues(&self) -> UploadQueues {
+        self.queues
+    }
+
+    // Records follow-up work that needs the graphics queue, such as blits, into the current batch. It
+    // runs after the batch's copies, which are visible to it in the dst_stage they were uploaded for.
+    pub fn record_graphics(
+        &mut self,
+        record: impl FnOnce(vk::CommandBuffer),
+    ) -> Result<UploadHandle> {
+        let device = self.device.clone();
+        let graphics_command_pool = self.queues.graphics_command_pool;
+        let recording = self.recording()?;
+        let command_buffer = match recording.graphics_command_buffer {
+            Some(command_buffer) => command_buffer,
+            None => {
+                let command_buffer =
+                    commands::begin_single_time_commands(&device, &graphics_command_pool)?;
+                recording.graphics_command_buffer = Some(command_buffer);
+                command_buffer
+            }
+        };
+        record(command_buffer);
+        Ok(UploadHandle {
+            serial: recording.serial,
+        })
+    }
+
+    // Records a copy of data into dst at dst_offset. dst_stage and dst_access say how rendering reads
+    // dst afterwards, the batch makes the copy visible there and hands dst to the graphics family.
+    pub fn upload_buffer<T: Copy>(
+        &mut self,
+        data: &[T],
+        dst: vk::Buffer,
+        dst_offset: vk::DeviceSize,
+        dst_stage: vk::PipelineStageFlags,
+        dst_access: vk::AccessFlags,
+    ) -> Result<UploadHandle> {
+        let size = size_of_val(data) as vk::DeviceSize;
+        let (src, src_offset) = self.stage(data.as_ptr() as *const u8, size)?;
+        let command_buffer = self.recording()?.command_buffer;
+        unsafe {
+            self.device.cmd_copy_buffer(
+                command_buffer,
+                src,
+                dst,
+                &[vk::BufferCopy {
+                    src_offset,
+                    dst_offset,
+                    size,
+                }],
+            );
+        }
+        let recording = self.recording()?;
+        recording.destinations.push((dst, dst_stage, dst_access));
+        Ok(UploadHandle {
+            serial: recording.serial,
+        })
+    }
+
+    // Records copies of data into an image, transitioning it for the copies and again after them
+    pub fn upload_image(&mut self, data: &[u8], upload: &ImageUpload) -> Result<UploadHandle> {
+        let (src, src_offset) = self.stage(data.as_ptr(), data.len() as vk::DeviceSize)?;
+        let command_buffer = self.recording()?.command_buffer;
+        let regions: Vec<vk::BufferImageCopy> = upload
+            .regions
+            .iter()
+            .map(|region| vk::BufferImageCopy {
+                buffer_offset: src_offset + region.buffer_offset,
+                ..*region
+            })
+            .collect();
+        unsafe {
+            self.device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::TOP_OF_PIPE,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[commands::image_layout_barrier(
+                    upload.image,
+                    upload.subresource_range,
+                    vk::ImageLayout::UNDEFINED,
+                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    vk::AccessFlags::empty(),
+                    vk::AccessFlags::TRANSFER_WRITE,
+                )],
+            );
+            self.device.cmd_copy_buffer_to_image(
+                command_buffer,
+                src,
+                upload.image,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                &regions,
+            );
+        }
+        let recording = self.recording()?;
+        recording.images.push(ImageDestination {
+            image: upload.image,
+            subresource_range: upload.subresource_range,
+            layout: upload.final_layout,
+            stage: upload.dst_stage,
+            access: upload.dst_access,
+        });
+        Ok(UploadHandle {
+            serial: recording.serial,
+        })
+    }
+
+    // Submits everything recorded since the last flush, without waiting for it
+    pub fn flush(&mut self) -> Result<()> {
+        let recording = match self.recording.take() {
+            Some(recording) => recording,
+            None => return Ok(()),
+        };
+        let fence_info = vk::FenceCreateInfo {
+            s_type: vk::StructureType::FENCE_CREATE_INFO,
+            ..Default::default()
+        };
+        let fence = unsafe { self.device.create_fence(&fence_info, None)? };
+        let mut batch = Batch {
+            serial: recording.serial,
+            fence,
+            command_buffers: vec![(self.queues.transfer_command_pool, recording.command_buffer)],
+            semaphore: vk::Semaphore::null(),
+            ring_end: if recording.uses_ring {
+                Some(self.ring.head)
+            } else {
+                None
+            },
+            dedicated_staging: recording.dedicated_staging,
+        };
+        if let Some(graphics_command_buffer) = recording.graphics_command_buffer {
+            batch
+                .command_buffers
+                .push((self.queues.graphics_command_pool, graphics_command_buffer));
+        }
+        let dst_stage = recording
+            .destinations
+            .iter()
+            .map(|(_, stage, _)| *stage)
+            .chain(recording.images.iter().map(|image| image.stage))
+            .fold(vk::PipelineStageFlags::empty(), |stages, stage| {
+                stages | stage
+            });
+        let result = if self.queues.needs_ownership_transfer() {
+            self.submit_with_ownership_transfer(
+                &mut batch,
+                recording.command_buffer,
+                recording.graphics_command_buffer,
+                &recording.destinations,
+                &recording.images,
+                dst_stage,
+            )
+        } else {
+            self.submit_on_one_queue(
+                &batch,
+                recording.command_buffer,
+                recording.graphics_command_buffer,
+                &recording.destinations,
+                &recording.images,
+                dst_stage,
+            )
+        };
+        self.in_flight.push_back(batch);
+        // A batch that wasn't submitted never signals its fence, so everything is freed once the
+        // device has gone idle instead
+        if result.is_err() {
+            unsafe { self.device.device_wait_idle().ok() };
+            while !self.in_flight.is_empty() {
+                self.retire_oldest();
+            }
+        }
+        result
+    }
+
+    // Checks without blocking, also frees the staging space of every batch that has finished
+    pub fn is_complete(&mut self, handle: UploadHandle) -> Result<bool> {
+        while let Some(batch) = self.in_flight.front() {
+            if !unsafe { self.device.get_fence_status(batch.fence)? } {
+                break;
+            }
+            self.retire_oldest();
+        }
+        Ok(handle.serial <= self.completed_serial)
+    }
+
+    // Blocks until the upload has finished, submitting it first if that hasn't happened yet
+    pub fn wait(&mut self, handle: UploadHandle) -> Result<()> {
+        if matches!(&self.recording, Some(recording) if recording.serial <= handle.serial) {
+            self.flush()?;
+        }
+        while handle.serial > self.completed_serial && !self.in_flight.is_empty() {
+            self.wait_oldest()?;
+        }
+        Ok(())
+    }
+
+    pub fn wait_idle(&mut self) -> Result<()> {
+        self.flush()?;
+        while !self.in_flight.is_empty() {
+            self.wait_oldest()?;
+        }
+        Ok(())
+    }
+
+    // Frees the ring and everything still pending, after waiting for the GPU to finish with them
+    pub fn destroy(&mut self) {
+        if let Err(error) = self.wait_idle() {
+            log::error!("Unable to finish the pending uploads: {}", error);
+            unsafe { self.device.device_wait_idle().ok() };
+        }
+        while !self.in_flight.is_empty() {
+            self.retire_oldest();
+        }
+        buffer::destroy_buffer(
+            &self.device,
+            &self.allocator,
+            self.ring_buffer,
+            &self.ring_allocation,
+        );
+    }
+
+    fn recording(&mut self) -> Result<&mut Recording> {
+        if self.recording.is_none() {
+            let command_buffer = commands::begin_single_time_commands(
+                &self.device,
+                &self.queues.transfer_command_pool,
+            )?;
+            self.recording = Some(Recording {
+                serial: self.next_serial,
+                command_buffer,
+                uses_ring: false,
+                destinations: Vec::new(),
+                images: Vec::new(),
+                dedicated_staging: Vec::new(),
+                graphics_command_buffer: None,
+            });
+            self.next_serial += 1;
+        }
+        Ok(self.recording.as_mut().unwrap())
+    }
+
+    // Copies the data into staging memory, returning the buffer and offset to copy from
+    fn stage(&mut self, data: *const u8, size: vk::DeviceSize) -> Result<(vk::Buffer, u64)> {
+        if size <= self.ring.size {
+            loop {
+                if let Some(offset) = self.ring.allocate(size) {
+                    unsafe {
+                        ptr::copy_nonoverlapping(
+                            data,
+                            self.ring_allocation.mapped.add(offset as usize),
+                            size as usize,
+                        );
+                    }
+                    self.recording()?.uses_ring = true;
+                    return Ok((self.ring_buffer, offset));
+                }
+                // The ring is full, so older batches have to finish before it has room again
+                if self.in_flight.is_empty() {
+                    self.flush()?;
+                }
+                if self.in_flight.is_empty() {
+                    break;
+                }
+                self.wait_oldest()?;
+            }
+        }
+        // These only live until their batch is done, so they are packed one after another
+        let (staging_buffer, staging_allocation) = buffer::create_buffer_with_strategy(
+            &self.device,
+            &self.allocator,
+            size,
+            vk::BufferUsageFlags::TRANSFER_SRC,
+            staging_memory_request(),
+            AllocationStrategy::Linear,
+        )?;
+        unsafe {
+            ptr::copy_nonoverlapping(data, staging_allocation.mapped, size as usize);
+        }
+        self.recording()?
+            .dedicated_staging
+            .push((staging_buffer, staging_allocation));
+        Ok((staging_buffer, 0))
+    }
+
+    // Waits on the oldest batch and frees what it held
+    fn wait_oldest(&mut self) -> Result<()> {
+        if let Some(batch) = self.in_flight.front() {
+            unsafe {
+                self.device
+                    .wait_for_fences(&[batch.fence], true, u64::MAX)?
+            };
+            self.retire_oldest();
+        }
+        Ok(())
+    }
+
+    fn retire_oldest(&mut self) {
+        let batch = match self.in_flight.pop_front() {
+            Some(batch) => batch,
+            None => return,
+        };
+        unsafe {
+            self.device.destroy_fence(batch.fence, None);
+            if batch.semaphore != vk::Semaphore::null() {
+                self.device.destroy_semaphore(batch.semaphore, None);
+            }
+            for (command_pool, command_buffer) in batch.command_buffers {
+                self.device
+                    .free_command_buffers(command_pool, &[command_buffer]);
+            }
+        }
+        for (staging_buffer, staging_allocation) in batch.dedicated_staging {
+            buffer::destroy_buffer(
+                &self.device,
+                &self.allocator,
+                staging_buffer,
+                &staging_allocation,
+            );
+        }
+        if let Some(ring_end) = batch.ring_end {
+            let recording_uses_ring = self
+                .recording
+                .as_ref()
+                .is_some_and(|recording| recording.uses_ring);
+            let later_batches_use_ring =
+                self.in_flight.iter().any(|batch| batch.ring_end.is_some());
+            self.ring
+                .release(ring_end, recording_uses_ring || later_batches_use_ring);
+        }
+        self.completed_serial = batch.serial;
+    }
+
+    // On one queue a barrier after the copies is enough, later submissions are ordered behind it
+    fn submit_on_one_queue(
+        &self,
+        batch: &Batch,
+        command_buffer: vk::CommandBuffer,
+        graphics_command_buffer: Option<vk::CommandBuffer>,
+        destinations: &[(vk::Buffer, vk::PipelineStageFlags, vk::AccessFlags)],
+        images: &[ImageDestination],
+        dst_stage: vk::PipelineStageFlags,
+    ) -> Result<()> {
+        let dst_access = destinations
+            .iter()
+            .fold(vk::AccessFlags::empty(), |access, (_, _, dst_access)| {
+                access | *dst_access
+            });
+        let image_barriers: Vec<vk::ImageMemoryBarrier> = images
+            .iter()
+            .map(|image| {
+                commands::image_layout_barrier(
+                    image.image,
+                    image.subresource_range,
+                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    image.layout,
+                    vk::AccessFlags::TRANSFER_WRITE,
+                    image.access,
+                )
+            })
+            .collect();
+        let memory_barrier = vk::MemoryBarrier {
+            s_type: vk::StructureType::MEMORY_BARRIER,
+            src_access_mask: vk::AccessFlags::TRANSFER_WRITE,
+            dst_access_mask: dst_access,
+            ..Default::default()
+        };
+        let command_buffers: Vec<vk::CommandBuffer> =
+            [Some(command_buffer), graphics_command_buffer]
+                .into_iter()
+                .flatten()
+                .collect();
+        let submit_info = vk::SubmitInfo {
+            s_type: vk::StructureType::SUBMIT_INFO,
+            command_buffer_count: command_buffers.len() as u32,
+            p_command_buffers: command_buffers.as_ptr(),
+            ..Default::default()
+        };
+        unsafe {
+            self.device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                dst_stage,
+                vk::DependencyFlags::empty(),
+                &[memory_barrier],
+                &[],
+                &image_barriers,
+            );
+            self.device.end_command_buffer(command_buffer)?;
+            if let Some(graphics_command_buffer) = graphics_command_buffer {
+                self.device.end_command_buffer(graphics_command_buffer)?;
+            }
+            self.device
+                .queue_submit(self.queues.transfer_queue, &[submit_info], batch.fence)?;
+        }
+        Ok(())
+    }
+
+    // The transfer queue releases every destination and the graphics queue acquires them, waiting
+    // on a semaphore. The fence goes with the acquire, so the batch is done once both have run.
+    fn submit_with_ownership_transfer(
+        &self,
+        batch: &mut Batch,
+        command_buffer: vk::CommandBuffer,
+        graphics_command_buffer: Option<vk::CommandBuffer>,
+        destinations: &[(vk::Buffer, vk::PipelineStageFlags, vk::AccessFlags)],
+        images: &[ImageDestination],
+        dst_stage: vk::PipelineStageFlags,
+    ) -> Result<()> {
+        let image_ownership_barrier =
+            |image: &ImageDestination, src_access, dst_access| vk::ImageMemoryBarrier {
+                src_queue_family_index: self.queues.transfer_family,
+                dst_queue_family_index: self.queues.graphics_family,
+                ..commands::image_layout_barrier(
+                    image.image,
+                    image.subresource_range,
+                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    image.layout,
+                    src_access,
+                    dst_access,
+                )
+            };
+        let image_releases: Vec<vk::ImageMemoryBarrier> = images
+            .iter()
+            .map(|image| {
+                image_ownership_barrier(
+                    image,
+                    vk::AccessFlags::TRANSFER_WRITE,
+                    vk::AccessFlags::empty(),
+                )
+            })
+            .collect();
+        let image_acquires: Vec<vk::ImageMemoryBarrier> = images
+            .iter()
+            .map(|image| image_ownership_barrier(image, vk::AccessFlags::empty(), image.access))
+            .collect();
+        let releases: Vec<vk::BufferMemoryBarrier> = destinations
+            .iter()
+            .map(|(buffer, _, _)| {
+                commands::buffer_ownership_barrier(
+                    *buffer,
+                    self.queues.transfer_family,
+                    self.queues.graphics_family,
+                    vk::AccessFlags::TRANSFER_WRITE,
+                    vk::AccessFlags::empty(),
+                )
+            })
+            .collect();
+        let acquires: Vec<vk::BufferMemoryBarrier> = destinations
+            .iter()
+            .map(|(buffer, _, dst_access)| {
+                commands::buffer_ownership_barrier(
+                    *buffer,
+                    self.queues.transfer_family,
+                    self.queues.graphics_family,
+                    vk::AccessFlags::empty(),
+                    *dst_access,
+                )
+            })
+            .collect();
+        let semaphore_info = vk::SemaphoreCreateInfo {
+            s_type: vk::StructureType::SEMAPHORE_CREATE_INFO,
+            ..Default::default()
+        };
+        unsafe {
+            self.device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                vk::DependencyFlags::empty(),
+                &[],
+                &releases,
+                &image_releases,
+            );
+            self.device.end_command_buffer(command_buffer)?;
+            let acquire_command_buffer = commands::begin_single_time_commands(
+                &self.device,
+                &self.queues.graphics_command_pool,
+            )?;
+            batch
+                .command_buffers
+                .push((self.queues.graphics_command_pool, acquire_command_buffer));
+            // The semaphore wait only blocks dst_stage, so the acquire has to start from the same
+            // stages to be ordered after it
+            self.device.cmd_pipeline_barrier(
+                acquire_command_buffer,
+                dst_stage,
+                dst_stage,
+                vk::DependencyFlags::empty(),
+                &[],
+                &acquires,
+                &image_acquires,
+            );
+            self.device.end_command_buffer(acquire_command_buffer)?;
+            if let Some(graphics_command_buffer) = graphics_command_buffer {
+                self.device.end_command_buffer(graphics_command_buffer)?;
+            }
+            let acquire_command_buffers: Vec<vk::CommandBuffer> =
+                [Some(acquire_command_buffer), graphics_command_buffer]
+                    .into_iter()
+                    .flatten()
+                    .collect();
+            batch.semaphore = self.device.create_semaphore(&semaphore_info, None)?;
+            let release_submit = vk::SubmitInfo {
+                s_type: vk::StructureType::SUBMIT_INFO,
+                command_buffer_count: 1,
+                p_command_buffers: &command_buffer,
+                signal_semaphore_count: 1,
+                p_signal_semaphores: &batch.semaphore,
+                ..Default::default()
+            };
+            let acquire_submit = vk::SubmitInfo {
+                s_type: vk::StructureType::SUBMIT_INFO,
+                wait_semaphore_count: 1,
+                p_wait_semaphores: &batch.semaphore,
+                p_wait_dst_stage_mask: &dst_stage,
+                command_buffer_count: acquire_command_buffers.len() as u32,
+                p_command_buffers: acquire_command_buffers.as_ptr(),
+                ..Default::default()
+            };
+            self.device.queue_submit(
+                self.queues.transfer_queue,
+                &[release_submit],
+                vk::Fence::null(),
+            )?;
+            self.device
+                .queue_submit(self.queues.graphics_queue, &[acquire_submit], batch.fence)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allocations_are_aligned() {
+        let mut ring = Ring::new(256);
+        assert_eq!(ring.allocate(10), Some(0));
+        assert_eq!(ring.allocate(10), Some(16));
+        assert_eq!(ring.allocate(16), Some(32));
+    }
+
+    #[test]
+    fn wraps_at_the_end() {
+        let mut ring = Ring::new(256);
+        assert_eq!(ring.allocate(100), Some(0));
+        assert_eq!(ring.allocate(100), Some(112));
+        // The first batch is done, but the second still uses the ring
+        ring.release(100, true);
+        // 224 + 64 is past the end, so it goes in the space the first batch freed
+        assert_eq!(ring.allocate(64), Some(0));
+        assert_eq!(ring.allocate(40), None);
+        assert_eq!(ring.allocate(32), Some(64));
+    }
+
+    #[test]
+    fn full_ring_needs_a_retired_batch() {
+        let mut ring = Ring::new(256);
+        assert_eq!(ring.allocate(128), Some(0));
+        assert_eq!(ring.allocate(128), Some(128));
+        assert_eq!(ring.allocate(1), None);
+        ring.release(128, true);
+        assert_eq!(ring.allocate(128), Some(0));
+        assert_eq!(ring.allocate(1), None);
+        // Once nothing uses it the whole ring is free again
+        ring.release(128, false);
+        assert_eq!(ring.allocate(256), Some(0));
+    }
+
+    #[test]
+    fn larger_than_the_ring_never_fits() {
+        let mut ring = Ring::new(256);
+        assert_eq!(ring.allocate(257), None);
+        // Even after failing, smaller allocations still start at the beginning
+        assert_eq!(ring.allocate(256), Some(0));
+    }
+}
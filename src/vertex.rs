@@ -0,0 +1,128 @@
+use ash::vk;
+use glam::{Vec2, Vec3};
+use memoffset::offset_of;
+use std::mem::size_of;
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct Vertex {
+    pub pos: glam::Vec3,
+    pub color: glam::Vec3,
+    pub tex_coord: glam::Vec2,
+    pub normal: glam::Vec3,
+}
+
+impl Vertex {
+    pub fn get_binding_description() -> vk::VertexInputBindingDescription {
+        vk::VertexInputBindingDescription {
+            binding: 0,
+            stride: size_of::<Vertex>() as u32,
+            input_rate: vk::VertexInputRate::VERTEX,
+        }
+    }
+    pub fn get_attribute_descriptions() -> [vk::VertexInputAttributeDescription; 4] {
+        [
+            vk::VertexInputAttributeDescription {
+                binding: 0,
+                location: 0,
+                format: vk::Format::R32G32B32_SFLOAT,
+                offset: offset_of!(Vertex, pos) as u32,
+            },
+            vk::VertexInputAttributeDescription {
+                binding: 0,
+                location: 1,
+                format: vk::Format::R32G32B32_SFLOAT,
+                offset: offset_of!(Vertex, color) as u32,
+            },
+            vk::VertexInputAttributeDescription {
+                binding: 0,
+                location: 2,
+                format: vk::Format::R32G32_SFLOAT,
+                offset: offset_of!(Vertex, tex_coord) as u32,
+            },
+            vk::VertexInputAttributeDescription {
+                binding: 0,
+                location: 3,
+                format: vk::Format::R32G32B32_SFLOAT,
+                offset: offset_of!(Vertex, normal) as u32,
+            },
+        ]
+    }
+}
+
+pub const VERTICES: [Vertex; 4] = [
+    Vertex {
+        pos: Vec3 {
+            x: -0.5,
+            y: -0.5,
+            z: 0.0,
+        },
+        color: Vec3 {
+            x: 1.0,
+            y: 0.0,
+            z: 0.0,
+        },
+        tex_coord: Vec2 { x: 1.0, y: 0.0 },
+        normal: Vec3 {
+            x: 0.0,
+            y: 0.0,
+            z: 1.0,
+        },
+    },
+    Vertex {
+        pos: Vec3 {
+            x: 0.5,
+            y: -0.5,
+            z: 0.0,
+        },
+        color: Vec3 {
+            x: 0.0,
+            y: 1.0,
+            z: 0.0,
+        },
+        tex_coord: Vec2 { x: 0.0, y: 0.0 },
+        normal: Vec3 {
+            x: 0.0,
+            y: 0.0,
+            z: 1.0,
+        },
+    },
+    Vertex {
+        pos: Vec3 {
+            x: 0.5,
+            y: 0.5,
+            z: 0.0,
+        },
+        color: Vec3 {
+            x: 0.0,
+            y: 0.0,
+            z: 1.0,
+        },
+        tex_coord: Vec2 { x: 0.0, y: 1.0 },
+        normal: Vec3 {
+            x: 0.0,
+            y: 0.0,
+            z: 1.0,
+        },
+    },
+    Vertex {
+        pos: Vec3 {
+            x: -0.5,
+            y: 0.5,
+            z: 0.0,
+        },
+        color: Vec3 {
+            x: 1.0,
+            y: 1.0,
+            z: 1.0,
+        },
+        tex_coord: Vec2 { x: 1.0, y: 1.0 },
+        normal: Vec3 {
+            x: 0.0,
+            y: 0.0,
+            z: 1.0,
+        },
+    },
+];
+
+pub const INDICES: [u16; 6] = [0, 1, 2, 2, 3, 0];
@@ -0,0 +1,219 @@
+// Renders each chapter's scene headlessly with a pinned clock and compares it against the reference
+// images in tests/golden. This needs the compiled shaders in shaders/ (build.rs makes them when glslc
+// is installed), the Khronos validation layer and a Vulkan driver, a software one such as lavapipe is
+// fine. Without a driver the tests are skipped. Any validation error fails the render.
+// Run with VULKANRUST_BLESS=1 to (re)generate the reference images.
+
+use ash::vk;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// Largest difference allowed in any channel of any pixel, to absorb rounding differences between drivers
+const TOLERANCE: u8 = 2;
+
+struct Image {
+    width: u32,
+    height: u32,
+    rgba: Vec<u8>,
+}
+
+fn read_png(path: &Path) -> Image {
+    let decoder = png::Decoder::new(File::open(path).unwrap());
+    let mut reader = decoder.read_info().unwrap();
+    let mut rgba = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut rgba).unwrap();
+    assert_eq!(info.color_type, png::ColorType::Rgba);
+    assert_eq!(info.bit_depth, png::BitDepth::Eight);
+    rgba.truncate(info.buffer_size());
+    Image {
+        width: info.width,
+        height: info.height,
+        rgba,
+    }
+}
+
+fn write_png(path: &Path, image: &Image) {
+    let file = File::create(path).unwrap();
+    let mut encoder = png::Encoder::new(BufWriter::new(file), image.width, image.height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().unwrap();
+    writer.write_image_data(&image.rgba).unwrap();
+}
+
+// Marks every pixel outside the tolerance in red on top of a faded copy of the reference
+fn diff_images(reference: &Image, actual: &Image) -> (usize, Image) {
+    let mut mismatched_pixels = 0;
+    let mut rgba = Vec::with_capacity(reference.rgba.len());
+    for (expected, found) in reference
+        .rgba
+        .chunks_exact(4)
+        .zip(actual.rgba.chunks_exact(4))
+    {
+        let mismatched = expected
+            .iter()
+            .zip(found)
+            .any(|(expected, found)| expected.abs_diff(*found) > TOLERANCE);
+        if mismatched {
+            mismatched_pixels += 1;
+            rgba.extend_from_slice(&[255, 0, 0, 255]);
+        } else {
+            let luma = (expected[0] as u32 + expected[1] as u32 + expected[2] as u32) / 3;
+            let faded = (luma / 4) as u8;
+            rgba.extend_from_slice(&[faded, faded, faded, 255]);
+        }
+    }
+    (
+        mismatched_pixels,
+        Image {
+            width: reference.width,
+            height: reference.height,
+            rgba,
+        },
+    )
+}
+
+// Whether the loader finds a driver with at least one device, the render can't work otherwise
+fn vulkan_device_available() -> bool {
+    let entry = ash::Entry::linked();
+    let app_info = vk::ApplicationInfo {
+        s_type: vk::StructureType::APPLICATION_INFO,
+        api_version: vk::API_VERSION_1_0,
+        ..Default::default()
+    };
+    let create_info = vk::InstanceCreateInfo {
+        s_type: vk::StructureType::INSTANCE_CREATE_INFO,
+        p_application_info: &app_info,
+        ..Default::default()
+    };
+    unsafe {
+        match entry.create_instance(&create_info, None) {
+            Ok(instance) => {
+                let found = instance
+                    .enumerate_physical_devices()
+                    .is_ok_and(|devices| !devices.is_empty());
+                instance.destroy_instance(None);
+                found
+            }
+            Err(_) => false,
+        }
+    }
+}
+
+// Renders with the main binary, extra_args pick the chapter's shaders
+fn check_scene(name: &str, time: f32, extra_args: &[&str]) {
+    if !vulkan_device_available() {
+        eprintln!("Skipping {}: no Vulkan driver or device was found", name);
+        return;
+    }
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    for shader in extra_args.iter().filter(|arg| arg.ends_with(".spv")) {
+        assert!(
+            manifest_dir.join(shader).exists(),
+            "{} is missing, install glslc and rebuild or run shaders/compile.sh",
+            shader
+        );
+    }
+    let output_dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("golden");
+    std::fs::create_dir_all(&output_dir).unwrap();
+    let actual_path = output_dir.join(format!("{}.actual.png", name));
+
+    let status = Command::new(env!("CARGO_BIN_EXE_vulkanrust"))
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .args([
+            "--headless",
+            "--fail-on-validation-error",
+            "--time",
+            &time.to_string(),
+            "--output",
+        ])
+        .arg(&actual_path)
+        .args(extra_args)
+        .status()
+        .unwrap();
+    assert!(
+        status.success(),
+        "Rendering {} failed with {}",
+        name,
+        status
+    );
+
+    let reference_path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("golden")
+        .join(format!("{}.png", name));
+    if std::env::var_os("VULKANRUST_BLESS").is_some() {
+        std::fs::create_dir_all(reference_path.parent().unwrap()).unwrap();
+        std::fs::copy(&actual_path, &reference_path).unwrap();
+        return;
+    }
+    assert!(
+        reference_path.exists(),
+        "No reference image at {}, run with VULKANRUST_BLESS=1 to create it",
+        reference_path.display()
+    );
+
+    let reference = read_png(&reference_path);
+    let actual = read_png(&actual_path);
+    assert_eq!(
+        (reference.width, reference.height),
+        (actual.width, actual.height),
+        "{} was rendered at the wrong size",
+        name
+    );
+
+    let (mismatched_pixels, diff) = diff_images(&reference, &actual);
+    if mismatched_pixels > 0 {
+        let diff_path = output_dir.join(format!("{}.diff.png", name));
+        write_png(&diff_path, &diff);
+        panic!(
+            "{} differs from its reference in {} pixels, see {} and {}",
+            name,
+            mismatched_pixels,
+            actual_path.display(),
+            diff_path.display()
+        );
+    }
+}
+
+// The hard-coded triangle of drawing.rs, graphicspipeline.rs builds the same pipeline but draws nothing
+#[test]
+fn drawing_triangle() {
+    check_scene(
+        "drawing_triangle",
+        0.0,
+        &[
+            "--vert-shader",
+            "shaders/triangle_vert.spv",
+            "--frag-shader",
+            "shaders/triangle_frag.spv",
+        ],
+    );
+}
+
+// The quad of vertexbuffer.rs, read from the vertex buffer without any transform
+#[test]
+fn vertexbuffer_quad() {
+    check_scene(
+        "vertexbuffer_quad",
+        0.0,
+        &[
+            "--vert-shader",
+            "shaders/vertexbuffer_vert.spv",
+            "--frag-shader",
+            "shaders/triangle_frag.spv",
+        ],
+    );
+}
+
+#[test]
+fn uniformbuffer_at_rest() {
+    check_scene("uniformbuffer_at_rest", 0.0, &[]);
+}
+
+#[test]
+fn uniformbuffer_rotated() {
+    check_scene("uniformbuffer_rotated", 0.5, &[]);
+}